@@ -0,0 +1,82 @@
+//! Standard base64, as used for byte strings.
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Encode `bytes` with `=` padding.
+pub(crate) fn encode(bytes: &[u8]) -> String {
+    let mut output = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let mut buffer = [0; 4];
+        buffer[1..=chunk.len()].copy_from_slice(chunk);
+        let bits = u32::from_be_bytes(buffer);
+
+        for i in 0..4 {
+            if i <= chunk.len() {
+                output.push(ALPHABET[(bits >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                output.push('=');
+            }
+        }
+    }
+
+    output
+}
+
+/// Decode `s`, with or without `=` padding. Returns `None` if it isn't valid base64.
+pub(crate) fn decode(s: &str) -> Option<Vec<u8>> {
+    let s = s.as_bytes();
+    let unpadded = match s {
+        [rest @ .., b'=', b'='] | [rest @ .., b'='] if s.len().is_multiple_of(4) => rest,
+        _ => s,
+    };
+    if unpadded.len() % 4 == 1 {
+        return None;
+    }
+
+    let mut output = Vec::with_capacity(unpadded.len() * 3 / 4);
+    for chunk in unpadded.chunks(4) {
+        let mut bits = 0u32;
+        for (i, &c) in chunk.iter().enumerate() {
+            let value = ALPHABET.iter().position(|&a| a == c)? as u32;
+            bits |= value << (18 - 6 * i);
+        }
+
+        let bytes = bits.to_be_bytes();
+        output.extend_from_slice(&bytes[1..chunk.len()]);
+    }
+
+    Some(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode, encode};
+
+    #[test]
+    fn test_encode() {
+        assert_eq!(encode(b""), "");
+        assert_eq!(encode(b"f"), "Zg==");
+        assert_eq!(encode(b"fo"), "Zm8=");
+        assert_eq!(encode(b"foo"), "Zm9v");
+        assert_eq!(encode(b"foob"), "Zm9vYg==");
+        assert_eq!(encode(&[0, 255, 63, 251]), "AP8/+w==");
+
+        let bytes: Vec<u8> = (0..=255).collect();
+        assert_eq!(decode(&encode(&bytes)).unwrap(), bytes);
+    }
+
+    #[test]
+    fn test_decode() {
+        assert_eq!(decode("").unwrap(), b"");
+        assert_eq!(decode("Zg==").unwrap(), b"f");
+        assert_eq!(decode("Zm8=").unwrap(), b"fo");
+        assert_eq!(decode("Zm9v").unwrap(), b"foo");
+        assert_eq!(decode("Zm9vYg").unwrap(), b"foob");
+        assert_eq!(decode("AP8/+w==").unwrap(), [0, 255, 63, 251]);
+
+        assert_eq!(decode("Zm9vY"), None);
+        assert_eq!(decode("Zm9v!"), None);
+        assert_eq!(decode("Zg="), None);
+        assert_eq!(decode("=Zg="), None);
+    }
+}
@@ -1,27 +1,234 @@
+use std::collections::HashMap;
+
+use std::fmt;
+use std::marker::PhantomData;
+
 use serde::de::{EnumAccess, IntoDeserializer, MapAccess, SeqAccess, VariantAccess, Visitor};
 use serde::{de, Deserialize};
 
 use crate::error::{Error, Result};
-use crate::parser;
+use crate::parser::{self, TotValue};
 
 // TODO July 17, 2023 Tim: integers are rounded when deserializing, check that this is okay
 
+/// Options for a [`Deserializer`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct DeserializerOptions {
+    strict: bool,
+    strict_integers: bool,
+    quoted_numbers: bool,
+    coerce_bools: bool,
+    max_length: Option<usize>,
+    max_collection_size: Option<usize>,
+}
+
+impl DeserializerOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reject keys that aren't fields of the struct being deserialized, as if every struct had
+    /// `#[serde(deny_unknown_fields)]`.
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Reject numbers with a fraction when deserializing an integer, instead of rounding them,
+    /// and numbers out of the range of an `i64` or `u64` (or negative ones for a `u64`), instead
+    /// of clamping them.
+    pub fn strict_integers(mut self, strict_integers: bool) -> Self {
+        self.strict_integers = strict_integers;
+        self
+    }
+
+    /// Also accept numbers written as strings, like `"3.14"`, when deserializing a number.
+    pub fn quoted_numbers(mut self, quoted_numbers: bool) -> Self {
+        self.quoted_numbers = quoted_numbers;
+        self
+    }
+
+    /// Also accept `1` and `on` as `true`, and `0` and `off` as `false`, when deserializing a
+    /// `bool`.
+    pub fn coerce_bools(mut self, coerce_bools: bool) -> Self {
+        self.coerce_bools = coerce_bools;
+        self
+    }
+
+    /// Reject input longer than `max_length` bytes before reading any of it.
+    ///
+    /// Only [`from_str_with_options`] and [`SeqDeserializer`] check this. A [`Deserializer`]
+    /// created with [`Deserializer::with_options`] reads whatever it's given, so check the length
+    /// before creating one.
+    pub fn max_length(mut self, max_length: usize) -> Self {
+        self.max_length = Some(max_length);
+        self
+    }
+
+    /// Reject lists with more than `max_collection_size` elements and dicts with more than that
+    /// many entries, as soon as the limit is passed.
+    pub fn max_collection_size(mut self, max_collection_size: usize) -> Self {
+        self.max_collection_size = Some(max_collection_size);
+        self
+    }
+}
+
+/// A dict key or list index on the way to the value being deserialized.
+#[derive(Debug)]
+enum PathSegment {
+    Key(String),
+    Index(usize),
+}
+
 #[derive(Debug)]
 pub struct Deserializer<'de> {
     input: &'de str,
+    /// Length of the input the deserializer was created with.
+    original_len: usize,
     depth: u64,
+    options: DeserializerOptions,
+    /// Keys and indices of the dict values and list elements being deserialized.
+    path: Vec<PathSegment>,
+    /// Whether the error being returned already names its path.
+    path_reported: bool,
+    /// Whether a [`TotValue`] is being deserialized, which keeps whole floats like `10.0` as
+    /// floats.
+    in_tot_value: bool,
+    warnings: Vec<Warning>,
+}
+
+/// A number that couldn't be deserialized exactly, see [`from_str_checked`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Warning {
+    /// Path to the number, like `server.ports[2]`, or empty at the root.
+    pub path: String,
+    /// What happened to it, like ``"`2.5` was rounded to 3"``.
+    pub message: String,
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.path.is_empty() {
+            true => f.write_str(&self.message),
+            false => write!(f, "{}: {}", self.path, self.message),
+        }
+    }
 }
 
 impl<'de> Deserializer<'de> {
+    #[allow(clippy::should_implement_trait)]
     pub fn from_str(input: &'de str) -> Self {
-        Deserializer { input, depth: 0 }
+        Self::with_options(input, DeserializerOptions::default())
+    }
+
+    pub fn with_options(input: &'de str, options: DeserializerOptions) -> Self {
+        Deserializer {
+            // A byte order mark counts as read
+            input: parser::strip_bom(input),
+            original_len: input.len(),
+            depth: 0,
+            options,
+            path: Vec::new(),
+            path_reported: false,
+            in_tot_value: false,
+            warnings: Vec::new(),
+        }
+    }
+
+    /// The numbers so far that were rounded, clamped or lost precision, in the order they were
+    /// read.
+    pub fn warnings(&self) -> &[Warning] {
+        &self.warnings
+    }
+
+    /// Number of bytes of the input read so far.
+    ///
+    /// ```
+    /// use serde::Deserialize;
+    ///
+    /// let input = "[1 2] rest";
+    /// let mut deserializer = tot::de::Deserializer::from_str(input);
+    /// let list = Vec::<u32>::deserialize(&mut deserializer).unwrap();
+    ///
+    /// assert_eq!(list, [1, 2]);
+    /// assert_eq!(&input[deserializer.bytes_consumed()..], "rest");
+    /// ```
+    pub fn bytes_consumed(&self) -> usize {
+        self.original_len - self.input.len()
+    }
+
+    /// Check that nothing but whitespace and comments is left in the input.
+    pub fn end(&mut self) -> Result<()> {
+        self.parse_ws()?;
+        if self.input.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::SerdeError("Input not empty".to_string()))
+        }
+    }
+
+    /// Deserialize the dict value or list element at `segment`. An error from it starts with the
+    /// path to the innermost value that failed, like `server.ports[2]: `.
+    fn deserialize_at<T>(&mut self, segment: PathSegment, seed: T) -> Result<T::Value>
+    where
+        T: de::DeserializeSeed<'de>,
+    {
+        self.path.push(segment);
+        self.path_reported = false;
+        let r = seed.deserialize(&mut *self).map_err(|e| match e {
+            Error::SerdeError(msg) if !self.path_reported => {
+                self.path_reported = true;
+                Error::SerdeError(format!("{}: {msg}", self.path_string()))
+            }
+            Error::ParserError(e) if !self.path_reported => {
+                self.path_reported = true;
+                Error::ParserError(e.with_path(&self.path_string()))
+            }
+            e => e,
+        });
+        self.path.pop();
+
+        r
+    }
+
+    /// Warn about the number read since the input was `before`.
+    fn warn(&mut self, before: &'de str, message: impl fmt::Display) {
+        let written = &before[..before.len() - self.input.len()];
+        self.warnings.push(Warning {
+            path: self.path_string(),
+            message: format!("`{written}` {message}"),
+        });
+    }
+
+    /// Warn if the number read since the input was `before` is an integer that `n` isn't
+    /// exactly.
+    fn warn_if_inexact(&mut self, before: &'de str, n: f64) {
+        let Ok((_, literal)) = parser::integer_literal(before) else {
+            return;
+        };
+        if literal.replace('_', "").parse::<i128>().ok() != Some(n as i128) {
+            self.warn(before, format_args!("lost precision as {n}"));
+        }
+    }
+
+    fn path_string(&self) -> String {
+        let mut path = String::new();
+        for segment in &self.path {
+            match segment {
+                PathSegment::Key(key) if path.is_empty() => path.push_str(key),
+                PathSegment::Key(key) => {
+                    path.push('.');
+                    path.push_str(key);
+                }
+                PathSegment::Index(i) => path.push_str(&format!("[{i}]")),
+            }
+        }
+
+        path
     }
 
     fn peek(&self) -> Result<char> {
-        self.input
-            .chars()
-            .next()
-            .ok_or(Error::SerdeError("eof".to_string()))
+        self.input.chars().next().ok_or(Error::Eof)
     }
 
     fn take(&mut self) -> Result<char> {
@@ -31,9 +238,89 @@ impl<'de> Deserializer<'de> {
         Ok(c)
     }
 
+    /// Type name of the value at the start of the input, as given by [`TotValue::type_name`].
+    fn peek_type(&self) -> Option<&'static str> {
+        let value = match self.peek().ok()? {
+            '[' => TotValue::List(Vec::new()),
+            '{' => TotValue::Dict(HashMap::new()),
+            _ => parser::unit(self.input)
+                .map(|_| TotValue::Unit)
+                .or_else(|_| parser::boolean(self.input).map(|(_, v)| TotValue::Boolean(v)))
+                .or_else(|_| parser::integer(self.input).map(|(_, v)| TotValue::Integer(v)))
+                .or_else(|_| parser::number(self.input).map(|(_, v)| TotValue::Float(v)))
+                .or_else(|_| parser::string(self.input).map(|(_, v)| TotValue::String(v)))
+                .or_else(|_| parser::bytes(self.input).map(|(_, v)| TotValue::Bytes(v)))
+                .ok()?,
+        };
+
+        Some(value.type_name())
+    }
+
+    /// Error for input that isn't the `expected` type of value.
+    fn type_error(&self, expected: &str) -> Error {
+        if self.input.is_empty() {
+            return Error::Eof;
+        }
+
+        match self.peek_type() {
+            Some(found) => Error::SerdeError(format!("expected {expected}, found {found}")),
+            None => {
+                let found = self.input.split(char::is_whitespace).next().unwrap_or("");
+                Error::SerdeError(format!("expected {expected}, found `{found}`"))
+            }
+        }
+    }
+
+    /// Error for input that isn't the `expected` string or key, which is a parser error if it
+    /// starts like a quoted string but isn't one.
+    fn string_error(&self, expected: &str) -> Error {
+        if !self.input.starts_with('"') {
+            return self.type_error(expected);
+        }
+
+        let found = self.input.lines().next().unwrap_or("");
+        parser::Error::StringError(format!("malformed string `{found}`")).into()
+    }
+
+    /// Whether the rest of the input is a single scalar, where a dict would have a key and value.
+    fn is_single_value(&self) -> bool {
+        parser::key(self.input)
+            .map(|(rem, _)| rem)
+            .or_else(|_| parser::bytes(self.input).map(|(rem, _)| rem))
+            .and_then(parser::all_ignored)
+            .is_ok_and(|(rem, _)| rem.is_empty())
+    }
+
+    /// Visit the source of the next value as a borrowed string, without reading it, for a
+    /// [`RawValue`]. At the root, that's the rest of the document.
+    fn deserialize_raw<V>(&mut self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        let raw = if self.depth < 1 {
+            std::mem::take(&mut self.input).trim_end()
+        } else {
+            let (rem, raw) =
+                parser::raw_value(self.input).map_err(|_| self.type_error("a value"))?;
+            self.input = rem;
+            raw
+        };
+
+        visitor.visit_borrowed_str(raw)
+    }
+
+    /// Visit the contents of a list or dict one level deeper, restoring the depth even if that
+    /// fails partway, so the deserializer can still be used after an error.
+    fn nested<T>(&mut self, visit: impl FnOnce(&mut Self) -> Result<T>) -> Result<T> {
+        self.depth += 1;
+        let r = visit(self);
+        self.depth -= 1;
+
+        r
+    }
+
     fn parse_ws(&mut self) -> Result<()> {
-        let (rem, _) =
-            parser::all_ignored(self.input).map_err(|e| Error::SerdeError(e.to_string()))?;
+        let (rem, _) = parser::all_ignored(self.input).map_err(|_| parser::Error::ParseError)?;
 
         self.input = rem;
 
@@ -41,7 +328,7 @@ impl<'de> Deserializer<'de> {
     }
 
     fn parse_unit(&mut self) -> Result<()> {
-        let (rem, _) = parser::unit(self.input).map_err(|e| Error::SerdeError(e.to_string()))?;
+        let (rem, _) = parser::unit(self.input).map_err(|_| self.type_error("null"))?;
 
         self.input = rem;
 
@@ -49,8 +336,18 @@ impl<'de> Deserializer<'de> {
     }
 
     fn parse_bool(&mut self) -> Result<bool> {
-        let (rem, par) =
-            parser::boolean(self.input).map_err(|e| Error::SerdeError(e.to_string()))?;
+        let mut parsed = parser::boolean(self.input);
+        if parsed.is_err() && self.options.coerce_bools {
+            parsed = parser::coerced_boolean(self.input);
+        }
+        let (rem, par) = parsed.map_err(|_| {
+            if self.input.is_empty() || self.peek_type().is_some() {
+                return self.type_error("bool");
+            }
+
+            let found = self.input.split(char::is_whitespace).next().unwrap_or("");
+            parser::Error::BoolError(format!("expected `true` or `false`, found `{found}`")).into()
+        })?;
 
         self.input = rem;
 
@@ -58,17 +355,87 @@ impl<'de> Deserializer<'de> {
     }
 
     fn parse_number(&mut self) -> Result<f64> {
-        let (rem, par) =
-            parser::number(self.input).map_err(|e| Error::SerdeError(e.to_string()))?;
+        if self.options.quoted_numbers && self.input.starts_with('"') {
+            let s = self.parse_string()?;
+            return match parser::number(&s) {
+                Ok(("", n)) => Ok(n),
+                _ => Err(Error::SerdeError(format!(
+                    "expected number, found string `{s}`"
+                ))),
+            };
+        }
+
+        let (rem, par) = parser::number(self.input).map_err(|_| self.type_error("number"))?;
 
         self.input = rem;
 
         Ok(par)
     }
 
+    /// Read a number into a `u64`, exactly if it's written as an integer and rounded otherwise.
+    fn parse_u64(&mut self) -> Result<u64> {
+        if let Ok((rem, literal)) = parser::integer_literal(self.input) {
+            if let Ok(v) = literal.replace('_', "").parse::<u64>() {
+                self.input = rem;
+                return Ok(v);
+            }
+        }
+
+        // `u64::MAX as f64` rounds up to 2^64, the first value that's out of range
+        self.parse_in_range(0.0..u64::MAX as f64).map(|n| n as u64)
+    }
+
+    fn parse_i64(&mut self) -> Result<i64> {
+        if let Ok((rem, literal)) = parser::integer_literal(self.input) {
+            if let Ok(v) = literal.replace('_', "").parse::<i64>() {
+                self.input = rem;
+                return Ok(v);
+            }
+        }
+
+        self.parse_in_range(i64::MIN as f64..-(i64::MIN as f64))
+            .map(|n| n as i64)
+    }
+
+    /// Read a rounded number for an integer that's in `range` if integers are strict. Otherwise
+    /// it's clamped to the range when it's cast.
+    fn parse_in_range(&mut self, range: std::ops::Range<f64>) -> Result<f64> {
+        let before = self.input;
+        let n = self.parse_rounded()?;
+        if !range.contains(&n) {
+            if self.options.strict_integers {
+                return Err(Error::SerdeError("integer out of range".to_string()));
+            }
+            self.warn(before, "is out of range and was clamped");
+        }
+
+        Ok(n)
+    }
+
+    /// Read a number for an integer, rounding it unless that's disallowed by the options.
+    fn parse_rounded(&mut self) -> Result<f64> {
+        let before = self.input;
+        let n = self.parse_number()?;
+        if n.fract() != 0.0 {
+            if self.options.strict_integers {
+                return Err(Error::SerdeError(format!("expected integer, found {n}")));
+            }
+            self.warn(before, format_args!("was rounded to {}", n.round()));
+        }
+
+        Ok(n.round())
+    }
+
     fn parse_string(&mut self) -> Result<String> {
-        let (rem, par) =
-            parser::string(self.input).map_err(|e| Error::SerdeError(e.to_string()))?;
+        let (rem, par) = parser::string(self.input).map_err(|_| self.string_error("string"))?;
+
+        self.input = rem;
+
+        Ok(par)
+    }
+
+    fn parse_bytes(&mut self) -> Result<Vec<u8>> {
+        let (rem, par) = parser::bytes(self.input).map_err(|_| self.type_error("bytes"))?;
 
         self.input = rem;
 
@@ -76,42 +443,383 @@ impl<'de> Deserializer<'de> {
     }
 
     fn parse_key(&mut self) -> Result<String> {
-        let (rem, par) = parser::key(self.input).map_err(|e| Error::SerdeError(e.to_string()))?;
+        let (rem, par) = parser::key(self.input).map_err(|_| self.string_error("key"))?;
 
         self.input = rem;
 
         Ok(par)
     }
+
+    /// Deserialize a dict in braces, or without them if it's the root. If `fields` is set, every
+    /// key must be one of them.
+    ///
+    /// A key can't start with `{`, so seeing one is enough to tell the two apart, however the
+    /// dict was reached (through a newtype or an `Option` at the root).
+    fn deserialize_dict<V>(
+        &mut self,
+        visitor: V,
+        fields: Option<&'static [&'static str]>,
+    ) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.parse_ws()?;
+        if !self.input.is_empty() && self.peek()? == '{' {
+            self.take()?;
+            let val = self.nested(|de| visitor.visit_map(Access::dict(de, fields, true)))?;
+
+            if self.take()? == '}' {
+                let _ = self.parse_ws();
+                Ok(val)
+            } else {
+                Err(Error::SerdeError("Expected dict end".to_string()))
+            }
+        } else if self.depth < 1 {
+            self.nested(|de| visitor.visit_map(Access::dict(de, fields, false)))
+        } else {
+            Err(self.type_error("dict"))
+        }
+    }
+}
+
+/// Name of the newtype struct that [`TotValue`] deserializes as, so this crate's deserializer
+/// can keep its floats as floats.
+const TOT_VALUE: &str = "$tot::private::TotValue";
+
+impl<'de> Deserialize<'de> for TotValue {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        deserializer.deserialize_newtype_struct(TOT_VALUE, TotValueVisitor)
+    }
+}
+
+struct TotValueVisitor;
+
+impl<'de> Visitor<'de> for TotValueVisitor {
+    type Value = TotValue;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("any Tot value")
+    }
+
+    fn visit_bool<E>(self, v: bool) -> std::result::Result<TotValue, E> {
+        Ok(TotValue::Boolean(v))
+    }
+
+    fn visit_i64<E>(self, v: i64) -> std::result::Result<TotValue, E> {
+        Ok(TotValue::Integer(v))
+    }
+
+    fn visit_u64<E>(self, v: u64) -> std::result::Result<TotValue, E> {
+        Ok(i64::try_from(v).map_or(TotValue::Float(v as f64), TotValue::Integer))
+    }
+
+    fn visit_f64<E>(self, v: f64) -> std::result::Result<TotValue, E> {
+        Ok(TotValue::Float(v))
+    }
+
+    fn visit_str<E>(self, v: &str) -> std::result::Result<TotValue, E> {
+        Ok(TotValue::String(v.to_string()))
+    }
+
+    fn visit_bytes<E>(self, v: &[u8]) -> std::result::Result<TotValue, E> {
+        Ok(TotValue::Bytes(v.to_vec()))
+    }
+
+    fn visit_byte_buf<E>(self, v: Vec<u8>) -> std::result::Result<TotValue, E> {
+        Ok(TotValue::Bytes(v))
+    }
+
+    fn visit_string<E>(self, v: String) -> std::result::Result<TotValue, E> {
+        Ok(TotValue::String(v))
+    }
+
+    fn visit_unit<E>(self) -> std::result::Result<TotValue, E> {
+        Ok(TotValue::Unit)
+    }
+
+    fn visit_none<E>(self) -> std::result::Result<TotValue, E> {
+        Ok(TotValue::Unit)
+    }
+
+    fn visit_some<D>(self, deserializer: D) -> std::result::Result<TotValue, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        TotValue::deserialize(deserializer)
+    }
+
+    fn visit_newtype_struct<D>(self, deserializer: D) -> std::result::Result<TotValue, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        deserializer.deserialize_any(self)
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> std::result::Result<TotValue, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let mut list = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(v) = seq.next_element()? {
+            list.push(v);
+        }
+
+        Ok(TotValue::List(list))
+    }
+
+    fn visit_map<A>(self, mut map: A) -> std::result::Result<TotValue, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let mut dict = HashMap::with_capacity(map.size_hint().unwrap_or(0));
+        while let Some((k, v)) = map.next_entry()? {
+            dict.insert(k, v);
+        }
+
+        Ok(TotValue::Dict(dict))
+    }
 }
 
 /// Try to deserialize a `str` into a `T`.
 pub fn from_str<'a, T>(s: &'a str) -> Result<T>
+where
+    T: Deserialize<'a>,
+{
+    from_str_with_options(s, DeserializerOptions::default())
+}
+
+/// Fail if `s` is longer than the `max_length` of `options`.
+fn check_length(s: &str, options: &DeserializerOptions) -> Result<()> {
+    match options.max_length {
+        Some(max) if s.len() > max => Err(Error::SerdeError(format!(
+            "input is longer than the maximum of {max} bytes"
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// Try to deserialize a `str` into a `T` with the given options.
+pub fn from_str_with_options<'a, T>(s: &'a str, options: DeserializerOptions) -> Result<T>
+where
+    T: Deserialize<'a>,
+{
+    check_length(s, &options)?;
+
+    let mut deserializer = Deserializer::with_options(s, options);
+    deserializer.parse_ws()?;
+    let t = T::deserialize(&mut deserializer)?;
+    deserializer.end()?;
+
+    Ok(t)
+}
+
+/// Try to deserialize a `str` into a `T`, along with a warning for every number that couldn't be
+/// deserialized exactly.
+///
+/// Numbers are rounded when read into an integer, clamped when they're out of its range, and
+/// integers that don't fit in an `f64` lose precision. This finds where that happened, like when
+/// auditing existing documents.
+///
+/// ```
+/// let (value, warnings) = tot::de::from_str_checked::<Vec<u64>>("[1 2.5 -3]").unwrap();
+///
+/// assert_eq!(value, [1, 3, 0]);
+/// assert_eq!(warnings[0].to_string(), "[1]: `2.5` was rounded to 3");
+/// assert_eq!(warnings[1].to_string(), "[2]: `-3` is out of range and was clamped");
+/// ```
+pub fn from_str_checked<'a, T>(s: &'a str) -> Result<(T, Vec<Warning>)>
+where
+    T: Deserialize<'a>,
+{
+    let mut deserializer = Deserializer::from_str(s);
+    deserializer.parse_ws()?;
+    let t = T::deserialize(&mut deserializer)?;
+    deserializer.end()?;
+
+    Ok((t, deserializer.warnings))
+}
+
+/// Try to deserialize a `T` from the start of a `str`, returning it with the rest of the input.
+///
+/// Whitespace, commas and comments after the value are skipped, so the rest starts at the next
+/// value, if any. A root dict reads the whole input as usual.
+///
+/// ```
+/// let (first, rest) = tot::de::from_str_partial::<bool>("true false").unwrap();
+/// let (second, rest) = tot::de::from_str_partial::<bool>(rest).unwrap();
+///
+/// assert!(first);
+/// assert!(!second);
+/// assert!(rest.is_empty());
+/// ```
+pub fn from_str_partial<'a, T>(s: &'a str) -> Result<(T, &'a str)>
 where
     T: Deserialize<'a>,
 {
     let mut deserializer = Deserializer::from_str(s);
+    deserializer.parse_ws()?;
     let t = T::deserialize(&mut deserializer)?;
-    if deserializer.input.is_empty() {
-        Ok(t)
-    } else {
-        Err(Error::SerdeError("Input not empty".to_string()))
+    deserializer.parse_ws()?;
+
+    Ok((t, deserializer.input))
+}
+
+/// Iterator over the elements of a list at the root of a document, deserializing each one only
+/// when it's reached instead of collecting them all.
+///
+/// Iteration stops after the first error.
+///
+/// ```
+/// let mut total = 0;
+/// for n in tot::de::SeqDeserializer::<u32>::from_str("[1 2 3]") {
+///     total += n.unwrap();
+/// }
+///
+/// assert_eq!(total, 6);
+/// ```
+pub struct SeqDeserializer<'de, T> {
+    de: Deserializer<'de>,
+    state: SeqState,
+    marker: PhantomData<T>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum SeqState {
+    Start,
+    Elements,
+    Done,
+}
+
+impl<'de, T> SeqDeserializer<'de, T> {
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(input: &'de str) -> Self {
+        Self::with_options(input, DeserializerOptions::default())
+    }
+
+    pub fn with_options(input: &'de str, options: DeserializerOptions) -> Self {
+        SeqDeserializer {
+            de: Deserializer::with_options(input, options),
+            state: SeqState::Start,
+            marker: PhantomData,
+        }
+    }
+
+    /// Read up to the next element, returning whether there is one.
+    fn advance(&mut self) -> Result<bool> {
+        if self.state == SeqState::Start {
+            check_length(self.de.input, &self.de.options)?;
+        }
+
+        self.de.parse_ws()?;
+        if self.state == SeqState::Start {
+            if self.de.input.is_empty() || self.de.peek()? != '[' {
+                return Err(self.de.type_error("list"));
+            }
+            self.de.take()?;
+            self.de.depth += 1;
+            self.de.parse_ws()?;
+            self.state = SeqState::Elements;
+        }
+
+        if self.de.peek()? != ']' {
+            return Ok(true);
+        }
+
+        self.de.take()?;
+        self.de.depth -= 1;
+        self.de.end()?;
+
+        Ok(false)
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Iterator for SeqDeserializer<'de, T> {
+    type Item = Result<T>;
+
+    fn next(&mut self) -> Option<Result<T>> {
+        if self.state == SeqState::Done {
+            return None;
+        }
+
+        let r = match self.advance() {
+            Ok(true) => T::deserialize(&mut self.de),
+            Ok(false) => {
+                self.state = SeqState::Done;
+                return None;
+            }
+            Err(e) => Err(e),
+        };
+        if r.is_err() {
+            self.state = SeqState::Done;
+        }
+
+        Some(r)
     }
 }
 
-impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
+/// Try to deserialize UTF-8 bytes into a `T`.
+pub fn from_slice<'a, T>(v: &'a [u8]) -> Result<T>
+where
+    T: Deserialize<'a>,
+{
+    from_str(std::str::from_utf8(v).map_err(Error::Utf8)?)
+}
+
+impl<'de> de::Deserializer<'de> for &mut Deserializer<'de> {
     type Error = Error;
 
     fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
+        self.parse_ws()?;
+
+        // The root is a dict without braces, unless it's a list or a single value
+        if self.depth < 1
+            && (self.input.is_empty() || (self.peek()? != '[' && !self.is_single_value()))
+        {
+            return self.deserialize_map(visitor);
+        }
+
         match self.peek()? {
+            'n' if parser::unit(self.input).is_ok() => self.deserialize_unit(visitor),
             't' | 'f' => self.deserialize_bool(visitor),
-            '0'..='9' | '-' => self.deserialize_f64(visitor),
+            '0'..='9' | '-' | '+' | '.' | 'i' | 'I' | 'n' | 'N' => {
+                if let Ok((rem, literal)) = parser::integer_literal(self.input) {
+                    let literal = literal.replace('_', "");
+                    if let Ok(v) = literal.parse::<u64>() {
+                        self.input = rem;
+                        return visitor.visit_u64(v);
+                    } else if let Ok(v) = literal.parse::<i64>() {
+                        self.input = rem;
+                        return visitor.visit_i64(v);
+                    }
+                }
+
+                let before = self.input;
+                let n = self.parse_number()?;
+                self.warn_if_inexact(before, n);
+
+                // Whole floats are visited as integers so they can be read as any number type,
+                // e.g. when buffered for an untagged enum. A `TotValue` keeps them as floats.
+                if self.in_tot_value || n.fract() != 0.0 || (n == 0.0 && n.is_sign_negative()) {
+                    visitor.visit_f64(n)
+                } else if n >= 0.0 && n < u64::MAX as f64 {
+                    visitor.visit_u64(n as u64)
+                } else if n < 0.0 && n >= i64::MIN as f64 {
+                    visitor.visit_i64(n as i64)
+                } else {
+                    visitor.visit_f64(n)
+                }
+            }
             '"' | '\'' => self.deserialize_str(visitor),
+            'b' => self.deserialize_byte_buf(visitor),
             '{' => self.deserialize_map(visitor),
             '[' => self.deserialize_seq(visitor),
-            _ => Err(Error::SerdeError("Syntax".to_string())),
+            _ => Err(self.type_error("a value")),
         }
     }
 
@@ -126,21 +834,21 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: de::Visitor<'de>,
     {
-        visitor.visit_i8(i8::try_from(self.parse_number()?.round() as i64)?)
+        visitor.visit_i8(i8::try_from(self.parse_i64()?)?)
     }
 
     fn deserialize_i16<V>(self, visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        visitor.visit_i16(i16::try_from(self.parse_number()?.round() as i64)?)
+        visitor.visit_i16(i16::try_from(self.parse_i64()?)?)
     }
 
     fn deserialize_i32<V>(self, visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        visitor.visit_i32(i32::try_from(self.parse_number()?.round() as i64)?)
+        visitor.visit_i32(i32::try_from(self.parse_i64()?)?)
     }
 
     // TODO: this less fallible than smaller integers because we do a raw cast to i64
@@ -148,50 +856,58 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: de::Visitor<'de>,
     {
-        visitor.visit_i64(self.parse_number()?.round() as i64)
+        visitor.visit_i64(self.parse_i64()?)
     }
 
     fn deserialize_u8<V>(self, visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        visitor.visit_u8(u8::try_from(self.parse_number()?.round() as u64)?)
+        visitor.visit_u8(u8::try_from(self.parse_u64()?)?)
     }
 
     fn deserialize_u16<V>(self, visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        visitor.visit_u16(u16::try_from(self.parse_number()?.round() as u64)?)
+        visitor.visit_u16(u16::try_from(self.parse_u64()?)?)
     }
 
     fn deserialize_u32<V>(self, visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        visitor.visit_u32(u32::try_from(self.parse_number()?.round() as u64)?)
+        visitor.visit_u32(u32::try_from(self.parse_u64()?)?)
     }
 
-    // TODO: this less fallible than smaller integers because we do a raw cast to u64
+    // TODO: this less fallible than smaller integers because non-integers are cast to u64
     fn deserialize_u64<V>(self, visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        visitor.visit_u64(self.parse_number()?.round() as u64)
+        visitor.visit_u64(self.parse_u64()?)
     }
 
     fn deserialize_f32<V>(self, visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        visitor.visit_f32(self.parse_number()? as f32)
+        let before = self.input;
+        let n = self.parse_number()? as f32;
+        self.warn_if_inexact(before, n.into());
+
+        visitor.visit_f32(n)
     }
 
     fn deserialize_f64<V>(self, visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        visitor.visit_f64(self.parse_number()?)
+        let before = self.input;
+        let n = self.parse_number()?;
+        self.warn_if_inexact(before, n);
+
+        visitor.visit_f64(n)
     }
 
     fn deserialize_char<V>(self, visitor: V) -> Result<V::Value>
@@ -205,7 +921,14 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: de::Visitor<'de>,
     {
-        visitor.visit_str(self.parse_string()?.as_str())
+        // Strings are borrowed unless escapes have to be decoded
+        match parser::borrowed_string(self.input) {
+            Ok((rem, s)) => {
+                self.input = rem;
+                visitor.visit_borrowed_str(s)
+            }
+            Err(_) => visitor.visit_string(self.parse_string()?),
+        }
     }
 
     fn deserialize_string<V>(self, visitor: V) -> Result<V::Value>
@@ -215,18 +938,28 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
         visitor.visit_string(self.parse_string()?)
     }
 
+    // Bytes are written as `b"..."`, but can also be read from a base64 string or a list of numbers
     fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        self.deserialize_seq(visitor)
+        self.deserialize_byte_buf(visitor)
     }
 
     fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        self.deserialize_seq(visitor)
+        match self.peek()? {
+            'b' => visitor.visit_byte_buf(self.parse_bytes()?),
+            '"' => {
+                let s = self.parse_string()?;
+                let bytes = crate::base64::decode(&s)
+                    .ok_or_else(|| Error::SerdeError(format!("invalid base64 string `{s}`")))?;
+                visitor.visit_byte_buf(bytes)
+            }
+            _ => self.deserialize_seq(visitor),
+        }
     }
 
     fn deserialize_option<V>(self, visitor: V) -> Result<V::Value>
@@ -245,7 +978,7 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: de::Visitor<'de>,
     {
-        let _ = self.parse_unit()?;
+        self.parse_unit()?;
         visitor.visit_unit()
     }
 
@@ -256,10 +989,20 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
         self.deserialize_unit(visitor)
     }
 
-    fn deserialize_newtype_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value>
+    fn deserialize_newtype_struct<V>(self, name: &'static str, visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
+        if name == RAW_VALUE {
+            return self.deserialize_raw(visitor);
+        }
+        if name == TOT_VALUE {
+            let in_tot_value = std::mem::replace(&mut self.in_tot_value, true);
+            let r = self.deserialize_any(visitor);
+            self.in_tot_value = in_tot_value;
+            return r;
+        }
+
         visitor.visit_newtype_struct(self)
     }
 
@@ -267,10 +1010,9 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: de::Visitor<'de>,
     {
-        if self.take()? == '[' {
-            self.depth += 1;
-            let val = visitor.visit_seq(Access::new(self))?;
-            self.depth -= 1;
+        if self.peek()? == '[' {
+            self.take()?;
+            let val = self.nested(|de| visitor.visit_seq(Access::new(de)))?;
             if self.take()? == ']' {
                 let _ = self.parse_ws();
                 Ok(val)
@@ -278,94 +1020,85 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
                 Err(Error::SerdeError("Expected array end".to_string()))
             }
         } else {
-            Err(Error::SerdeError("Expected array open".to_string()))
+            Err(self.type_error("list"))
         }
     }
 
-    fn deserialize_tuple<V>(self, _len: usize, visitor: V) -> Result<V::Value>
+    fn deserialize_tuple<V>(self, len: usize, visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        self.deserialize_seq(visitor)
-    }
-
+        if self.peek()? == '[' {
+            self.take()?;
+            let val = self.nested(|de| visitor.visit_seq(TupleAccess::new(de, len)))?;
+            if self.take()? == ']' {
+                let _ = self.parse_ws();
+                Ok(val)
+            } else {
+                Err(TupleAccess::length_error(len))
+            }
+        } else {
+            Err(self.type_error("list"))
+        }
+    }
+
     fn deserialize_tuple_struct<V>(
         self,
         _name: &'static str,
-        _len: usize,
+        len: usize,
         visitor: V,
     ) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        self.deserialize_seq(visitor)
+        self.deserialize_tuple(len, visitor)
     }
 
     fn deserialize_map<V>(self, visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        if self.depth < 1 {
-            self.depth += 1;
-            let val = visitor.visit_map(Access::new(self))?;
-            self.depth -= 1;
-
-            Ok(val)
-        } else {
-            if self.take()? == '{' {
-                self.depth += 1;
-                let val = visitor.visit_map(Access::new(self))?;
-                self.depth -= 1;
-
-                if self.take()? == '}' {
-                    let _ = self.parse_ws();
-                    Ok(val)
-                } else {
-                    Err(Error::SerdeError("Expected dict end".to_string()))
-                }
-            } else {
-                Err(Error::SerdeError("Expected dict open".to_string()))
-            }
-        }
+        self.deserialize_dict(visitor, None)
     }
 
     fn deserialize_struct<V>(
         self,
         _name: &'static str,
-        _fields: &'static [&'static str],
+        fields: &'static [&'static str],
         visitor: V,
     ) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        self.deserialize_map(visitor)
+        let fields = self.options.strict.then_some(fields);
+        self.deserialize_dict(visitor, fields)
     }
 
     // TODO logic is mostly the same as map except for the visit method call. maybe pass function pointer?
+    /// Enums are read as follows:
+    ///
+    /// * A quoted string (`"Name"`) is always a unit variant.
+    /// * A bare variant name followed by a value (`Name value`) is a variant with a payload.
+    ///   Nested inside another value, the pair is wrapped in braces (`{ Name value }`).
     fn deserialize_enum<V>(
         self,
         _name: &'static str,
-        _variants: &'static [&'static str],
+        variants: &'static [&'static str],
         visitor: V,
     ) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
         if self.peek()? == '"' {
-            return visitor.visit_enum(self.parse_string()?.into_deserializer());
+            return visitor.visit_enum(UnitVariantAccess(self.parse_string()?, variants));
         }
 
         if self.depth < 1 {
-            self.depth += 1;
-            let val = visitor.visit_enum(Access::new(self))?;
-            self.depth -= 1;
-
-            Ok(val)
+            self.nested(|de| visitor.visit_enum(Access::dict(de, Some(variants), false)))
         } else {
             if self.take()? == '{' {
-                self.depth += 1;
-                let val = visitor.visit_enum(Access::new(self))?;
-                self.depth -= 1;
+                let val =
+                    self.nested(|de| visitor.visit_enum(Access::dict(de, Some(variants), false)))?;
 
                 if self.take()? == '}' {
                     Ok(val)
@@ -393,6 +1126,85 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
     }
 }
 
+/// Name of the newtype struct that [`RawValue`] deserializes as.
+const RAW_VALUE: &str = "$tot::private::RawValue";
+
+/// A value kept as the source it was written as, to be read later or passed on as is.
+///
+/// When deserialized by this crate, this captures the source of the next value, comments and
+/// all, without reading it. At the root, it's the whole document. Other deserializers can only
+/// provide it as a string.
+///
+/// ```
+/// use serde::Deserialize;
+/// use tot::de::RawValue;
+///
+/// #[derive(Deserialize)]
+/// struct Plugin {
+///     name: String,
+///     settings: RawValue,
+/// }
+///
+/// let plugin = tot::from_str::<Plugin>("name \"cache\"\nsettings { size 64 }\n").unwrap();
+///
+/// assert_eq!(plugin.settings.get(), "{ size 64 }");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RawValue(String);
+
+impl RawValue {
+    /// The source of the value.
+    pub fn get(&self) -> &str {
+        &self.0
+    }
+
+    pub fn into_string(self) -> String {
+        self.0
+    }
+
+    /// Deserialize the value into a `T`.
+    pub fn deserialize_into<'a, T: Deserialize<'a>>(&'a self) -> Result<T> {
+        from_str(&self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for RawValue {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        struct RawValueVisitor;
+
+        impl<'de> Visitor<'de> for RawValueVisitor {
+            type Value = RawValue;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("the source of a value")
+            }
+
+            fn visit_str<E>(self, v: &str) -> std::result::Result<RawValue, E> {
+                Ok(RawValue(v.to_string()))
+            }
+
+            fn visit_string<E>(self, v: String) -> std::result::Result<RawValue, E> {
+                Ok(RawValue(v))
+            }
+
+            fn visit_newtype_struct<D>(
+                self,
+                deserializer: D,
+            ) -> std::result::Result<RawValue, D::Error>
+            where
+                D: de::Deserializer<'de>,
+            {
+                String::deserialize(deserializer).map(RawValue)
+            }
+        }
+
+        deserializer.deserialize_newtype_struct(RAW_VALUE, RawValueVisitor)
+    }
+}
+
 struct KeyDeserializer<'a, 'de: 'a> {
     de: &'a mut Deserializer<'de>,
 }
@@ -410,7 +1222,7 @@ impl<'a, 'de> de::Deserializer<'de> for &'a mut KeyDeserializer<'a, 'de> {
     where
         V: de::Visitor<'de>,
     {
-        self.de.deserialize_any(visitor)
+        visitor.visit_string(self.de.parse_key()?)
     }
 
     fn deserialize_bool<V>(self, visitor: V) -> Result<V::Value>
@@ -501,7 +1313,13 @@ impl<'a, 'de> de::Deserializer<'de> for &'a mut KeyDeserializer<'a, 'de> {
     where
         V: de::Visitor<'de>,
     {
-        visitor.visit_str(self.de.parse_key()?.as_str())
+        match parser::borrowed_key(self.de.input) {
+            Ok((rem, s)) => {
+                self.de.input = rem;
+                visitor.visit_borrowed_str(s)
+            }
+            Err(_) => visitor.visit_string(self.de.parse_key()?),
+        }
     }
 
     fn deserialize_string<V>(self, visitor: V) -> Result<V::Value>
@@ -598,16 +1416,17 @@ impl<'a, 'de> de::Deserializer<'de> for &'a mut KeyDeserializer<'a, 'de> {
         self.de.deserialize_struct(name, fields, visitor)
     }
 
+    /// A key names a unit variant, quoted or not, like `Red` in a `BTreeMap<Color, u32>`.
     fn deserialize_enum<V>(
         self,
-        name: &'static str,
+        _name: &'static str,
         variants: &'static [&'static str],
         visitor: V,
     ) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        self.de.deserialize_enum(name, variants, visitor)
+        visitor.visit_enum(UnitVariantAccess(self.de.parse_key()?, variants))
     }
 
     fn deserialize_identifier<V>(self, visitor: V) -> Result<V::Value>
@@ -627,11 +1446,43 @@ impl<'a, 'de> de::Deserializer<'de> for &'a mut KeyDeserializer<'a, 'de> {
 
 struct Access<'a, 'de: 'a> {
     de: &'a mut Deserializer<'de>,
+    /// Keys allowed in a dict, or the variants of an enum.
+    fields: Option<&'static [&'static str]>,
+    /// Index of the next list element, or number of dict entries read.
+    index: usize,
+    /// The dict key just read.
+    key: Option<String>,
+    /// Whether the dict is in braces, so it ends at `}` rather than the end of the input.
+    braced: bool,
 }
 
 impl<'a, 'de> Access<'a, 'de> {
     fn new(de: &'a mut Deserializer<'de>) -> Self {
-        Access { de }
+        Self::dict(de, None, false)
+    }
+
+    fn dict(
+        de: &'a mut Deserializer<'de>,
+        fields: Option<&'static [&'static str]>,
+        braced: bool,
+    ) -> Self {
+        Access {
+            de,
+            fields,
+            index: 0,
+            key: None,
+            braced,
+        }
+    }
+
+    /// Check that another element or entry is within the maximum collection size.
+    fn check_size(&self, items: &str) -> Result<()> {
+        match self.de.options.max_collection_size {
+            Some(max) if self.index >= max => {
+                Err(Error::SerdeError(format!("more than {max} {items}")))
+            }
+            _ => Ok(()),
+        }
     }
 }
 
@@ -646,12 +1497,75 @@ impl<'de, 'a> SeqAccess<'de> for Access<'a, 'de> {
         if self.de.peek()? == ']' {
             return Ok(None);
         }
-        let r = seed.deserialize(&mut *self.de).map(Some);
-        if r.is_ok() {
-            self.de.parse_ws()?;
+        self.check_size("elements")?;
+
+        // A failed element is left unread
+        let start = self.de.input;
+        let v = self
+            .de
+            .deserialize_at(PathSegment::Index(self.index), seed)
+            .inspect_err(|_| self.de.input = start)?;
+        self.index += 1;
+        self.de.parse_ws()?;
+
+        // Elements have to be separated, like in the parser. A nested list or dict has already
+        // skipped what follows it, so look at how what was read ends: only whitespace, a comma or
+        // a comment (ending in `/`) can end it, and no value does.
+        let read = &start[..start.len() - self.de.input.len()];
+        let separated = read.ends_with(|c: char| c.is_whitespace() || c == ',' || c == '/');
+        if !separated && !self.de.input.is_empty() && self.de.peek()? != ']' {
+            return Err(Error::SerdeError(
+                "expected whitespace or a comma between list elements".to_string(),
+            ));
         }
 
-        r
+        Ok(Some(v))
+    }
+}
+
+/// Reads the elements of a tuple, erroring if the list is shorter than the tuple.
+struct TupleAccess<'a, 'de: 'a> {
+    access: Access<'a, 'de>,
+    remaining: usize,
+    len: usize,
+}
+
+impl<'a, 'de> TupleAccess<'a, 'de> {
+    fn new(de: &'a mut Deserializer<'de>, len: usize) -> Self {
+        TupleAccess {
+            access: Access::new(de),
+            remaining: len,
+            len,
+        }
+    }
+
+    fn length_error(len: usize) -> Error {
+        Error::SerdeError(format!("expected tuple of length {len}"))
+    }
+}
+
+impl<'de, 'a> SeqAccess<'de> for TupleAccess<'a, 'de> {
+    type Error = Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
+    where
+        T: de::DeserializeSeed<'de>,
+    {
+        if self.remaining == 0 {
+            return Ok(None);
+        }
+
+        match self.access.next_element_seed(seed)? {
+            Some(v) => {
+                self.remaining -= 1;
+                Ok(Some(v))
+            }
+            None => Err(Self::length_error(self.len)),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.remaining)
     }
 }
 
@@ -663,15 +1577,27 @@ impl<'de, 'a> MapAccess<'de> for Access<'a, 'de> {
         K: de::DeserializeSeed<'de>,
     {
         self.de.parse_ws()?;
-        if self.de.depth > 1 && self.de.peek()? == '}' {
+        if self.braced && self.de.peek()? == '}' {
+            return Ok(None);
+        }
+        if self.de.input.is_empty() {
             return Ok(None);
         }
+        self.check_size("entries")?;
+        self.index += 1;
+
+        self.key = parser::key(self.de.input).ok().map(|(_, key)| key);
+        if let (Some(fields), Some(key)) = (self.fields, &self.key) {
+            if !fields.contains(&key.as_str()) {
+                return Err(de::Error::unknown_field(key, fields));
+            }
+        }
         let r = seed
-            .deserialize(&mut KeyDeserializer::new(&mut self.de))
+            .deserialize(&mut KeyDeserializer::new(self.de))
             .map(Some);
         if r.is_ok() {
             self.de.parse_ws()?;
-        } else if self.de.depth < 2 {
+        } else if !self.braced {
             // We ran out of keys to parse
             let _ = self.de.parse_ws();
             return Ok(None);
@@ -685,7 +1611,8 @@ impl<'de, 'a> MapAccess<'de> for Access<'a, 'de> {
         V: de::DeserializeSeed<'de>,
     {
         self.de.parse_ws()?;
-        let r = seed.deserialize(&mut *self.de);
+        let key = self.key.take().unwrap_or_default();
+        let r = self.de.deserialize_at(PathSegment::Key(key), seed);
         if r.is_ok() {
             self.de.parse_ws()?;
         }
@@ -703,17 +1630,132 @@ impl<'de, 'a> EnumAccess<'de> for Access<'a, 'de> {
         V: de::DeserializeSeed<'de>,
     {
         self.de.parse_ws()?;
-        let val = seed.deserialize(&mut *self.de)?;
+        let before = self.de.input;
+        let val = seed.deserialize(&mut *self.de).map_err(|e| {
+            match (parser::key(before), self.fields) {
+                (Ok((_, name)), Some(variants)) => suggest_variant(e, &name, variants),
+                _ => e,
+            }
+        })?;
 
         Ok((val, self))
     }
 }
 
+/// Add the closest of `variants` to an error from reading the unknown variant `name`, if one is
+/// close enough to be a likely typo.
+fn suggest_variant(e: Error, name: &str, variants: &[&str]) -> Error {
+    let Error::SerdeError(msg) = e else {
+        return e;
+    };
+    if variants.contains(&name) {
+        return Error::SerdeError(msg);
+    }
+
+    // Differing in case, or in up to a third of the characters
+    let max_distance = name.chars().count().max(3) / 3;
+    let closest = variants
+        .iter()
+        .map(|v| match v.eq_ignore_ascii_case(name) {
+            true => (0, v),
+            false => (edit_distance(name, v), v),
+        })
+        .filter(|&(distance, _)| distance <= max_distance)
+        .min_by_key(|&(distance, _)| distance);
+
+    match closest {
+        Some((_, v)) => Error::SerdeError(format!("{msg}; did you mean `{v}`?")),
+        None => Error::SerdeError(msg),
+    }
+}
+
+/// The number of characters to insert, remove or replace, or pairs of neighbouring characters to
+/// swap, to turn `a` into `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let (a, b): (Vec<char>, Vec<char>) = (a.chars().collect(), b.chars().collect());
+    let mut d = vec![vec![0; b.len() + 1]; a.len() + 1];
+    d[0] = (0..=b.len()).collect();
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let replaced = d[i - 1][j - 1] + usize::from(a[i - 1] != b[j - 1]);
+            d[i][j] = replaced.min(d[i - 1][j] + 1).min(d[i][j - 1] + 1);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+
+    d[a.len()][b.len()]
+}
+
+/// Reads a variant written as just its name, which is a unit variant or a newtype variant of unit.
+/// Also holds the enum's variants, to suggest one if the name isn't among them.
+struct UnitVariantAccess(String, &'static [&'static str]);
+
+impl<'de> EnumAccess<'de> for UnitVariantAccess {
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self::Variant)>
+    where
+        V: de::DeserializeSeed<'de>,
+    {
+        let variant: de::value::StrDeserializer<Error> = self.0.as_str().into_deserializer();
+        let val = seed
+            .deserialize(variant)
+            .map_err(|e| suggest_variant(e, &self.0, self.1))?;
+
+        Ok((val, self))
+    }
+}
+
+impl<'de> VariantAccess<'de> for UnitVariantAccess {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<()> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value>
+    where
+        T: de::DeserializeSeed<'de>,
+    {
+        let unit: de::value::UnitDeserializer<Error> = ().into_deserializer();
+        seed.deserialize(unit)
+    }
+
+    fn tuple_variant<V>(self, _len: usize, _visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        Err(de::Error::invalid_type(
+            de::Unexpected::UnitVariant,
+            &"tuple variant",
+        ))
+    }
+
+    fn struct_variant<V>(self, _fields: &'static [&'static str], _visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        Err(de::Error::invalid_type(
+            de::Unexpected::UnitVariant,
+            &"struct variant",
+        ))
+    }
+}
+
 impl<'de, 'a> VariantAccess<'de> for Access<'a, 'de> {
     type Error = Error;
 
     fn unit_variant(self) -> Result<()> {
-        Err(Error::SerdeError("Expected string".to_string()))
+        Err(Error::SerdeError(
+            "Expected unit variant to be a quoted string".to_string(),
+        ))
     }
 
     fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value>
@@ -730,33 +1772,36 @@ impl<'de, 'a> VariantAccess<'de> for Access<'a, 'de> {
         val
     }
 
-    fn tuple_variant<V>(self, _len: usize, visitor: V) -> Result<V::Value>
+    fn tuple_variant<V>(self, len: usize, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
         self.de.parse_ws()?;
-        de::Deserializer::deserialize_seq(self.de, visitor)
+        de::Deserializer::deserialize_tuple(self.de, len, visitor)
     }
 
-    fn struct_variant<V>(self, _fields: &'static [&'static str], visitor: V) -> Result<V::Value>
+    fn struct_variant<V>(self, fields: &'static [&'static str], visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
         self.de.parse_ws()?;
-        de::Deserializer::deserialize_map(self.de, visitor)
+        de::Deserializer::deserialize_struct(self.de, "", fields, visitor)
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{from_str, Deserializer};
+    use super::{
+        edit_distance, from_slice, from_str, from_str_checked, from_str_partial,
+        from_str_with_options, Deserializer, DeserializerOptions, RawValue, SeqDeserializer,
+    };
     use serde::Deserialize;
     use std::collections::HashMap;
 
     mod deserializer_tests {
         use super::*;
 
-        fn de(i: &str) -> Deserializer {
+        fn de(i: &str) -> Deserializer<'_> {
             Deserializer::from_str(i)
         }
 
@@ -770,7 +1815,7 @@ mod tests {
 
             de.parse_ws().unwrap();
 
-            assert_eq!(de.parse_bool().unwrap(), true);
+            assert!(de.parse_bool().unwrap());
         }
 
         #[test]
@@ -782,7 +1827,7 @@ mod tests {
         #[test]
         fn test_boolean() {
             let mut de = de("true");
-            assert_eq!(de.parse_bool().unwrap(), true);
+            assert!(de.parse_bool().unwrap());
         }
 
         #[test]
@@ -801,6 +1846,207 @@ mod tests {
     mod de_tests {
         use super::*;
 
+        #[test]
+        fn test_de_type_mismatch() {
+            #[derive(Deserialize, Debug)]
+            #[allow(dead_code)]
+            struct Test {
+                flag: bool,
+                count: i32,
+                items: Vec<i32>,
+            }
+
+            let error = |i| from_str::<Test>(i).unwrap_err().to_string();
+
+            assert_eq!(
+                error("flag \"yes\" count 1 items []"),
+                "serde error: flag: expected bool, found string"
+            );
+            assert_eq!(
+                error("flag true count [1] items []"),
+                "serde error: count: expected number, found list"
+            );
+            assert_eq!(
+                error("flag true count 1 items { a 1 }"),
+                "serde error: items: expected list, found dict"
+            );
+            assert_eq!(
+                error("flag true count null items []"),
+                "serde error: count: expected number, found null"
+            );
+            assert_eq!(
+                from_str::<String>("1.5").unwrap_err().to_string(),
+                "serde error: expected string, found number"
+            );
+            assert_eq!(
+                from_str::<Vec<bool>>("[false]x").unwrap_err().to_string(),
+                "serde error: Input not empty"
+            );
+        }
+
+        #[test]
+        fn test_de_strict() {
+            #[derive(Deserialize, Debug, PartialEq)]
+            struct Inner {
+                a: i32,
+            }
+
+            #[derive(Deserialize, Debug, PartialEq)]
+            struct Test {
+                name: String,
+                inner: Inner,
+                #[serde(default)]
+                map: HashMap<String, i32>,
+            }
+
+            #[derive(Deserialize, Debug)]
+            #[serde(deny_unknown_fields)]
+            #[allow(dead_code)]
+            struct Denied {
+                name: String,
+            }
+
+            let strict = DeserializerOptions::new().strict(true);
+
+            let input = "name \"x\"\ninner { a 1 b 2 }\n1 true";
+            assert!(from_str::<Test>(input).is_ok());
+            assert_eq!(
+                from_str_with_options::<Test>(input, strict)
+                    .unwrap_err()
+                    .to_string(),
+                "serde error: inner: unknown field `b`, expected `a`"
+            );
+            assert_eq!(
+                from_str_with_options::<Test>("name \"x\"\ninner { a 1 }\n1 true", strict)
+                    .unwrap_err()
+                    .to_string(),
+                "serde error: unknown field `1`, expected one of `name`, `inner`, `map`"
+            );
+
+            // Maps still allow any key
+            assert_eq!(
+                from_str_with_options::<Test>("name \"x\" inner { a 1 } map { k 2 }", strict)
+                    .unwrap(),
+                Test {
+                    name: "x".to_string(),
+                    inner: Inner { a: 1 },
+                    map: HashMap::from([("k".to_string(), 2)]),
+                }
+            );
+
+            // deny_unknown_fields is respected without strict mode
+            assert!(from_str::<Denied>("name \"x\" other 1").is_err());
+        }
+
+        #[test]
+        fn test_de_tot_value() {
+            let input = "\
+a 1
+b [true null \"x\"]
+c {
+    d -2.5
+}
+";
+            assert_eq!(
+                from_str::<crate::TotValue>(input).unwrap(),
+                crate::parser::parse(input).unwrap()
+            );
+            assert_eq!(
+                from_str::<crate::TotValue>("[1 2]").unwrap(),
+                crate::TotValue::List(vec![
+                    crate::TotValue::Integer(1),
+                    crate::TotValue::Integer(2)
+                ])
+            );
+        }
+
+        #[test]
+        fn test_de_flatten() {
+            use crate::TotValue;
+
+            #[derive(Deserialize, Debug, PartialEq)]
+            struct Inner {
+                name: String,
+                #[serde(flatten)]
+                extra: HashMap<String, TotValue>,
+            }
+
+            #[derive(Deserialize, Debug, PartialEq)]
+            struct Test {
+                count: i32,
+                inner: Inner,
+                #[serde(flatten)]
+                extra: HashMap<String, TotValue>,
+            }
+
+            let test = from_str::<Test>(
+                "\
+count 3
+unknown \"kept\"
+inner {
+    name \"x\"
+    list [1 2]
+}
+other {
+    flag true
+}
+",
+            )
+            .unwrap();
+
+            assert_eq!(test.count, 3);
+            assert_eq!(test.inner.name, "x");
+            assert_eq!(
+                test.inner.extra,
+                HashMap::from([(
+                    "list".to_string(),
+                    TotValue::List(vec![TotValue::Integer(1), TotValue::Integer(2)])
+                )])
+            );
+            assert_eq!(
+                test.extra,
+                HashMap::from([
+                    ("unknown".to_string(), TotValue::String("kept".to_string())),
+                    (
+                        "other".to_string(),
+                        TotValue::Dict(HashMap::from([(
+                            "flag".to_string(),
+                            TotValue::Boolean(true)
+                        )]))
+                    ),
+                ])
+            );
+
+            // A struct with a flattened field is buffered, which still reads whole floats as
+            // integers
+            assert_eq!(
+                from_str::<Test>("count 3.0 inner { name \"x\" }")
+                    .unwrap()
+                    .count,
+                3
+            );
+        }
+
+        #[test]
+        fn test_de_eof() {
+            assert!(matches!(from_str::<f64>(""), Err(crate::Error::Eof)));
+            assert!(matches!(from_str::<bool>(""), Err(crate::Error::Eof)));
+            assert!(matches!(
+                from_str::<Option<f64>>(""),
+                Err(crate::Error::Eof)
+            ));
+            assert!(matches!(from_str::<Vec<f64>>(""), Err(crate::Error::Eof)));
+            assert!(matches!(
+                from_str::<Vec<f64>>("[1.0"),
+                Err(crate::Error::Eof)
+            ));
+
+            assert!(matches!(
+                from_str::<f64>("x"),
+                Err(crate::Error::SerdeError(_))
+            ));
+        }
+
         #[test]
         fn test_de_unit() {
             assert!(from_str::<()>("null").is_ok());
@@ -809,10 +2055,58 @@ mod tests {
 
         #[test]
         fn test_de_bool() {
-            assert_eq!(from_str::<bool>("true").unwrap(), true);
-            assert_eq!(from_str::<bool>("false").unwrap(), false);
+            assert!(from_str::<bool>("true").unwrap());
+            assert!(!from_str::<bool>("false").unwrap());
 
             assert!(from_str::<bool>("1.0").is_err());
+
+            let err = from_str::<bool>("truex").unwrap_err();
+            assert!(matches!(
+                err,
+                crate::Error::ParserError(crate::parser::Error::BoolError(_))
+            ));
+            assert_eq!(
+                err.to_string(),
+                "parser error: bool error: expected `true` or `false`, found `truex`"
+            );
+
+            assert_eq!(
+                from_str::<Vec<bool>>("[false, true]").unwrap(),
+                vec![false, true]
+            );
+        }
+
+        #[test]
+        fn test_de_coerce_bools() {
+            let coerce = DeserializerOptions::new().coerce_bools(true);
+
+            for (input, expected) in [
+                ("1", true),
+                ("on", true),
+                ("true", true),
+                ("0", false),
+                ("off", false),
+                ("false", false),
+            ] {
+                assert_eq!(
+                    from_str_with_options::<bool>(input, coerce).unwrap(),
+                    expected
+                );
+                assert_eq!(from_str::<bool>(input).is_ok(), input.ends_with('e'));
+            }
+
+            assert_eq!(
+                from_str_with_options::<Vec<bool>>("[1 off, on 0]", coerce).unwrap(),
+                [true, false, true, false]
+            );
+            for input in ["10", "1.0", "2", "onward", "\"on\""] {
+                assert!(
+                    from_str_with_options::<bool>(input, coerce).is_err(),
+                    "{input}"
+                );
+            }
+            // Only bools are coerced
+            assert_eq!(from_str_with_options::<u8>("1", coerce).unwrap(), 1);
         }
 
         mod ints {
@@ -878,6 +2172,126 @@ mod tests {
                 assert!(from_str::<i64>("true").is_err());
             }
 
+            #[test]
+            fn test_de_strict_integers() {
+                let strict = DeserializerOptions::new().strict_integers(true);
+
+                assert_eq!(from_str::<i32>("5.9").unwrap(), 6);
+                assert_eq!(from_str::<u64>("5.9").unwrap(), 6);
+                assert_eq!(
+                    from_str_with_options::<i32>("5.9", strict)
+                        .unwrap_err()
+                        .to_string(),
+                    "serde error: expected integer, found 5.9"
+                );
+                assert!(from_str_with_options::<u64>("5.9", strict).is_err());
+                assert!(from_str_with_options::<i8>("-0.5", strict).is_err());
+
+                assert_eq!(from_str_with_options::<i32>("5", strict).unwrap(), 5);
+                assert_eq!(from_str_with_options::<i32>("5.0", strict).unwrap(), 5);
+                assert_eq!(from_str_with_options::<f64>("5.9", strict).unwrap(), 5.9);
+            }
+
+            #[test]
+            fn test_de_size_limits() {
+                #[derive(Deserialize, Debug)]
+                #[allow(dead_code)]
+                struct Test {
+                    list: Vec<u32>,
+                    map: HashMap<String, u32>,
+                }
+
+                let options = DeserializerOptions::new().max_collection_size(2);
+                let error = |i| {
+                    from_str_with_options::<Test>(i, options)
+                        .unwrap_err()
+                        .to_string()
+                };
+
+                assert!(
+                    from_str_with_options::<Test>("list [1 2] map { a 1 b 2 }", options).is_ok()
+                );
+                assert_eq!(
+                    error("list [1 2 3] map {}"),
+                    "serde error: list: more than 2 elements"
+                );
+                assert_eq!(
+                    error("list [] map { a 1 b 2 c 3 }"),
+                    "serde error: map: more than 2 entries"
+                );
+                assert_eq!(
+                    error("list [] map {} extra 1"),
+                    "serde error: more than 2 entries"
+                );
+
+                let options = DeserializerOptions::new().max_length(8);
+                assert_eq!(
+                    from_str_with_options::<String>("\"123456\"", options).unwrap(),
+                    "123456"
+                );
+                assert_eq!(
+                    from_str_with_options::<String>("\"1234567\"", options)
+                        .unwrap_err()
+                        .to_string(),
+                    "serde error: input is longer than the maximum of 8 bytes"
+                );
+
+                let items = SeqDeserializer::<u32>::with_options("[1 2 3 4]", options);
+                assert_eq!(
+                    items
+                        .map(|r| r.map_err(|e| e.to_string()))
+                        .collect::<Vec<_>>(),
+                    [Err(
+                        "serde error: input is longer than the maximum of 8 bytes".to_string()
+                    )]
+                );
+                let items = SeqDeserializer::<u32>::with_options("[1 2 3]", options);
+                assert_eq!(items.map(Result::unwrap).sum::<u32>(), 6);
+            }
+
+            #[test]
+            fn test_de_quoted_numbers() {
+                let quoted = DeserializerOptions::new().quoted_numbers(true);
+
+                assert_eq!(
+                    from_str_with_options::<f64>("\"1.25\"", quoted).unwrap(),
+                    1.25
+                );
+                assert_eq!(
+                    from_str_with_options::<f32>("\"-1e3\"", quoted).unwrap(),
+                    -1000.0
+                );
+                assert_eq!(
+                    from_str_with_options::<Vec<u32>>("[1 \"2\"]", quoted).unwrap(),
+                    vec![1, 2]
+                );
+                assert_eq!(from_str_with_options::<f64>("2.5", quoted).unwrap(), 2.5);
+                assert_eq!(
+                    from_str_with_options::<String>("\"1.25\"", quoted).unwrap(),
+                    "1.25"
+                );
+
+                assert_eq!(
+                    from_str_with_options::<f64>("\"1.25 \"", quoted)
+                        .unwrap_err()
+                        .to_string(),
+                    "serde error: expected number, found string `1.25 `"
+                );
+                assert!(from_str::<f64>("\"1.25\"").is_err());
+            }
+
+            #[test]
+            fn test_de_sign() {
+                assert_eq!(from_str::<i32>("+1").unwrap(), 1);
+                assert_eq!(from_str::<u8>("+255").unwrap(), 255);
+                assert_eq!(from_str::<f64>("+1.0").unwrap(), 1.0);
+                assert_eq!(from_str::<i32>("-0").unwrap(), 0);
+                assert_eq!(from_str::<u32>("-0").unwrap(), 0);
+                assert!(from_str::<f64>("-0").unwrap().is_sign_negative());
+
+                assert!(from_str::<i32>("+-1").is_err());
+            }
+
             #[test]
             fn test_de_i64_truncate() {
                 assert_eq!(
@@ -885,6 +2299,35 @@ mod tests {
                     9223372036854775807
                 );
             }
+
+            #[test]
+            fn test_de_strict_integers_range() {
+                let strict = DeserializerOptions::new().strict_integers(true);
+
+                for input in ["9223372036854775808", "-1e19", "1e19"] {
+                    assert_eq!(
+                        from_str_with_options::<i64>(input, strict)
+                            .unwrap_err()
+                            .to_string(),
+                        "serde error: integer out of range"
+                    );
+                }
+                assert!(from_str_with_options::<u64>("18446744073709551616", strict).is_err());
+                assert!(from_str_with_options::<u8>("-3", strict).is_err());
+
+                assert_eq!(
+                    from_str_with_options::<i64>("-9223372036854775808", strict).unwrap(),
+                    i64::MIN
+                );
+                assert_eq!(
+                    from_str_with_options::<i64>("-9.223372036854775e18", strict).unwrap(),
+                    -9223372036854774784
+                );
+                assert_eq!(
+                    from_str_with_options::<u64>("1e19", strict).unwrap(),
+                    10u64.pow(19)
+                );
+            }
         }
 
         mod unsigned_ints {
@@ -936,6 +2379,28 @@ mod tests {
                 assert!(from_str::<u64>("true").is_err());
             }
 
+            #[test]
+            fn test_de_u64_exact() {
+                assert_eq!(
+                    from_str::<u64>("1234567890123456789").unwrap(),
+                    1234567890123456789
+                );
+                assert_eq!(
+                    from_str::<u64>("18446744073709551614").unwrap(),
+                    18446744073709551614
+                );
+                assert_eq!(from_str::<u64>("1_000_001").unwrap(), 1_000_001);
+                assert_eq!(from_str::<u64>("2.5").unwrap(), 3);
+
+                assert_eq!(
+                    from_str::<crate::TotValue>("[9007199254740993 18446744073709551615]").unwrap(),
+                    crate::TotValue::List(vec![
+                        crate::TotValue::Integer(9007199254740993),
+                        crate::TotValue::Float(18446744073709551615.0)
+                    ])
+                );
+            }
+
             #[test]
             fn test_de_u64_truncate() {
                 assert_eq!(
@@ -968,45 +2433,418 @@ mod tests {
                 assert!(from_str::<f32>("true").is_err());
             }
 
-            #[test]
-            fn test_de_f64() {
-                assert_eq!(from_str::<f64>("-1").unwrap(), -1.0);
-                assert_eq!(from_str::<f64>("0").unwrap(), 0.0);
-                assert_eq!(from_str::<f64>("1").unwrap(), 1.0);
+            #[test]
+            fn test_de_f64() {
+                assert_eq!(from_str::<f64>("-1").unwrap(), -1.0);
+                assert_eq!(from_str::<f64>("0").unwrap(), 0.0);
+                assert_eq!(from_str::<f64>("1").unwrap(), 1.0);
+
+                assert_eq!(
+                    from_str::<f64>("9223372036854775807").unwrap(),
+                    9223372036854775807.0
+                );
+                assert_eq!(
+                    from_str::<f64>("-9223372036854775808").unwrap(),
+                    -9223372036854775808.0
+                );
+
+                assert!(from_str::<f64>("true").is_err());
+
+                assert_eq!(from_str::<f64>("inf").unwrap(), f64::INFINITY);
+                assert_eq!(from_str::<f64>("-infinity").unwrap(), f64::NEG_INFINITY);
+                assert!(from_str::<f64>("nan").unwrap().is_nan());
+                assert_eq!(
+                    from_str::<Vec<crate::TotValue>>("[inf null]").unwrap(),
+                    [crate::TotValue::Float(f64::INFINITY), crate::TotValue::Unit]
+                );
+                assert!(matches!(
+                    from_str::<crate::TotValue>("nan").unwrap(),
+                    crate::TotValue::Float(n) if n.is_nan()
+                ));
+            }
+
+            #[test]
+            fn test_de_float_truncate() {
+                assert!(from_str::<f32>("123819023801928309128301231234218309812408210").is_ok());
+                assert!(from_str::<f64>("123819023801928309128301231234218309812408210").is_ok());
+            }
+        }
+
+        #[test]
+        fn test_de_char() {
+            assert_eq!(from_str::<char>("\"a\"").unwrap(), 'a');
+            assert_eq!(from_str::<char>("\"0\"").unwrap(), '0');
+
+            assert!(from_str::<char>("\"abc\"").is_err());
+        }
+
+        #[test]
+        fn test_de_string() {
+            assert_eq!(
+                from_str::<String>("\"hello world\"").unwrap(),
+                "hello world"
+            );
+
+            for input in ["\"unterminated", "\"bad \\q escape\""] {
+                assert!(matches!(
+                    from_str::<String>(input),
+                    Err(crate::Error::ParserError(
+                        crate::parser::Error::StringError(_)
+                    ))
+                ));
+            }
+            assert_eq!(
+                from_str::<Vec<String>>("[\"a]").unwrap_err().to_string(),
+                "parser error: string error: [0]: malformed string `\"a]`"
+            );
+
+            #[derive(Deserialize, Debug)]
+            #[allow(dead_code)]
+            struct Inner {
+                name: String,
+            }
+
+            #[derive(Deserialize, Debug)]
+            #[allow(dead_code)]
+            struct Outer {
+                inner: Inner,
+            }
+
+            let err = from_str::<Outer>("inner { name \"a\\q\" }").unwrap_err();
+            assert!(matches!(
+                err,
+                crate::Error::ParserError(crate::parser::Error::StringError(_))
+            ));
+            assert_eq!(
+                err.to_string(),
+                "parser error: string error: inner.name: malformed string `\"a\\q\" }`"
+            );
+            assert_eq!(
+                from_str::<Outer>("inner { name 1 }")
+                    .unwrap_err()
+                    .to_string(),
+                "serde error: inner.name: expected string, found number"
+            );
+            assert!(matches!(
+                from_str::<String>("1"),
+                Err(crate::Error::SerdeError(_))
+            ));
+        }
+
+        #[test]
+        fn test_de_borrowed_str() {
+            use std::{borrow::Cow, collections::BTreeMap};
+
+            #[derive(Deserialize)]
+            struct Test<'a> {
+                #[serde(borrow)]
+                plain: Cow<'a, str>,
+                #[serde(borrow)]
+                escaped: Cow<'a, str>,
+                name: &'a str,
+            }
+
+            let input = "plain \"no escapes\"\nescaped \"a\\tb\"\nname \"\"";
+            let data = from_str::<Test>(input).unwrap();
+            assert!(matches!(data.plain, Cow::Borrowed("no escapes")));
+            assert!(matches!(data.escaped, Cow::Owned(ref s) if s == "a\tb"));
+            assert_eq!(data.name, "");
+
+            assert!(from_str::<Test>("plain \"\"\nescaped \"\"\nname \"\\n\"").is_err());
+
+            let map = from_str::<BTreeMap<&str, &str>>("a \"b\"\n\"quoted key\" \"c\"").unwrap();
+            assert_eq!(map, BTreeMap::from([("a", "b"), ("quoted key", "c")]));
+        }
+
+        #[test]
+        fn test_de_raw_value() {
+            use std::collections::BTreeMap;
+
+            #[derive(Deserialize)]
+            struct Test {
+                name: String,
+                settings: RawValue,
+                list: Vec<RawValue>,
+                after: bool,
+            }
+
+            let input = "\
+name \"x\"
+settings {
+    a 1 // one
+    b [2 3]
+}
+list [1.5 \"two\", [3]] // trailing
+after true
+";
+            let data = from_str::<Test>(input).unwrap();
+            assert_eq!(data.name, "x");
+            assert_eq!(data.settings.get(), "{\n    a 1 // one\n    b [2 3]\n}");
+            let raw = data.list.iter().map(RawValue::get).collect::<Vec<_>>();
+            assert_eq!(raw, ["1.5", "\"two\"", "[3]"]);
+            assert!(data.after);
+
+            assert_eq!(
+                data.settings
+                    .deserialize_into::<BTreeMap<String, crate::TotValue>>()
+                    .unwrap(),
+                BTreeMap::from([
+                    ("a".to_string(), crate::TotValue::Integer(1)),
+                    (
+                        "b".to_string(),
+                        crate::TotValue::List(vec![
+                            crate::TotValue::Integer(2),
+                            crate::TotValue::Integer(3)
+                        ])
+                    ),
+                ])
+            );
+
+            let root = from_str::<RawValue>("// config\na 1\n\n").unwrap();
+            assert_eq!(root.into_string(), "a 1");
+
+            assert!(from_str::<Test>("name \"x\" settings { a [ } list [] after true").is_err());
+        }
+
+        #[test]
+        fn test_from_str_checked() {
+            #[derive(Deserialize, Debug, PartialEq)]
+            struct Server {
+                id: u64,
+                ports: Vec<u16>,
+                weight: f64,
+            }
+
+            #[derive(Deserialize, Debug, PartialEq)]
+            struct Test {
+                server: Server,
+                total: crate::TotValue,
+            }
+
+            let input = "\
+server {
+    id 18446744073709551616
+    ports [80.4 443]
+    weight 9007199254740993
+}
+total 100000000000000000001
+";
+            let (data, warnings) = from_str_checked::<Test>(input).unwrap();
+            assert_eq!(data.server.id, u64::MAX);
+            assert_eq!(data.server.ports, [80, 443]);
+            assert_eq!(
+                warnings.iter().map(ToString::to_string).collect::<Vec<_>>(),
+                [
+                    "server.id: `18446744073709551616` is out of range and was clamped",
+                    "server.ports[0]: `80.4` was rounded to 80",
+                    "server.weight: `9007199254740993` lost precision as 9007199254740992",
+                    "total: `100000000000000000001` lost precision as 100000000000000000000",
+                ]
+            );
+
+            let (value, warnings) =
+                from_str_checked::<Server>("id 1\nports [1_000 2.0]\nweight 0.1").unwrap();
+            assert_eq!(value.ports, [1000, 2]);
+            assert!(warnings.is_empty());
+        }
+
+        #[test]
+        fn test_from_slice() {
+            assert_eq!(
+                from_slice::<HashMap<String, String>>("name \"caf\u{e9}\"".as_bytes())
+                    .unwrap()
+                    .get("name")
+                    .unwrap(),
+                "caf\u{e9}"
+            );
+
+            let err = from_slice::<HashMap<String, String>>(b"name \"caf\xe9\"").unwrap_err();
+            assert!(matches!(err, crate::Error::Utf8(_)));
+            assert_eq!(
+                err.to_string(),
+                "invalid utf-8: invalid utf-8 sequence of 1 bytes from index 9"
+            );
+        }
+
+        #[test]
+        fn test_from_str_partial() {
+            let (first, rest) = from_str_partial::<bool>("true false").unwrap();
+            assert!(first);
+            assert_eq!(rest, "false");
+
+            let (second, rest) = from_str_partial::<bool>(rest).unwrap();
+            assert!(!second);
+            assert_eq!(rest, "");
+
+            let (list, rest) = from_str_partial::<Vec<f64>>("[1.0 2.0], // next\n\"x\"").unwrap();
+            assert_eq!(list, vec![1.0, 2.0]);
+            assert_eq!(rest, "\"x\"");
+
+            assert!(from_str::<bool>("true false").is_err());
+        }
+
+        #[test]
+        fn test_depth_restored_on_error() {
+            #[derive(Deserialize, Debug)]
+            #[allow(dead_code)]
+            struct Test {
+                a: u32,
+                b: Vec<Vec<u32>>,
+            }
+
+            for input in ["[[1] [x]]", "a 1 b [[2] {}]", "{a 1 b [[\"3\"]]}"] {
+                let mut deserializer = Deserializer::from_str(input);
+                if input.starts_with('[') {
+                    assert!(Vec::<Vec<u32>>::deserialize(&mut deserializer).is_err());
+                } else {
+                    assert!(Test::deserialize(&mut deserializer).is_err());
+                }
+                assert_eq!(deserializer.depth, 0, "{input}");
+            }
+
+            #[derive(Deserialize, Debug)]
+            #[allow(dead_code)]
+            enum Event {
+                Pair(u32, u32),
+            }
+            let mut deserializer = Deserializer::from_str("[{Pair [1 x]}]");
+            assert!(Vec::<Event>::deserialize(&mut deserializer).is_err());
+            assert_eq!(deserializer.depth, 0);
+        }
+
+        #[test]
+        fn test_bytes_consumed() {
+            let input = "true [1 2], // list\n\"rest\"";
+            let mut deserializer = Deserializer::from_str(input);
+            assert_eq!(deserializer.bytes_consumed(), 0);
+
+            assert!(bool::deserialize(&mut deserializer).unwrap());
+            assert_eq!(deserializer.bytes_consumed(), 4);
+            deserializer.parse_ws().unwrap();
+
+            // Whitespace, commas and comments after a list are skipped with it
+            let list = Vec::<u32>::deserialize(&mut deserializer).unwrap();
+            assert_eq!(list, vec![1, 2]);
+            assert_eq!(&input[deserializer.bytes_consumed()..], "\"rest\"");
+            assert!(deserializer.end().is_err());
+
+            assert_eq!(String::deserialize(&mut deserializer).unwrap(), "rest");
+            assert_eq!(deserializer.bytes_consumed(), input.len());
+            assert!(deserializer.end().is_ok());
+        }
+
+        #[test]
+        fn test_list_element_error() {
+            assert_eq!(
+                from_str::<Vec<i32>>("[1 \"two\" 3]")
+                    .unwrap_err()
+                    .to_string(),
+                "serde error: [1]: expected number, found string"
+            );
+            assert_eq!(
+                from_str::<Vec<Vec<u8>>>("[[1] [2 true]]")
+                    .unwrap_err()
+                    .to_string(),
+                "serde error: [1][1]: expected number, found bool"
+            );
+            assert!(from_str_partial::<Vec<i32>>("[1 \"two\"] rest").is_err());
 
-                assert_eq!(
-                    from_str::<f64>("9223372036854775807").unwrap(),
-                    9223372036854775807.0
-                );
-                assert_eq!(
-                    from_str::<f64>("-9223372036854775808").unwrap(),
-                    -9223372036854775808.0
-                );
+            // The failed element isn't read
+            let input = "[1 \"two\" 3]";
+            let mut deserializer = Deserializer::from_str(input);
+            assert!(Vec::<i32>::deserialize(&mut deserializer).is_err());
+            assert_eq!(&input[deserializer.bytes_consumed()..], "\"two\" 3]");
+        }
 
-                assert!(from_str::<f64>("true").is_err());
+        #[test]
+        fn test_error_path() {
+            #[derive(Deserialize, Debug)]
+            #[allow(dead_code)]
+            struct Server {
+                host: String,
+                ports: Vec<u16>,
             }
 
-            #[test]
-            fn test_de_float_truncate() {
-                assert!(from_str::<f32>("123819023801928309128301231234218309812408210").is_ok());
-                assert!(from_str::<f64>("123819023801928309128301231234218309812408210").is_ok());
+            #[derive(Deserialize, Debug)]
+            #[allow(dead_code)]
+            struct Config {
+                name: String,
+                servers: HashMap<String, Server>,
             }
+
+            let input = "\
+name \"tot\"
+servers {
+    main {
+        host \"localhost\"
+        ports [80 443 \"8080\"]
+    }
+}
+";
+            assert_eq!(
+                from_str::<Config>(input).unwrap_err().to_string(),
+                "serde error: servers.main.ports[2]: expected number, found string"
+            );
+            assert_eq!(
+                from_str::<Config>("name \"tot\" servers { main { host 1 ports [] } }")
+                    .unwrap_err()
+                    .to_string(),
+                "serde error: servers.main.host: expected string, found number"
+            );
+
+            // Only the innermost failure names its path
+            assert_eq!(
+                from_str::<Config>("servers { main { host \"a\" ports [] } } name 1")
+                    .unwrap_err()
+                    .to_string(),
+                "serde error: name: expected string, found number"
+            );
         }
 
         #[test]
-        fn test_de_char() {
-            assert_eq!(from_str::<char>("\"a\"").unwrap(), 'a');
-            assert_eq!(from_str::<char>("\"0\"").unwrap(), '0');
+        fn test_bom() {
+            #[derive(Deserialize, Debug, PartialEq)]
+            struct Test {
+                a: u32,
+            }
 
-            assert!(from_str::<char>("\"abc\"").is_err());
+            let input = "\u{FEFF}a 1\n";
+            assert_eq!(from_str::<Test>(input).unwrap(), Test { a: 1 });
+            assert_eq!(from_slice::<Test>(input.as_bytes()).unwrap(), Test { a: 1 });
+
+            // Only a leading one is skipped
+            assert!(from_str::<Test>("a \u{FEFF}1\n").is_err());
         }
 
         #[test]
-        fn test_de_string() {
-            assert_eq!(
-                from_str::<String>("\"hello world\"").unwrap(),
-                "hello world"
+        fn test_seq_deserializer() {
+            let input = format!(
+                "[\n{}]\n",
+                (0..1000).map(|i| format!("    {i}\n")).collect::<String>()
             );
+
+            let mut count = 0;
+            for (i, n) in SeqDeserializer::<u32>::from_str(&input).enumerate() {
+                assert_eq!(n.unwrap(), i as u32);
+                count += 1;
+            }
+            assert_eq!(count, 1000);
+
+            assert_eq!(SeqDeserializer::<u32>::from_str(" [] ").count(), 0);
+
+            let items = SeqDeserializer::<u32>::from_str("[1 \"two\" 3]").collect::<Vec<_>>();
+            assert_eq!(items.len(), 2);
+            assert_eq!(items[0].as_ref().unwrap(), &1);
+            assert!(items[1].is_err());
+
+            let items = SeqDeserializer::<u32>::from_str("[1 2").collect::<Vec<_>>();
+            assert!(matches!(items[..], [Ok(1), Ok(2), Err(crate::Error::Eof)]));
+
+            for input in ["", "a 1", "[1] 2"] {
+                let mut items = SeqDeserializer::<u32>::from_str(input).skip_while(Result::is_ok);
+                assert!(items.next().unwrap().is_err(), "{input}");
+                assert!(items.next().is_none());
+            }
         }
 
         #[test]
@@ -1026,6 +2864,67 @@ mod tests {
             );
         }
 
+        #[test]
+        fn test_de_bytes_either_form() {
+            #[derive(Debug, PartialEq)]
+            struct Bytes(Vec<u8>);
+
+            impl<'de> Deserialize<'de> for Bytes {
+                fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+                where
+                    D: serde::Deserializer<'de>,
+                {
+                    struct BytesVisitor;
+
+                    impl<'de> serde::de::Visitor<'de> for BytesVisitor {
+                        type Value = Bytes;
+
+                        fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                            f.write_str("bytes")
+                        }
+
+                        fn visit_byte_buf<E>(self, v: Vec<u8>) -> std::result::Result<Bytes, E> {
+                            Ok(Bytes(v))
+                        }
+
+                        fn visit_seq<A>(self, mut seq: A) -> std::result::Result<Bytes, A::Error>
+                        where
+                            A: serde::de::SeqAccess<'de>,
+                        {
+                            let mut bytes = Vec::new();
+                            while let Some(b) = seq.next_element()? {
+                                bytes.push(b);
+                            }
+                            Ok(Bytes(bytes))
+                        }
+                    }
+
+                    deserializer.deserialize_byte_buf(BytesVisitor)
+                }
+            }
+
+            #[derive(Deserialize, Debug, PartialEq)]
+            struct Test {
+                old: Bytes,
+                new: Bytes,
+                literal: Bytes,
+            }
+
+            assert_eq!(
+                from_str::<Test>("old [104.0 105.0 0.0]\nnew \"aGkA\"\nliteral b\"aGkA\"").unwrap(),
+                Test {
+                    old: Bytes(b"hi\0".to_vec()),
+                    new: Bytes(b"hi\0".to_vec()),
+                    literal: Bytes(b"hi\0".to_vec()),
+                }
+            );
+            assert_eq!(from_str::<Bytes>("\"\"").unwrap(), Bytes(Vec::new()));
+            assert_eq!(
+                from_str::<Bytes>("\"not base64\"").unwrap_err().to_string(),
+                "serde error: invalid base64 string `not base64`"
+            );
+        }
+
         #[test]
         fn test_de_list() {
             assert_eq!(from_str::<Vec<bool>>("[\ntrue\n]").unwrap(), vec![true]);
@@ -1057,6 +2956,22 @@ mod tests {
             );
         }
 
+        #[test]
+        fn test_de_list_separators() {
+            assert_eq!(from_str::<Vec<i32>>("[-1 -2]").unwrap(), vec![-1, -2]);
+            assert_eq!(
+                from_str::<Vec<Vec<i32>>>("[[1] [2],[3]/* */[4]]").unwrap(),
+                vec![vec![1], vec![2], vec![3], vec![4]]
+            );
+
+            assert_eq!(
+                from_str::<Vec<i32>>("[-1-2]").unwrap_err().to_string(),
+                "serde error: expected whitespace or a comma between list elements"
+            );
+            assert!(from_str::<(i32, i32)>("[-1-2]").is_err());
+            assert!(from_str::<Vec<Vec<i32>>>("[[1][2]]").is_err());
+        }
+
         #[test]
         fn test_de_tuple() {
             assert_eq!(
@@ -1074,6 +2989,23 @@ mod tests {
             );
         }
 
+        #[test]
+        fn test_de_tuple_length() {
+            assert_eq!(
+                from_str::<(i8, i8)>("[1 2 3]").unwrap_err().to_string(),
+                "serde error: expected tuple of length 2"
+            );
+            assert_eq!(
+                from_str::<(i8, i8)>("[1]").unwrap_err().to_string(),
+                "serde error: expected tuple of length 2"
+            );
+            assert_eq!(
+                from_str::<[u8; 3]>("[]").unwrap_err().to_string(),
+                "serde error: expected tuple of length 3"
+            );
+            assert_eq!(from_str::<(i8, i8)>("[1, 2]").unwrap(), (1, 2));
+        }
+
         #[test]
         fn test_de_map_string_integer() {
             let dict = from_str::<HashMap<String, i8>>(
@@ -1093,8 +3025,8 @@ hello 101
 
                 m
             });
-            assert_eq!(dict.get(&"hello".to_string()).unwrap(), &101);
-            assert_eq!(dict.get(&"world".to_string()).unwrap(), &-2);
+            assert_eq!(dict.get("hello").unwrap(), &101);
+            assert_eq!(dict.get("world").unwrap(), &-2);
         }
 
         #[test]
@@ -1114,7 +3046,7 @@ hello 101
         #[test]
         fn test_de_option() {
             let r = from_str::<Option<bool>>("true").unwrap();
-            assert_eq!(r.unwrap(), true);
+            assert!(r.unwrap());
 
             let r = from_str::<Option<String>>("\"hello world\"").unwrap();
             assert_eq!(r.unwrap(), "hello world");
@@ -1163,7 +3095,7 @@ hello 101
                     from_str::<TestStruct>("hello \"world\"")
                         .unwrap()
                         .0
-                        .get(&"hello".to_string())
+                        .get("hello")
                         .unwrap(),
                     &"world".to_string()
                 );
@@ -1171,12 +3103,114 @@ hello 101
                     from_str::<TestStruct>("\"hello\" \"world\"")
                         .unwrap()
                         .0
-                        .get(&"hello".to_string())
+                        .get("hello")
                         .unwrap(),
                     &"world".to_string()
                 );
             }
 
+            #[test]
+            fn test_de_dict_braces() {
+                #[derive(Deserialize, Debug, PartialEq)]
+                struct Wrapper(HashMap<String, u32>);
+
+                #[derive(Deserialize, Debug, PartialEq)]
+                struct Outer {
+                    wrapper: Wrapper,
+                    nested: HashMap<String, HashMap<String, u32>>,
+                }
+
+                let map = HashMap::from([("a".to_string(), 1), ("b".to_string(), 2)]);
+
+                assert_eq!(
+                    from_str::<Wrapper>("a 1\nb 2").unwrap(),
+                    Wrapper(map.clone())
+                );
+                assert_eq!(
+                    from_str::<Wrapper>("{ a 1, b 2 }").unwrap(),
+                    Wrapper(map.clone())
+                );
+                assert_eq!(
+                    from_str::<Option<Wrapper>>(" a 1 b 2").unwrap(),
+                    Some(Wrapper(map.clone()))
+                );
+
+                let outer = from_str::<Outer>(
+                    "\
+wrapper { a 1, b 2 }
+nested {
+    x { a 1, b 2 }
+    y {}
+}
+",
+                )
+                .unwrap();
+                assert_eq!(outer.wrapper, Wrapper(map.clone()));
+                assert_eq!(outer.nested["x"], map);
+                assert!(outer.nested["y"].is_empty());
+
+                assert!(from_str::<Outer>("wrapper a 1").is_err());
+                assert!(from_str::<Wrapper>("{ a 1").is_err());
+            }
+
+            #[test]
+            fn test_de_empty_struct_block() {
+                #[derive(Deserialize, Debug, Default, PartialEq)]
+                struct Inner {
+                    #[serde(default)]
+                    count: u32,
+                    #[serde(default)]
+                    names: Vec<String>,
+                }
+
+                #[derive(Deserialize, Debug, PartialEq)]
+                struct Outer {
+                    inner: Inner,
+                    list: Vec<Inner>,
+                }
+
+                assert_eq!(
+                    from_str::<Outer>("inner {\n}\nlist [{} {\n}]").unwrap(),
+                    Outer {
+                        inner: Inner::default(),
+                        list: vec![Inner::default(), Inner::default()],
+                    }
+                );
+                assert_eq!(from_str::<Inner>("").unwrap(), Inner::default());
+                assert!(from_str::<Outer>("inner {\n}").is_err());
+            }
+
+            #[test]
+            fn test_de_newtype_struct_vec() {
+                #[derive(Deserialize, Debug, PartialEq)]
+                struct Wrapper(Vec<i32>);
+
+                #[derive(Deserialize, Debug, PartialEq)]
+                struct Outer {
+                    wrapper: Wrapper,
+                }
+
+                assert_eq!(
+                    from_str::<Wrapper>("[1 2 3]").unwrap(),
+                    Wrapper(vec![1, 2, 3])
+                );
+                assert_eq!(
+                    from_str::<Wrapper>("// numbers\n[1 2 3]\n").unwrap(),
+                    Wrapper(vec![1, 2, 3])
+                );
+                assert_eq!(
+                    from_str::<Vec<Wrapper>>("[[1] []]").unwrap(),
+                    vec![Wrapper(vec![1]), Wrapper(vec![])]
+                );
+                assert_eq!(
+                    from_str::<Outer>("wrapper [1 2]").unwrap(),
+                    Outer {
+                        wrapper: Wrapper(vec![1, 2])
+                    }
+                );
+                assert!(from_str::<Wrapper>("a 1").is_err());
+            }
+
             #[test]
             fn test_de_tuple_struct_int_int() {
                 #[derive(Deserialize)]
@@ -1196,7 +3230,7 @@ hello 101
                 let r = from_str::<TestStruct>("[\"hello world\" false]").unwrap();
 
                 assert_eq!(r.0, "hello world".to_string());
-                assert_eq!(r.1, false);
+                assert!(!r.1);
             }
 
             #[test]
@@ -1222,9 +3256,9 @@ hello 101
                 .unwrap();
 
                 assert_eq!(r.0 .0, -22);
-                assert_eq!(r.0 .1, true);
+                assert!(r.0 .1);
                 assert_eq!(r.1 .0, 123);
-                assert_eq!(r.1 .1, false);
+                assert!(!r.1 .1);
             }
 
             #[test]
@@ -1253,7 +3287,7 @@ hungry true
                 assert_eq!(r.age, 18);
                 assert_eq!(r.foods[0], "rice");
                 assert_eq!(r.foods[1], "chicken");
-                assert_eq!(r.hungry, true);
+                assert!(r.hungry);
             }
 
             #[test]
@@ -1282,7 +3316,34 @@ hungry true
                 assert_eq!(r.age, 18);
                 assert_eq!(r.foods[0], "rice");
                 assert_eq!(r.foods[1], "chicken");
-                assert_eq!(r.hungry, true);
+                assert!(r.hungry);
+            }
+
+            #[test]
+            fn test_de_struct_escaped_keys() {
+                #[derive(Deserialize, Debug, PartialEq)]
+                struct TestStruct {
+                    #[serde(rename = "a\"b")]
+                    quote: u32,
+                    #[serde(rename = "line\nbreak")]
+                    newline: u32,
+                    #[serde(rename = "\u{e9}")]
+                    unicode: u32,
+                }
+
+                let expected = TestStruct {
+                    quote: 1,
+                    newline: 2,
+                    unicode: 3,
+                };
+                let input = "\"a\\\"b\" 1\n\"line\\nbreak\" 2\n\"\\u{e9}\" 3\n";
+                assert_eq!(from_str::<TestStruct>(input).unwrap(), expected);
+
+                let strict = DeserializerOptions::new().strict(true);
+                assert_eq!(
+                    from_str_with_options::<TestStruct>(input, strict).unwrap(),
+                    expected
+                );
             }
 
             #[test]
@@ -1315,8 +3376,8 @@ inner {
 
                 assert_eq!(r.name, "Tim".to_string());
                 assert_eq!(r.inner.age, 100);
-                assert_eq!(r.inner.bools[0], true);
-                assert_eq!(r.inner.bools[1], false);
+                assert!(r.inner.bools[0]);
+                assert!(!r.inner.bools[1]);
             }
 
             #[test]
@@ -1348,7 +3409,7 @@ inner {
                 )
                 .unwrap();
 
-                assert_eq!(r.boolean, true);
+                assert!(r.boolean);
                 assert_eq!(r.inner.key1, "hello");
                 assert_eq!(r.inner.key2, "world");
                 assert_eq!(r.inner.key3, "wew");
@@ -1372,6 +3433,279 @@ inner {
                 assert!(from_str::<TestEnum>("\"Missing\"").is_err());
             }
 
+            #[test]
+            fn test_de_enum_unknown_variant() {
+                #[derive(Deserialize, Debug, PartialEq, Eq, Hash)]
+                enum Color {
+                    Red,
+                    Green,
+                    Custom(u32),
+                }
+
+                #[derive(Deserialize, Debug)]
+                #[allow(dead_code)]
+                struct Test {
+                    color: Color,
+                    counts: HashMap<Color, u32>,
+                }
+
+                let err = |input| from_str::<Test>(input).unwrap_err().to_string();
+
+                assert_eq!(
+                    err("color \"Gren\" counts {}"),
+                    "serde error: color: unknown variant `Gren`, expected one of `Red`, `Green`, \
+                     `Custom`; did you mean `Green`?"
+                );
+                assert_eq!(
+                    err("color { Cutsom 1 } counts {}"),
+                    "serde error: color: unknown variant `Cutsom`, expected one of `Red`, `Green`, \
+                     `Custom`; did you mean `Custom`?"
+                );
+                assert_eq!(
+                    err("color \"Red\" counts { red 1 }"),
+                    "serde error: counts: unknown variant `red`, expected one of `Red`, `Green`, \
+                     `Custom`; did you mean `Red`?"
+                );
+                assert_eq!(
+                    err("color \"Blue\" counts {}"),
+                    "serde error: color: unknown variant `Blue`, expected one of `Red`, `Green`, \
+                     `Custom`"
+                );
+            }
+
+            #[test]
+            fn test_edit_distance() {
+                assert_eq!(edit_distance("", ""), 0);
+                assert_eq!(edit_distance("Green", "Green"), 0);
+                assert_eq!(edit_distance("Gren", "Green"), 1);
+                assert_eq!(edit_distance("Grene", "Green"), 1);
+                assert_eq!(edit_distance("Blue", "Green"), 4);
+                assert_eq!(edit_distance("", "Red"), 3);
+            }
+
+            #[test]
+            fn test_de_enum_unit_or_newtype() {
+                #[derive(Deserialize, Debug, PartialEq, Eq)]
+                enum TestEnum {
+                    String,
+                    Str(String),
+                }
+
+                #[derive(Deserialize, Debug, PartialEq, Eq)]
+                struct TestStruct {
+                    unit: TestEnum,
+                    newtype: TestEnum,
+                }
+
+                assert_eq!(
+                    from_str::<TestEnum>("\"String\"").unwrap(),
+                    TestEnum::String
+                );
+                assert_eq!(
+                    from_str::<TestEnum>("Str \"String\"").unwrap(),
+                    TestEnum::Str("String".to_string())
+                );
+                assert_eq!(
+                    from_str::<TestStruct>(
+                        "\
+unit \"String\"
+newtype {
+    Str \"String\"
+}
+"
+                    )
+                    .unwrap(),
+                    TestStruct {
+                        unit: TestEnum::String,
+                        newtype: TestEnum::Str("String".to_string()),
+                    }
+                );
+
+                // A unit variant can't take a payload and a newtype variant needs one
+                assert!(from_str::<TestEnum>("String \"String\"").is_err());
+                assert!(from_str::<TestEnum>("\"Str\"").is_err());
+            }
+
+            #[test]
+            fn test_de_enum_untagged() {
+                #[derive(Deserialize, Debug, PartialEq)]
+                #[serde(untagged)]
+                enum Value {
+                    Int(i32),
+                    Float(f64),
+                    Str(String),
+                    List(Vec<Value>),
+                    Struct { name: String },
+                }
+
+                #[derive(Deserialize, Debug, PartialEq)]
+                struct Test {
+                    a: Value,
+                    b: Value,
+                    c: Value,
+                }
+
+                assert_eq!(from_str::<Value>("1").unwrap(), Value::Int(1));
+                assert_eq!(from_str::<Value>("-1.5").unwrap(), Value::Float(-1.5));
+                // Whole floats can still fill integers when buffered
+                assert_eq!(from_str::<Value>("5.0").unwrap(), Value::Int(5));
+                assert_eq!(from_str::<Value>("1e3").unwrap(), Value::Int(1000));
+                assert_eq!(
+                    from_str::<Value>("\"hello\"").unwrap(),
+                    Value::Str("hello".to_string())
+                );
+                assert_eq!(
+                    from_str::<Value>("[1 \"x\"]").unwrap(),
+                    Value::List(vec![Value::Int(1), Value::Str("x".to_string())])
+                );
+                assert_eq!(
+                    from_str::<Value>("name \"tot\"").unwrap(),
+                    Value::Struct {
+                        name: "tot".to_string()
+                    }
+                );
+
+                assert_eq!(
+                    from_str::<Test>(
+                        "\
+a 10
+b \"ten\"
+c {
+    name \"nested\"
+}
+"
+                    )
+                    .unwrap(),
+                    Test {
+                        a: Value::Int(10),
+                        b: Value::Str("ten".to_string()),
+                        c: Value::Struct {
+                            name: "nested".to_string()
+                        },
+                    }
+                );
+
+                assert!(from_str::<Value>("true").is_err());
+            }
+
+            #[test]
+            fn test_de_enum_internally_tagged() {
+                #[derive(Deserialize, Debug, PartialEq)]
+                #[serde(tag = "type")]
+                enum Shape {
+                    Circle { radius: f64 },
+                    Rect { width: u32, height: u32 },
+                    Empty,
+                }
+
+                #[derive(Deserialize, Debug, PartialEq)]
+                struct Test {
+                    shape: Shape,
+                    shapes: Vec<Shape>,
+                }
+
+                assert_eq!(
+                    from_str::<Shape>("type \"Circle\" radius 1.5").unwrap(),
+                    Shape::Circle { radius: 1.5 }
+                );
+
+                // The tag doesn't have to come first
+                assert_eq!(
+                    from_str::<Test>(
+                        "\
+shape {
+    width 2
+    height 3
+    type \"Rect\"
+}
+shapes [
+    {
+        type \"Empty\"
+    }
+    {
+        type \"Circle\"
+        radius 1
+    }
+]
+"
+                    )
+                    .unwrap(),
+                    Test {
+                        shape: Shape::Rect {
+                            width: 2,
+                            height: 3
+                        },
+                        shapes: vec![Shape::Empty, Shape::Circle { radius: 1.0 }],
+                    }
+                );
+
+                assert_eq!(
+                    from_str::<Shape>("radius 1").unwrap_err().to_string(),
+                    "serde error: missing field `type`"
+                );
+                assert!(from_str::<Shape>("type \"Square\"").is_err());
+            }
+
+            #[test]
+            fn test_de_enum_adjacently_tagged() {
+                #[derive(serde::Serialize, Deserialize, Debug, PartialEq)]
+                struct Point {
+                    x: i32,
+                    y: i32,
+                }
+
+                #[derive(serde::Serialize, Deserialize, Debug, PartialEq)]
+                #[serde(tag = "t", content = "c")]
+                enum Event {
+                    Moved(Point),
+                    Count(u32),
+                    Reset,
+                }
+
+                #[derive(serde::Serialize, Deserialize, Debug, PartialEq)]
+                struct Test {
+                    event: Event,
+                    events: Vec<Event>,
+                }
+
+                // The content can come before the tag
+                assert_eq!(
+                    from_str::<Event>("c { x 1, y -2 }\nt \"Moved\"").unwrap(),
+                    Event::Moved(Point { x: 1, y: -2 })
+                );
+
+                let data = Test {
+                    event: Event::Moved(Point { x: 3, y: 4 }),
+                    events: vec![Event::Count(2), Event::Reset],
+                };
+                let output = crate::to_string(&data).unwrap();
+                assert_eq!(
+                    output,
+                    "\
+event {
+    t \"Moved\"
+    c {
+        x 3
+        y 4
+    }
+}
+events [
+    {
+        t \"Count\"
+        c 2
+    }
+    {
+        t \"Reset\"
+    }
+]
+"
+                );
+                assert_eq!(from_str::<Test>(&output).unwrap(), data);
+
+                assert!(from_str::<Event>("t \"Moved\" c 1").is_err());
+                assert!(from_str::<Event>("c 1").is_err());
+            }
+
             #[test]
             fn test_de_enum_variant_newtype() {
                 #[derive(Deserialize, Debug, PartialEq, Eq)]
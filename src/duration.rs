@@ -0,0 +1,86 @@
+/*!
+Serialize a [`Duration`] as a number of seconds.
+
+serde's own impl writes a `Duration` as a dict of `secs` and `nanos`. Use this module with
+`#[serde(with = "tot::duration")]` to write it as a single float instead.
+
+```
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize)]
+struct Config {
+    #[serde(with = "tot::duration")]
+    timeout: Duration,
+}
+
+let config = Config {
+    timeout: Duration::from_millis(1500),
+};
+
+let output = tot::to_string(&config).unwrap();
+assert_eq!(output, "timeout 1.5\n");
+
+let config = tot::from_str::<Config>(&output).unwrap();
+assert_eq!(config.timeout, Duration::from_millis(1500));
+```
+*/
+
+use std::time::Duration;
+
+use serde::{de, Deserialize, Deserializer, Serializer};
+
+pub fn serialize<S>(value: &Duration, serializer: S) -> std::result::Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.serialize_f64(value.as_secs_f64())
+}
+
+pub fn deserialize<'de, D>(deserializer: D) -> std::result::Result<Duration, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let secs = f64::deserialize(deserializer)?;
+    Duration::try_from_secs_f64(secs)
+        .map_err(|_| de::Error::custom(format!("invalid duration of {secs} seconds")))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use serde::{Deserialize, Serialize};
+
+    use crate::{from_str, to_string};
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Test {
+        #[serde(with = "super")]
+        duration: Duration,
+    }
+
+    #[test]
+    fn test_duration() {
+        for (duration, output) in [
+            (Duration::from_secs(0), "duration 0.0\n"),
+            (Duration::from_secs(30), "duration 30.0\n"),
+            (Duration::from_millis(250), "duration 0.25\n"),
+            (Duration::from_nanos(1), "duration 1e-9\n"),
+        ] {
+            let data = Test { duration };
+            assert_eq!(to_string(&data).unwrap(), output);
+            assert_eq!(from_str::<Test>(output).unwrap(), data);
+        }
+    }
+
+    #[test]
+    fn test_duration_invalid() {
+        assert_eq!(
+            from_str::<Test>("duration -1").unwrap_err().to_string(),
+            "serde error: duration: invalid duration of -1 seconds"
+        );
+        assert!(from_str::<Test>("duration \"1s\"").is_err());
+    }
+}
@@ -10,6 +10,10 @@ pub enum Error {
     ParserError(crate::parser::Error),
     #[error("io error: {0}")]
     Io(std::io::Error),
+    #[error("unexpected end of input")]
+    Eof,
+    #[error("invalid utf-8: {0}")]
+    Utf8(std::str::Utf8Error),
 }
 
 // TODO stub
@@ -32,6 +36,12 @@ impl serde::ser::Error for Error {
     }
 }
 
+impl From<crate::parser::Error> for Error {
+    fn from(value: crate::parser::Error) -> Self {
+        Self::ParserError(value)
+    }
+}
+
 // TODO stub
 impl From<TryFromIntError> for Error {
     fn from(value: TryFromIntError) -> Self {
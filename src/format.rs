@@ -0,0 +1,299 @@
+use crate::error::Result;
+use crate::parser::{self, SpanChildren, Spans};
+use crate::ser::INDENT;
+
+/// Reformat Tot source with canonical indentation and spacing.
+///
+/// Every dict entry and list element goes on its own line, indented by nesting depth, and commas
+/// are dropped. Keys, scalars and entry order are kept exactly as written. Comments are kept
+/// where they were, either at the end of the line they were on or on a line of their own. Any
+/// document [`parse`](crate::parser::parse) reads can be formatted, including a root list or a
+/// single value.
+///
+/// ```
+/// let output = tot::format_source("a   1, b [2,3] // note").unwrap();
+///
+/// assert_eq!(
+///     output,
+///     "\
+/// a 1
+/// b [
+///     2
+///     3
+/// ] // note
+/// "
+/// );
+/// ```
+pub fn format_source(input: &str) -> Result<String> {
+    let (_, spans) = parser::parse_with_spans(input)?;
+
+    let mut formatter = SourceFormatter {
+        src: input,
+        output: String::with_capacity(input.len()),
+        indent: 0,
+    };
+    // A root dict without braces spans the whole input, where a braced one starts at its `{`
+    let braceless = matches!(spans.children, SpanChildren::Dict(_))
+        && !input[spans.span.clone()].starts_with('{');
+    if braceless {
+        formatter.write_children(&spans.children, 0, input.len());
+    } else {
+        formatter.write_comments(0, spans.span.start, true);
+        formatter.new_line();
+        formatter.write_value(&spans);
+        formatter.write_comments(spans.span.end, input.len(), true);
+    }
+    if !formatter.output.is_empty() {
+        formatter.output.push('\n');
+    }
+
+    Ok(formatter.output)
+}
+
+/// The lines of Tot source indented with tabs where the rest is indented with spaces, or the
+/// other way around, numbered from 1.
+///
+/// Indentation doesn't change what a document means, so this is only a warning for people
+/// reading it. Whichever of tabs and spaces starts the first indented line is taken as the
+/// document's style. Lines are checked as text, so a line within a multi-line string or comment
+/// counts too.
+///
+/// ```
+/// let input = "server {\n    host \"localhost\"\n\tport 80\n}\n";
+///
+/// assert_eq!(tot::format::mixed_indentation(input), [3]);
+/// ```
+pub fn mixed_indentation(input: &str) -> Vec<usize> {
+    let mut style = None;
+    let mut lines = Vec::new();
+    for (i, line) in input.lines().enumerate() {
+        let indent = &line[..line.len() - line.trim_start_matches([' ', '\t']).len()];
+        let Some(first) = indent.chars().next() else {
+            continue;
+        };
+        // Whitespace at the end of a line isn't indentation
+        if indent.len() == line.len() {
+            continue;
+        }
+
+        let style = *style.get_or_insert(first);
+        if indent.chars().any(|c| c != style) {
+            lines.push(i + 1);
+        }
+    }
+
+    lines
+}
+
+struct SourceFormatter<'a> {
+    src: &'a str,
+    output: String,
+    indent: usize,
+}
+
+impl SourceFormatter<'_> {
+    /// Start a new line at the current indent.
+    fn new_line(&mut self) {
+        if !self.output.is_empty() && !self.output.ends_with('\n') {
+            self.output.push('\n');
+        }
+        for _ in 0..self.indent {
+            self.output.push_str(INDENT);
+        }
+    }
+
+    /// Write the comments in `src[start..end]`. If `trailing` is set, ones that share a line with
+    /// what was written before are kept at the end of that line.
+    fn write_comments(&mut self, start: usize, end: usize, trailing: bool) {
+        let mut line_start = start;
+        for (offset, comment) in parser::comments(&self.src[start..end]) {
+            let offset = start + offset;
+            let trailing = trailing
+                && !self.output.is_empty()
+                && !self.output.ends_with('\n')
+                && !self.src[line_start..offset].contains('\n');
+
+            if trailing {
+                self.output.push(' ');
+            } else {
+                self.new_line();
+            }
+            self.output.push_str(comment.trim_end());
+            line_start = offset + comment.len();
+        }
+    }
+
+    /// Write the entries or elements of a dict or list spanning `src[start..end]`, without its
+    /// braces or brackets.
+    fn write_children(&mut self, children: &SpanChildren, start: usize, end: usize) {
+        let mut position = start;
+        match children {
+            SpanChildren::None => {}
+            SpanChildren::List(elements) => {
+                for element in elements {
+                    self.write_comments(position, element.span.start, true);
+                    self.new_line();
+                    self.write_value(element);
+                    position = element.span.end;
+                }
+            }
+            SpanChildren::Dict(entries) => {
+                for entry in entries {
+                    self.write_comments(position, entry.key_span.start, true);
+                    // Comments between a key and its value go before the entry
+                    self.write_comments(entry.key_span.end, entry.value.span.start, false);
+                    self.new_line();
+                    self.output.push_str(&self.src[entry.key_span.clone()]);
+                    self.output.push(' ');
+                    self.write_value(&entry.value);
+                    position = entry.value.span.end;
+                }
+            }
+        }
+        self.write_comments(position, end, true);
+    }
+
+    fn write_value(&mut self, spans: &Spans) {
+        let (open, close) = match spans.children {
+            SpanChildren::None => {
+                self.output.push_str(&self.src[spans.span.clone()]);
+                return;
+            }
+            SpanChildren::List(_) => ('[', ']'),
+            SpanChildren::Dict(_) => ('{', '}'),
+        };
+
+        self.output.push(open);
+        self.indent += 1;
+        self.write_children(&spans.children, spans.span.start + 1, spans.span.end - 1);
+        self.indent -= 1;
+        self.new_line();
+        self.output.push(close);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{format_source, mixed_indentation};
+
+    #[test]
+    fn test_format_source() {
+        let input = "\
+  name   \"tot\"   // the name
+\"quoted key\"    true
+ports [80,   443]
+server {host \"localhost\"
+      tls { enabled true, certs [] }}
+
+/* block */ empty {}
+// trailing
+";
+
+        let output = format_source(input).unwrap();
+
+        assert_eq!(
+            output,
+            "\
+name \"tot\" // the name
+\"quoted key\" true
+ports [
+    80
+    443
+]
+server {
+    host \"localhost\"
+    tls {
+        enabled true
+        certs [
+        ]
+    }
+}
+/* block */
+empty {
+}
+// trailing
+"
+        );
+
+        assert_eq!(format_source(&output).unwrap(), output);
+        assert_eq!(
+            crate::parser::parse(&output).unwrap(),
+            crate::parser::parse(input).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_format_source_comments() {
+        let output = format_source(
+            "\
+list [ // first
+    1 /* one */ 2
+    // last
+]
+key /* between */ 1
+",
+        )
+        .unwrap();
+
+        assert_eq!(
+            output,
+            "\
+list [ // first
+    1 /* one */
+    2
+    // last
+]
+/* between */
+key 1
+"
+        );
+    }
+
+    #[test]
+    fn test_format_source_errors() {
+        assert_eq!(format_source("").unwrap(), "");
+        assert_eq!(format_source("// c\n").unwrap(), "// c\n");
+        assert_eq!(
+            format_source("// numbers\n[1 2] // end\n").unwrap(),
+            "// numbers\n[\n    1\n    2\n] // end\n"
+        );
+        assert_eq!(format_source("1\n").unwrap(), "1\n");
+        assert_eq!(format_source("  \"hi\"  ").unwrap(), "\"hi\"\n");
+        assert_eq!(
+            format_source("{a 1, b 2}").unwrap(),
+            "{\n    a 1\n    b 2\n}\n"
+        );
+        assert!(matches!(
+            format_source("a [1"),
+            Err(crate::Error::ParserError(_))
+        ));
+    }
+
+    #[test]
+    fn test_mixed_indentation() {
+        let input = "\
+server {
+    host \"localhost\"
+\tport 80
+    tls {
+    \tenabled true
+        certs []
+\t
+    }
+}
+";
+        assert_eq!(mixed_indentation(input), [3, 5]);
+        assert_eq!(
+            mixed_indentation(&input.replace('\t', "    ")),
+            Vec::<usize>::new()
+        );
+        assert_eq!(
+            mixed_indentation(&input.replace("    ", "\t")),
+            Vec::<usize>::new()
+        );
+
+        let tabs_first = "a {\n\tb 1\n    c 2\n}\n";
+        assert_eq!(mixed_indentation(tabs_first), [3]);
+        assert_eq!(mixed_indentation(""), Vec::<usize>::new());
+    }
+}
@@ -9,7 +9,7 @@ Tot is a configuration language meant to be edited by hand.
 * Simple, limited syntax
 * JSON-style objects and lists
 * Reference values (WIP)
-* File import (WIP)
+* File import, see [`parser::parse_file`]
 * Non-Turing complete Lisp-style expressions (WIP)
 * Compatible with:
     * JSON
@@ -37,7 +37,7 @@ fn main() {
 
     assert_eq!("\
 name \"youwin\"
-age 100.0
+age 100
 ", output);
 
     let person = tot::from_str::<Person>(output.as_str()).unwrap();
@@ -50,12 +50,19 @@ age 100.0
 */
 
 pub mod de;
-pub use de::from_str;
+pub use de::{from_slice, from_str};
 pub mod ser;
-pub use ser::to_string;
+pub use ser::{to_string, to_string_pretty, to_vec};
 
+mod base64;
 mod error;
 pub use error::{Error, Result};
 
+pub mod format;
+pub use format::format_source;
+
+pub mod duration;
+pub mod timestamp;
+
 pub mod parser;
-pub use parser::TotValue;
+pub use parser::{ListMerge, TotValue};
@@ -1,190 +1,2411 @@
-use std::collections::HashMap;
+use std::{
+    borrow::Cow,
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    ops::Range,
+    path::{Path, PathBuf},
+};
 
 use nom::{
     branch::alt,
-    bytes::complete::{is_not, tag, take_till, take_till1, take_until},
-    character::complete::multispace1,
-    combinator::{map, value},
-    multi::many0,
-    number::complete::double,
-    sequence::{delimited, pair, separated_pair, tuple},
-    IResult,
+    bytes::complete::{
+        is_not, tag, tag_no_case, take_till1, take_until, take_while, take_while_m_n,
+    },
+    character::complete::{char, digit1, multispace1, one_of, satisfy},
+    combinator::{map, map_opt, map_res, not, opt, recognize, value, verify},
+    error::ErrorKind,
+    multi::{fold_many0, many0, separated_list1},
+    sequence::{delimited, pair, preceded, separated_pair, terminated, tuple},
+    IResult, Offset,
 };
 
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
     #[error("error ocurred while parsing")]
     ParseError,
+    #[error("list error: {0}")]
+    ListError(String),
+    #[error("dict error: {0}")]
+    DictError(String),
+    #[error("bool error: {0}")]
+    BoolError(String),
+    #[error("string error: {0}")]
+    StringError(String),
+    #[error("include error: {0}")]
+    IncludeError(String),
+    #[error("size error: {0}")]
+    SizeError(String),
+    /// An error in the root dict entry starting on `line`, counting from 1.
+    #[error("line {line}: {error}")]
+    LineError { line: usize, error: Box<Error> },
+}
+
+impl Error {
+    /// Start the message with `path`, the location of the value that failed, like the serde
+    /// errors of the deserializer.
+    pub(crate) fn with_path(self, path: &str) -> Self {
+        match self {
+            Error::ListError(msg) => Error::ListError(format!("{path}: {msg}")),
+            Error::DictError(msg) => Error::DictError(format!("{path}: {msg}")),
+            Error::BoolError(msg) => Error::BoolError(format!("{path}: {msg}")),
+            Error::StringError(msg) => Error::StringError(format!("{path}: {msg}")),
+            Error::SizeError(msg) => Error::SizeError(format!("{path}: {msg}")),
+            e => e,
+        }
+    }
 }
 
+/// A parsed Tot value.
+///
+/// [`parse`] keeps numbers as they're written: `1` is an [`Integer`](TotValue::Integer), while
+/// `1.0` and `1e0` are [`Float`](TotValue::Float)s. Deserializing a `TotValue` with
+/// [`from_str`](crate::from_str) does the same. Its [`Display`](std::fmt::Display) impl writes
+/// it like [`to_string`](crate::to_string) does.
 #[derive(Debug, PartialEq, Clone)]
 pub enum TotValue {
     Unit,
     Boolean(bool),
     String(String),
-    Number(f64),
+    /// A number written without a fraction or exponent that fits in an `i64`.
+    Integer(i64),
+    /// Any other number.
+    Float(f64),
+    /// Bytes, written in base64 like `b"AQID"`.
+    Bytes(Vec<u8>),
     List(Vec<TotValue>),
     Dict(HashMap<String, TotValue>),
 }
 
+/// How lists are combined by [`TotValue::merge_with`].
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub enum ListMerge {
+    /// The overriding list replaces the base list.
+    #[default]
+    Replace,
+    /// The overriding list's elements are appended to the base list.
+    Append,
+}
+
+impl TotValue {
+    /// Name of the type of this value, as used in error messages. Integers and floats are both
+    /// `number`.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            TotValue::Unit => "null",
+            TotValue::Boolean(_) => "bool",
+            TotValue::String(_) => "string",
+            TotValue::Bytes(_) => "bytes",
+            TotValue::Integer(_) | TotValue::Float(_) => "number",
+            TotValue::List(_) => "list",
+            TotValue::Dict(_) => "dict",
+        }
+    }
+
+    pub fn is_unit(&self) -> bool {
+        matches!(self, TotValue::Unit)
+    }
+
+    pub fn is_bool(&self) -> bool {
+        matches!(self, TotValue::Boolean(_))
+    }
+
+    pub fn is_integer(&self) -> bool {
+        matches!(self, TotValue::Integer(_))
+    }
+
+    pub fn is_float(&self) -> bool {
+        matches!(self, TotValue::Float(_))
+    }
+
+    /// Whether the value is an integer or a float.
+    pub fn is_number(&self) -> bool {
+        self.is_integer() || self.is_float()
+    }
+
+    pub fn is_string(&self) -> bool {
+        matches!(self, TotValue::String(_))
+    }
+
+    pub fn is_bytes(&self) -> bool {
+        matches!(self, TotValue::Bytes(_))
+    }
+
+    pub fn is_list(&self) -> bool {
+        matches!(self, TotValue::List(_))
+    }
+
+    pub fn is_dict(&self) -> bool {
+        matches!(self, TotValue::Dict(_))
+    }
+
+    /// Take the value out, leaving [`TotValue::Unit`] in its place.
+    pub fn take(&mut self) -> TotValue {
+        std::mem::replace(self, TotValue::Unit)
+    }
+
+    pub fn into_bool(self) -> Option<bool> {
+        match self {
+            TotValue::Boolean(b) => Some(b),
+            _ => None,
+        }
+    }
+
+    pub fn into_string(self) -> Option<String> {
+        match self {
+            TotValue::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn into_i64(self) -> Option<i64> {
+        match self {
+            TotValue::Integer(n) => Some(n),
+            _ => None,
+        }
+    }
+
+    /// The number as an `f64`, whether it's an integer or a float.
+    pub fn into_f64(self) -> Option<f64> {
+        match self {
+            TotValue::Integer(n) => Some(n as f64),
+            TotValue::Float(n) => Some(n),
+            _ => None,
+        }
+    }
+
+    pub fn into_bytes(self) -> Option<Vec<u8>> {
+        match self {
+            TotValue::Bytes(bytes) => Some(bytes),
+            _ => None,
+        }
+    }
+
+    pub fn into_list(self) -> Option<Vec<TotValue>> {
+        match self {
+            TotValue::List(list) => Some(list),
+            _ => None,
+        }
+    }
+
+    pub fn into_dict(self) -> Option<HashMap<String, TotValue>> {
+        match self {
+            TotValue::Dict(dict) => Some(dict),
+            _ => None,
+        }
+    }
+
+    /// Deep-merge `other` into `self`, replacing lists.
+    ///
+    /// See [`TotValue::merge_with`].
+    pub fn merge(&mut self, other: TotValue) {
+        self.merge_with(other, ListMerge::default());
+    }
+
+    /// Deep-merge `other` into `self`.
+    ///
+    /// Dicts are merged key by key, recursing into dicts present in both. Lists are combined
+    /// according to `lists`. Any other value in `other` replaces the value in `self`.
+    pub fn merge_with(&mut self, other: TotValue, lists: ListMerge) {
+        match (self, other) {
+            (TotValue::Dict(base), TotValue::Dict(other)) => {
+                for (k, v) in other {
+                    match base.get_mut(&k) {
+                        Some(existing) => existing.merge_with(v, lists),
+                        None => {
+                            base.insert(k, v);
+                        }
+                    }
+                }
+            }
+            (TotValue::List(base), TotValue::List(other)) if lists == ListMerge::Append => {
+                base.extend(other);
+            }
+            (base, other) => *base = other,
+        }
+    }
+
+    /// Look up a value by a path like `/server/ports/0`, as in a JSON Pointer.
+    ///
+    /// Each `/`-separated segment is an index into a list or a key into a dict, with `~1` and
+    /// `~0` standing for `/` and `~` in keys. An empty path is the value itself.
+    ///
+    /// ```
+    /// let value = tot::parser::parse("server { ports [80 443] }").unwrap();
+    ///
+    /// assert_eq!(
+    ///     value.pointer("/server/ports/1"),
+    ///     Some(&tot::TotValue::Integer(443))
+    /// );
+    /// assert_eq!(value.pointer("/server/host"), None);
+    /// ```
+    pub fn pointer(&self, path: &str) -> Option<&TotValue> {
+        pointer_segments(path)?.try_fold(self, |value, segment| match value {
+            TotValue::List(list) => list.get(parse_index(&segment)?),
+            TotValue::Dict(dict) => dict.get(segment.as_ref()),
+            _ => None,
+        })
+    }
+
+    /// Like [`TotValue::pointer`], but returns a mutable reference.
+    pub fn pointer_mut(&mut self, path: &str) -> Option<&mut TotValue> {
+        pointer_segments(path)?.try_fold(self, |value, segment| match value {
+            TotValue::List(list) => list.get_mut(parse_index(&segment)?),
+            TotValue::Dict(dict) => dict.get_mut(segment.as_ref()),
+            _ => None,
+        })
+    }
+
+    /// Every difference between `self` and `other`, in path order.
+    ///
+    /// Dicts are compared key by key and lists index by index, recursing into values present in
+    /// both. Paths use the same syntax as [`TotValue::pointer`].
+    ///
+    /// ```
+    /// use tot::parser::{parse, Change};
+    /// use tot::TotValue;
+    ///
+    /// let old = parse("server { port 80 }").unwrap();
+    /// let new = parse("server { port 8080 }").unwrap();
+    ///
+    /// assert_eq!(
+    ///     old.diff(&new),
+    ///     [Change {
+    ///         path: "/server/port".to_string(),
+    ///         old: Some(TotValue::Integer(80)),
+    ///         new: Some(TotValue::Integer(8080)),
+    ///     }]
+    /// );
+    /// ```
+    pub fn diff(&self, other: &TotValue) -> Vec<Change> {
+        let mut changes = Vec::new();
+        diff_into(&mut changes, &mut String::new(), self, other);
+
+        changes
+    }
+}
+
+/// How many lists or dicts deep an arbitrary [`TotValue`] is nested at most.
+#[cfg(any(test, feature = "arbitrary"))]
+const ARBITRARY_MAX_DEPTH: usize = 4;
+
+/// Random values for fuzzing, nested at most a few lists or dicts deep. Floats can be NaN, so
+/// compare them with care.
+#[cfg(any(test, feature = "arbitrary"))]
+impl<'a> arbitrary::Arbitrary<'a> for TotValue {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        arbitrary_value(u, ARBITRARY_MAX_DEPTH)
+    }
+}
+
+/// An arbitrary value with lists and dicts at most `depth` deep.
+#[cfg(any(test, feature = "arbitrary"))]
+fn arbitrary_value(
+    u: &mut arbitrary::Unstructured<'_>,
+    depth: usize,
+) -> arbitrary::Result<TotValue> {
+    let kinds = if depth == 0 { 6 } else { 8 };
+    Ok(match u.choose_index(kinds)? {
+        0 => TotValue::Unit,
+        1 => TotValue::Boolean(u.arbitrary()?),
+        2 => TotValue::String(u.arbitrary()?),
+        3 => TotValue::Integer(u.arbitrary()?),
+        4 => TotValue::Float(u.arbitrary()?),
+        5 => TotValue::Bytes(u.arbitrary()?),
+        6 => TotValue::List(
+            (0..u.int_in_range(0..=4)?)
+                .map(|_| arbitrary_value(u, depth - 1))
+                .collect::<arbitrary::Result<_>>()?,
+        ),
+        _ => TotValue::Dict(
+            (0..u.int_in_range(0..=4)?)
+                .map(|_| Ok((u.arbitrary()?, arbitrary_value(u, depth - 1)?)))
+                .collect::<arbitrary::Result<_>>()?,
+        ),
+    })
+}
+
+/// A difference found by [`TotValue::diff`].
+#[derive(Debug, PartialEq, Clone)]
+pub struct Change {
+    /// Path of the value that differs, as used by [`TotValue::pointer`].
+    pub path: String,
+    /// The value in the first document, or `None` if it was added.
+    pub old: Option<TotValue>,
+    /// The value in the second document, or `None` if it was removed.
+    pub new: Option<TotValue>,
+}
+
+fn diff_into(changes: &mut Vec<Change>, path: &mut String, old: &TotValue, new: &TotValue) {
+    // Append a segment to the path for the duration of `f`
+    fn with_segment(path: &mut String, segment: &str, f: impl FnOnce(&mut String)) {
+        let len = path.len();
+        path.push('/');
+        path.push_str(&segment.replace('~', "~0").replace('/', "~1"));
+        f(path);
+        path.truncate(len);
+    }
+
+    match (old, new) {
+        (TotValue::Dict(old), TotValue::Dict(new)) => {
+            let mut keys = old.keys().chain(new.keys()).collect::<Vec<_>>();
+            keys.sort_unstable();
+            keys.dedup();
+
+            for key in keys {
+                with_segment(path, key, |path| match (old.get(key), new.get(key)) {
+                    (Some(old), Some(new)) => diff_into(changes, path, old, new),
+                    (old, new) => changes.push(Change {
+                        path: path.clone(),
+                        old: old.cloned(),
+                        new: new.cloned(),
+                    }),
+                });
+            }
+        }
+        (TotValue::List(old), TotValue::List(new)) => {
+            for i in 0..old.len().max(new.len()) {
+                with_segment(path, &i.to_string(), |path| {
+                    match (old.get(i), new.get(i)) {
+                        (Some(old), Some(new)) => diff_into(changes, path, old, new),
+                        (old, new) => changes.push(Change {
+                            path: path.clone(),
+                            old: old.cloned(),
+                            new: new.cloned(),
+                        }),
+                    }
+                });
+            }
+        }
+        (old, new) if old != new => changes.push(Change {
+            path: path.clone(),
+            old: Some(old.clone()),
+            new: Some(new.clone()),
+        }),
+        _ => {}
+    }
+}
+
+/// Unescaped segments of a pointer path, or `None` if it doesn't start with `/`.
+fn pointer_segments(path: &str) -> Option<impl Iterator<Item = Cow<'_, str>>> {
+    if !path.is_empty() && !path.starts_with('/') {
+        return None;
+    }
+
+    // Splitting on `/` gives an empty first segment, or only that for an empty path
+    Some(path.split('/').skip(1).map(|segment| {
+        if segment.contains('~') {
+            Cow::Owned(segment.replace("~1", "/").replace("~0", "~"))
+        } else {
+            Cow::Borrowed(segment)
+        }
+    }))
+}
+
+/// A list index in a pointer path, which can't have a sign or leading zeroes.
+fn parse_index(segment: &str) -> Option<usize> {
+    if segment.starts_with('+') || (segment.len() > 1 && segment.starts_with('0')) {
+        return None;
+    }
+
+    segment.parse().ok()
+}
+
+/// Byte offsets of a parsed node in the source.
+pub type Span = Range<usize>;
+
+/// Source locations of a [`TotValue`] and its children, from [`parse_with_spans`].
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Spans {
+    /// Span of the value, including the brackets or braces of a list or dict.
+    pub span: Span,
+    pub children: SpanChildren,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum SpanChildren {
+    /// Scalars have no children.
+    None,
+    List(Vec<Spans>),
+    /// Dict entries in source order, including any duplicate keys.
+    Dict(Vec<EntrySpans>),
+}
+
+/// Source locations of a dict entry.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct EntrySpans {
+    pub key: String,
+    /// Span of the key, including quotes if it was quoted.
+    pub key_span: Span,
+    pub value: Spans,
+}
+
+impl Spans {
+    /// Spans of the dict entry for `key`.
+    ///
+    /// If the key appears more than once, this is the last entry, which is the one whose value
+    /// ends up in the [`TotValue`].
+    pub fn get(&self, key: &str) -> Option<&EntrySpans> {
+        match &self.children {
+            SpanChildren::Dict(entries) => entries.iter().rev().find(|e| e.key == key),
+            _ => None,
+        }
+    }
+
+    /// Spans of the list element at `index`.
+    pub fn index(&self, index: usize) -> Option<&Spans> {
+        match &self.children {
+            SpanChildren::List(elements) => elements.get(index),
+            _ => None,
+        }
+    }
+}
+
 pub type PResult<'a, T> = IResult<&'a str, T>;
 
-fn token(i: &str) -> PResult<&str> {
+fn token(i: &str) -> PResult<'_, &str> {
     take_till1(|c: char| c.is_whitespace())(i)
 }
 
-pub(crate) fn unit(i: &str) -> PResult<()> {
-    value((), tag("null"))(i)
+/// Whether `s` can be written as a key without quotes and still be read back by [`key`]. A byte
+/// order mark would be dropped from the first key of a document, so it's quoted anywhere.
+pub(crate) fn is_bare_key(s: &str) -> bool {
+    !s.is_empty()
+        && !s.chars().any(char::is_whitespace)
+        && !s.starts_with(['"', '\'', '{', '}', '[', ']', ',', '\u{feff}'])
+        && !s.starts_with("//")
+        && !s.starts_with("/*")
 }
 
-pub(crate) fn boolean(i: &str) -> PResult<bool> {
-    alt((value(true, tag("true")), value(false, tag("false"))))(i)
+/// `null`, not followed by more of a word like in `nullable`.
+pub(crate) fn unit(i: &str) -> PResult<'_, ()> {
+    value((), terminated(tag("null"), not(satisfy(is_word_char))))(i)
 }
 
-pub(crate) fn number(i: &str) -> PResult<f64> {
-    double(i)
+/// `true` or `false`, not followed by more of a word like in `trueish`.
+pub(crate) fn boolean(i: &str) -> PResult<'_, bool> {
+    terminated(
+        alt((value(true, tag("true")), value(false, tag("false")))),
+        not(satisfy(is_word_char)),
+    )(i)
 }
 
-pub(crate) fn string(i: &str) -> PResult<String> {
-    map(
-        delimited(tag("\""), take_till(|c: char| c == '"'), tag("\"")),
-        String::from,
+/// `1`, `on`, `0` or `off`, which can be read as booleans, see
+/// [`DeserializerOptions::coerce_bools`](crate::de::DeserializerOptions::coerce_bools).
+pub(crate) fn coerced_boolean(i: &str) -> PResult<'_, bool> {
+    terminated(
+        alt((
+            value(true, alt((tag("1"), tag("on")))),
+            value(false, alt((tag("0"), tag("off")))),
+        )),
+        not(satisfy(|c| is_word_char(c) || c == '.')),
+    )(i)
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '-'
+}
+
+/// Any of `keywords`, not followed by more of a word.
+fn keyword<'a>(keywords: &[String], i: &'a str) -> PResult<'a, ()> {
+    for keyword in keywords {
+        if let Some(rem) = i.strip_prefix(keyword.as_str()) {
+            if !rem.starts_with(is_word_char) {
+                return Ok((rem, ()));
+            }
+        }
+    }
+
+    Err(nom::Err::Error(nom::error::Error::new(i, ErrorKind::Tag)))
+}
+
+/// Digits that may be grouped with single underscores, e.g. `1_000`.
+fn digits(i: &str) -> PResult<'_, &str> {
+    recognize(separated_list1(char('_'), digit1))(i)
+}
+
+/// An integer as written, with an optional sign and any underscores, and without a fraction or
+/// exponent.
+pub(crate) fn integer_literal(i: &str) -> PResult<'_, &str> {
+    terminated(
+        recognize(pair(opt(one_of("+-")), digits)),
+        not(one_of("._eE")),
+    )(i)
+}
+
+/// An integer that fits in an `i64`.
+pub(crate) fn integer(i: &str) -> PResult<'_, i64> {
+    map_res(integer_literal, |s: &str| s.replace('_', "").parse::<i64>())(i)
+}
+
+/// Any number, including integers, with an optional `+` or `-` sign that works the same for
+/// both. `inf`, `infinity` and `nan` are numbers too, in any case.
+pub(crate) fn number(i: &str) -> PResult<'_, f64> {
+    let special = terminated(
+        recognize(pair(
+            opt(one_of("+-")),
+            alt((
+                tag_no_case("infinity"),
+                tag_no_case("inf"),
+                tag_no_case("nan"),
+            )),
+        )),
+        not(satisfy(is_word_char)),
+    );
+
+    map_res(
+        alt((
+            terminated(
+                recognize(tuple((
+                    opt(one_of("+-")),
+                    alt((
+                        recognize(pair(digits, opt(pair(char('.'), opt(digits))))),
+                        recognize(pair(char('.'), digits)),
+                    )),
+                    opt(tuple((one_of("eE"), opt(one_of("+-")), digits))),
+                ))),
+                not(char('_')),
+            ),
+            special,
+        )),
+        |s: &str| s.replace('_', "").parse::<f64>(),
+    )(i)
+}
+
+/// A `\u{XXXX}` escape with 1 to 6 hex digits.
+fn parse_unicode(i: &str) -> PResult<'_, char> {
+    map_opt(
+        map_res(
+            preceded(
+                char('u'),
+                delimited(
+                    char('{'),
+                    take_while_m_n(1, 6, |c: char| c.is_ascii_hexdigit()),
+                    char('}'),
+                ),
+            ),
+            |hex| u32::from_str_radix(hex, 16),
+        ),
+        std::char::from_u32,
+    )(i)
+}
+
+fn parse_escaped_char(i: &str) -> PResult<'_, char> {
+    preceded(
+        char('\\'),
+        alt((
+            parse_unicode,
+            value('\n', char('n')),
+            value('\r', char('r')),
+            value('\t', char('t')),
+            value('\u{08}', char('b')),
+            value('\u{0C}', char('f')),
+            value('\\', char('\\')),
+            value('/', char('/')),
+            value('"', char('"')),
+        )),
     )(i)
 }
 
-fn whitespace(i: &str) -> PResult<()> {
+fn parse_literal(i: &str) -> PResult<'_, &str> {
+    is_not("\"\\")(i)
+}
+
+enum StringFragment<'a> {
+    Literal(&'a str),
+    EscapedChar(char),
+}
+
+fn parse_fragment(i: &str) -> PResult<'_, StringFragment<'_>> {
+    alt((
+        map(parse_literal, StringFragment::Literal),
+        map(parse_escaped_char, StringFragment::EscapedChar),
+    ))(i)
+}
+
+/// A double-quoted string with escape sequences decoded.
+pub(crate) fn string(i: &str) -> PResult<'_, String> {
+    delimited(
+        char('"'),
+        fold_many0(parse_fragment, String::new, |mut s, fragment| {
+            match fragment {
+                StringFragment::Literal(v) => s.push_str(v),
+                StringFragment::EscapedChar(c) => s.push(c),
+            }
+            s
+        }),
+        char('"'),
+    )(i)
+}
+
+/// A double-quoted string without escape sequences, borrowed from the input.
+pub(crate) fn borrowed_string(i: &str) -> PResult<'_, &str> {
+    delimited(
+        char('"'),
+        map(opt(parse_literal), Option::unwrap_or_default),
+        char('"'),
+    )(i)
+}
+
+/// Base64 between `b"` and `"`, decoded.
+pub(crate) fn bytes(i: &str) -> PResult<'_, Vec<u8>> {
+    let base64 = take_while(|c: char| c.is_ascii_alphanumeric() || matches!(c, '+' | '/' | '='));
+    map_opt(
+        preceded(char('b'), delimited(char('"'), base64, char('"'))),
+        crate::base64::decode,
+    )(i)
+}
+
+fn whitespace(i: &str) -> PResult<'_, ()> {
     map(multispace1, |_| ())(i)
 }
 
-fn comma(i: &str) -> PResult<()> {
+fn comma(i: &str) -> PResult<'_, ()> {
     value((), tag(","))(i)
 }
 
-fn line_comment(i: &str) -> PResult<()> {
-    value((), pair(tag("//"), is_not("\r\n")))(i)
-}
+fn line_comment(i: &str) -> PResult<'_, ()> {
+    value((), pair(tag("//"), opt(is_not("\r\n"))))(i)
+}
+
+fn block_comment(i: &str) -> PResult<'_, ()> {
+    value((), tuple((tag("/*"), take_until("*/"), tag("*/"))))(i)
+}
+
+pub(crate) fn all_ignored(i: &str) -> PResult<'_, ()> {
+    map(
+        many0(alt((line_comment, block_comment, comma, whitespace))),
+        |_| (),
+    )(i)
+}
+
+/// Whitespace and comments, but not commas.
+fn ignored_without_commas(i: &str) -> PResult<'_, ()> {
+    map(
+        many0(alt((line_comment, block_comment, whitespace))),
+        |_| (),
+    )(i)
+}
+
+/// The comments in ignored input like that matched by [`all_ignored`], with their offsets.
+pub(crate) fn comments(i: &str) -> Vec<(usize, &str)> {
+    let mut comments = Vec::new();
+    let mut rest = i;
+    loop {
+        rest = rest.trim_start_matches(|c: char| c.is_whitespace() || c == ',');
+        match recognize(alt((line_comment, block_comment)))(rest) {
+            Ok((rem, comment)) => {
+                comments.push((i.offset(comment), comment));
+                rest = rem;
+            }
+            Err(_) => return comments,
+        }
+    }
+}
+
+/// A bare key as it's read, which is any token but one starting with a closing brace, so a dict
+/// ends rather than taking the brace as its next entry, or with a comma, which is only ever a
+/// separator. [`is_bare_key`] is stricter, so written keys stay unambiguous.
+fn key_token(i: &str) -> PResult<'_, &str> {
+    verify(token, |t: &str| !t.starts_with(['}', ',']))(i)
+}
+
+pub(crate) fn key(i: &str) -> PResult<'_, String> {
+    alt((map(string, String::from), map(key_token, String::from)))(i)
+}
+
+/// A key that can be borrowed from the input, because it's bare or quoted without escapes.
+pub(crate) fn borrowed_key(i: &str) -> PResult<'_, &str> {
+    alt((borrowed_string, key_token))(i)
+}
+
+/// A single value, like `1` or `{a [2 3]}`, as it's written.
+pub(crate) fn raw_value(i: &str) -> PResult<'_, &str> {
+    recognize(|i| Parser::default().scalar(i))(i)
+}
+
+#[allow(dead_code)]
+pub(crate) fn expression(_i: &str) -> PResult<'_, TotValue> {
+    todo!()
+}
+
+/// Line that separates documents in the input of [`parse_many`].
+pub const DOCUMENT_SEPARATOR: &str = "---";
+
+/// Byte order mark that some editors write at the start of UTF-8 files. It's skipped when parsing.
+pub const BYTE_ORDER_MARK: char = '\u{FEFF}';
+
+/// Skip a leading [`BYTE_ORDER_MARK`].
+pub(crate) fn strip_bom(i: &str) -> &str {
+    i.strip_prefix(BYTE_ORDER_MARK).unwrap_or(i)
+}
+
+/// Start of a comment on the first line of a document that declares the version of Tot it's
+/// written for, like `// tot: v1`. It's still a comment, so parsers that don't look for it skip
+/// it.
+pub const VERSION_PRAGMA: &str = "// tot:";
+
+/// The version declared by a [`VERSION_PRAGMA`] comment on the first line of `i`, if any.
+///
+/// ```
+/// assert_eq!(tot::parser::version("// tot: v1\na 1\n"), Some("v1"));
+/// assert_eq!(tot::parser::version("a 1 // tot: v1\n"), None);
+/// ```
+pub fn version(i: &str) -> Option<&str> {
+    let line = strip_bom(i).lines().next()?;
+    let version = line.trim().strip_prefix(VERSION_PRAGMA)?.trim();
+
+    (!version.is_empty()).then_some(version)
+}
+
+/// Key of a dict entry naming files to include in the dict, see [`Parser::parse_file`].
+pub const INCLUDE_KEY: &str = "include";
+
+/// Default maximum nesting depth of lists and dicts accepted by a [`Parser`].
+pub const DEFAULT_MAX_DEPTH: usize = 128;
+
+/// Options for a [`Parser`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseOptions {
+    true_keywords: Vec<String>,
+    false_keywords: Vec<String>,
+    null_keywords: Vec<String>,
+    strict_commas: bool,
+    max_depth: usize,
+    max_length: Option<usize>,
+    max_collection_size: Option<usize>,
+    trim_strings: bool,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        Self {
+            true_keywords: vec!["true".to_string()],
+            false_keywords: vec!["false".to_string()],
+            null_keywords: vec!["null".to_string()],
+            strict_commas: false,
+            max_depth: DEFAULT_MAX_DEPTH,
+            max_length: None,
+            max_collection_size: None,
+            trim_strings: false,
+        }
+    }
+}
+
+impl ParseOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Words read as `true`, instead of only `true`.
+    pub fn true_keywords<I, S>(mut self, keywords: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.true_keywords = keywords.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Words read as `false`, instead of only `false`.
+    pub fn false_keywords<I, S>(mut self, keywords: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.false_keywords = keywords.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Words read as null, instead of only `null`.
+    pub fn null_keywords<I, S>(mut self, keywords: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.null_keywords = keywords.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Only accept a comma right after a list element or dict entry, instead of anywhere
+    /// whitespace is. Each element or entry can be followed by at most one, so stray commas like
+    /// in `,a 1` or `[1,,2]` are errors.
+    pub fn strict_commas(mut self, strict_commas: bool) -> Self {
+        self.strict_commas = strict_commas;
+        self
+    }
+
+    /// Reject lists and dicts nested deeper than `max_depth`, instead of [`DEFAULT_MAX_DEPTH`].
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Reject input longer than `max_length` bytes before parsing it. With includes, the document
+    /// and all of the files it includes count together.
+    pub fn max_length(mut self, max_length: usize) -> Self {
+        self.max_length = Some(max_length);
+        self
+    }
+
+    /// Reject lists with more than `max_collection_size` elements and dicts with more than that
+    /// many entries, as soon as the limit is passed.
+    pub fn max_collection_size(mut self, max_collection_size: usize) -> Self {
+        self.max_collection_size = Some(max_collection_size);
+        self
+    }
+
+    /// Trim whitespace from the start and end of string values, so `"  hi  "` is read as `hi`.
+    /// Keys are kept as written.
+    pub fn trim_strings(mut self, trim_strings: bool) -> Self {
+        self.trim_strings = trim_strings;
+        self
+    }
+}
+
+/// Parser for Tot documents.
+///
+/// Lists and dicts are parsed recursively, so nesting is limited to a maximum depth to avoid
+/// overflowing the stack on pathological input.
+#[derive(Debug)]
+pub struct Parser {
+    options: ParseOptions,
+    depth: Cell<usize>,
+    /// Error raised from inside a nom combinator, returned instead of the generic nom failure.
+    error: RefCell<Option<Error>>,
+}
+
+impl Default for Parser {
+    fn default() -> Self {
+        Self::with_options(ParseOptions::default())
+    }
+}
+
+impl Parser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a parser that rejects lists and dicts nested deeper than `max_depth`.
+    pub fn with_max_depth(max_depth: usize) -> Self {
+        Self::with_options(ParseOptions::new().max_depth(max_depth))
+    }
+
+    /// Create a parser that parses as set in `options`.
+    ///
+    /// ```
+    /// use tot::parser::{ParseOptions, Parser};
+    /// use tot::TotValue;
+    ///
+    /// let options = ParseOptions::new()
+    ///     .true_keywords(["true", "yes"])
+    ///     .false_keywords(["false", "no"]);
+    /// let value = Parser::with_options(options).parse("enabled yes").unwrap();
+    ///
+    /// assert_eq!(
+    ///     value,
+    ///     TotValue::Dict([("enabled".to_string(), TotValue::Boolean(true))].into())
+    /// );
+    /// ```
+    pub fn with_options(options: ParseOptions) -> Self {
+        Self {
+            options,
+            depth: Cell::new(0),
+            error: RefCell::new(None),
+        }
+    }
+
+    /// Parse a document. Its root is a list if it starts with `[`, after any whitespace and
+    /// comments, and a dict otherwise, so an empty document is an empty dict. A document that
+    /// isn't a dict can also be a single value, like [`parse_value`](Self::parse_value) reads.
+    ///
+    /// ```
+    /// use tot::TotValue;
+    ///
+    /// assert_eq!(tot::parser::parse("[]").unwrap(), TotValue::List(vec![]));
+    /// assert_eq!(tot::parser::parse("").unwrap(), TotValue::Dict([].into()));
+    /// assert_eq!(tot::parser::parse("\"hi\"").unwrap(), TotValue::String("hi".to_string()));
+    /// ```
+    pub fn parse(&self, i: &str) -> Result<TotValue, Error> {
+        self.depth.set(0);
+        self.error.replace(None);
+        self.check_length(i)?;
+
+        if let Ok((rem, v)) = terminated(|i| self.root(i), |i| self.ignored(i))(strip_bom(i)) {
+            if rem.is_empty() {
+                return Ok(v);
+            }
+        }
+
+        // The error is about the document as a dict or list, which is what it most likely is
+        let error = self.error.take();
+        if let Ok(v) = self.parse_value(i) {
+            return Ok(v);
+        }
+
+        Err(error.unwrap_or(Error::ParseError))
+    }
+
+    /// Parse a single value, like `true`, `[1 2 3]` or `{a 1}`, instead of a document. Anything
+    /// but whitespace and comments after the value is an error.
+    pub fn parse_value(&self, i: &str) -> Result<TotValue, Error> {
+        self.depth.set(0);
+        self.error.replace(None);
+        self.check_length(i)?;
+
+        let value =
+            delimited(|i| self.ignored(i), |i| self.scalar(i), |i| self.ignored(i))(strip_bom(i));
+        if let Ok((rem, v)) = value {
+            if rem.is_empty() {
+                return Ok(v);
+            }
+        }
+
+        Err(self.error.take().unwrap_or(Error::ParseError))
+    }
+
+    /// Parse a file, resolving includes relative to the directory it's in.
+    ///
+    /// A dict entry with the key [`INCLUDE_KEY`] and a path or list of paths as its value is
+    /// replaced by the entries of those files. They're merged in order with [`TotValue::merge`],
+    /// and the dict's own entries override them. Included files can include others, but not any
+    /// file that's including them.
+    ///
+    /// ```no_run
+    /// // base.tot:   port 80 host "localhost"
+    /// // server.tot: include "base.tot" port 8080
+    /// let value = tot::parser::parse_file("server.tot").unwrap();
+    /// ```
+    pub fn parse_file<P: AsRef<Path>>(&self, path: P) -> Result<TotValue, Error> {
+        self.parse_included(path.as_ref(), &mut Vec::new(), &mut 0)
+    }
+
+    /// Parse a document, resolving includes relative to `base_dir`. See [`Parser::parse_file`].
+    pub fn parse_with_includes(&self, i: &str, base_dir: &Path) -> Result<TotValue, Error> {
+        let mut value = self.parse(i)?;
+        self.resolve_includes(&mut value, base_dir, &mut Vec::new(), &mut i.len())?;
+
+        Ok(value)
+    }
+
+    /// Parse an included file. `including` is the chain of files including it, and `read` is the
+    /// length of the document and the files included so far.
+    fn parse_included(
+        &self,
+        path: &Path,
+        including: &mut Vec<PathBuf>,
+        read: &mut usize,
+    ) -> Result<TotValue, Error> {
+        let include_error = |e: std::io::Error| {
+            Error::IncludeError(format!("cannot read `{}`: {e}", path.display()))
+        };
+
+        let path = path.canonicalize().map_err(include_error)?;
+        if including.contains(&path) {
+            return Err(Error::IncludeError(format!(
+                "cyclic include of `{}`",
+                path.display()
+            )));
+        }
+
+        let input = std::fs::read_to_string(&path).map_err(include_error)?;
+        *read += input.len();
+        if let Some(max) = self.options.max_length.filter(|&max| *read > max) {
+            return Err(Error::SizeError(format!(
+                "input and included files are longer than the maximum of {max} bytes"
+            )));
+        }
+        let mut value = self.parse(&input)?;
+
+        let base_dir = path.parent().unwrap_or(Path::new("")).to_path_buf();
+        including.push(path);
+        let r = self.resolve_includes(&mut value, &base_dir, including, read);
+        including.pop();
+
+        r.map(|_| value)
+    }
+
+    /// Replace include entries in `value` and every dict in it.
+    fn resolve_includes(
+        &self,
+        value: &mut TotValue,
+        base_dir: &Path,
+        including: &mut Vec<PathBuf>,
+        read: &mut usize,
+    ) -> Result<(), Error> {
+        match value {
+            TotValue::List(list) => {
+                for v in list {
+                    self.resolve_includes(v, base_dir, including, read)?;
+                }
+            }
+            TotValue::Dict(dict) => {
+                for v in dict.values_mut() {
+                    self.resolve_includes(v, base_dir, including, read)?;
+                }
+
+                let paths = match dict.remove(INCLUDE_KEY) {
+                    None => return Ok(()),
+                    Some(TotValue::String(path)) => vec![TotValue::String(path)],
+                    Some(TotValue::List(paths)) => paths,
+                    Some(other) => {
+                        return Err(Error::IncludeError(format!(
+                            "expected a path or list of paths, found {}",
+                            other.type_name()
+                        )))
+                    }
+                };
+
+                let mut merged = TotValue::Dict(HashMap::new());
+                for path in paths {
+                    let TotValue::String(path) = path else {
+                        return Err(Error::IncludeError(format!(
+                            "expected a path, found {}",
+                            path.type_name()
+                        )));
+                    };
+
+                    let included = self.parse_included(&base_dir.join(path), including, read)?;
+                    merged.merge(included);
+                }
+
+                merged.merge(TotValue::Dict(std::mem::take(dict)));
+                *value = merged;
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    /// Parse a document, skipping every root dict entry that can't be parsed instead of stopping
+    /// at the first.
+    ///
+    /// When an entry fails, the rest of its line is skipped and parsing goes on from the next
+    /// one. Every failure is returned as a [`Error::LineError`], along with the dict of the
+    /// entries that could be read. The dict is `None` if there were errors and no entry could
+    /// be read at all. An entry spanning several lines may fail again partway through.
+    ///
+    /// ```
+    /// let (value, errors) = tot::parser::parse_recover("a 1\nb [2\nc 3\n");
+    ///
+    /// assert_eq!(value, tot::parser::parse("a 1 c 3").ok());
+    /// assert_eq!(errors.len(), 1);
+    /// assert_eq!(errors[0].to_string(), "line 2: error ocurred while parsing");
+    /// ```
+    pub fn parse_recover(&self, i: &str) -> (Option<TotValue>, Vec<Error>) {
+        if let Err(e) = self.check_length(i) {
+            return (None, vec![e]);
+        }
+        let i = strip_bom(i);
+        let mut entries = HashMap::new();
+        let mut errors = Vec::new();
+
+        let mut rest = i;
+        loop {
+            if let Ok((r, _)) = self.ignored(rest) {
+                rest = r;
+            }
+            if rest.is_empty() {
+                break;
+            }
+
+            self.depth.set(0);
+            self.error.replace(None);
+            match self.key_value(rest) {
+                Ok((r, (k, v))) => {
+                    entries.insert(k, v);
+                    rest = r;
+                    if let Some(max) = self.options.max_collection_size {
+                        if entries.len() > max {
+                            errors.push(Error::DictError(format!("more than {max} entries")));
+                            break;
+                        }
+                    }
+                }
+                Err(_) => {
+                    let line = i[..i.offset(rest)].matches('\n').count() + 1;
+                    let error = self.error.take().unwrap_or(Error::ParseError);
+                    errors.push(Error::LineError {
+                        line,
+                        error: Box::new(error),
+                    });
+                    rest = rest.split_once('\n').map_or("", |(_, next)| next);
+                }
+            }
+        }
+
+        let value = (errors.is_empty() || !entries.is_empty()).then_some(TotValue::Dict(entries));
+        (value, errors)
+    }
+
+    /// Parse several documents separated by lines containing only [`DOCUMENT_SEPARATOR`].
+    ///
+    /// Every segment is parsed on its own, so comments before or after a separator belong to the
+    /// document they're in, and an empty segment is an empty dict. The input is split before
+    /// parsing, so a separator line inside a multi-line string or block comment still splits it.
+    /// A maximum length applies to the whole input.
+    pub fn parse_many(&self, i: &str) -> Result<Vec<TotValue>, Error> {
+        self.check_length(i)?;
+
+        let mut documents = Vec::new();
+        let mut start = 0;
+        let mut offset = 0;
+        for line in i.split_inclusive('\n') {
+            if line.trim() == DOCUMENT_SEPARATOR {
+                documents.push(self.parse(&i[start..offset])?);
+                start = offset + line.len();
+            }
+            offset += line.len();
+        }
+        documents.push(self.parse(&i[start..])?);
+
+        Ok(documents)
+    }
+
+    /// Parse a document along with the source location of every key and value. The document is
+    /// read like [`parse`](Self::parse) does, and a root dict without braces spans the whole
+    /// input.
+    pub fn parse_with_spans(&self, i: &str) -> Result<(TotValue, Spans), Error> {
+        self.depth.set(0);
+        self.error.replace(None);
+        self.check_length(i)?;
+
+        let root = |rest| self.spanned_root(i, rest);
+        if let Ok((rem, v)) = terminated(root, |i| self.ignored(i))(strip_bom(i)) {
+            if rem.is_empty() {
+                return Ok(v);
+            }
+        }
+
+        // Otherwise it can be a single value, like `parse` allows
+        let error = self.error.take();
+        self.depth.set(0);
+        let value = delimited(
+            |i| self.ignored(i),
+            |rest| self.spanned_scalar(i, rest),
+            |i| self.ignored(i),
+        )(strip_bom(i));
+        if let Ok((rem, v)) = value {
+            if rem.is_empty() {
+                return Ok(v);
+            }
+        }
+
+        Err(error.unwrap_or(Error::ParseError))
+    }
+
+    /// Parse the contents of a nested list or dict, tracking the nesting depth.
+    fn nested<'a, T, F>(
+        &self,
+        i: &'a str,
+        error: fn(String) -> Error,
+        mut contents: F,
+    ) -> PResult<'a, T>
+    where
+        F: FnMut(&'a str) -> PResult<'a, T>,
+    {
+        let depth = self.depth.get() + 1;
+        if depth > self.options.max_depth {
+            self.error.replace(Some(error(format!(
+                "maximum depth of {} exceeded",
+                self.options.max_depth
+            ))));
+
+            return Err(nom::Err::Failure(nom::error::Error::new(
+                i,
+                ErrorKind::TooLarge,
+            )));
+        }
+
+        self.depth.set(depth);
+        let r = contents(i);
+        self.depth.set(depth - 1);
+
+        r
+    }
+
+    fn list<'a>(&self, i: &'a str) -> PResult<'a, TotValue> {
+        let (i, _) = tag("[")(i)?;
+        self.nested(i, Error::ListError, |i| {
+            terminated(
+                |i| self.list_contents(i),
+                preceded(|i| self.ignored(i), tag("]")),
+            )(i)
+        })
+    }
+
+    fn list_contents<'a>(&self, i: &'a str) -> PResult<'a, TotValue> {
+        map(
+            many0(self.limited(
+                Error::ListError,
+                "elements",
+                delimited(
+                    |i| self.ignored(i),
+                    |i| self.scalar(i),
+                    |i| self.element_separator(i),
+                ),
+            )),
+            TotValue::List,
+        )(i)
+    }
+
+    /// What's after a list element. Elements have to be separated by whitespace, a comment or a
+    /// comma, so `[-1-2]` is an error rather than two numbers.
+    fn element_separator<'a>(&self, i: &'a str) -> PResult<'a, ()> {
+        let (rem, _) = self.separator(i)?;
+        if rem.len() == i.len() && !rem.is_empty() && !rem.starts_with(']') {
+            let next: String = rem.chars().take(10).collect();
+            self.error.replace(Some(Error::ListError(format!(
+                "expected whitespace or a comma between list elements, found `{next}`"
+            ))));
+            return Err(nom::Err::Failure(nom::error::Error::new(
+                rem,
+                ErrorKind::Verify,
+            )));
+        }
+
+        Ok((rem, ()))
+    }
+
+    fn check_length(&self, i: &str) -> Result<(), Error> {
+        match self.options.max_length {
+            Some(max) if i.len() > max => Err(Error::SizeError(format!(
+                "input is longer than the maximum of {max} bytes"
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Wrap the parser of one list element or dict entry so it fails once it's matched more than
+    /// the maximum collection size.
+    fn limited<'s, 'a, O, F>(
+        &'s self,
+        error: fn(String) -> Error,
+        items: &'static str,
+        mut f: F,
+    ) -> impl FnMut(&'a str) -> PResult<'a, O> + 's
+    where
+        F: FnMut(&'a str) -> PResult<'a, O> + 's,
+    {
+        let mut count = 0;
+        move |i| {
+            let (rem, o) = f(i)?;
+            count += 1;
+            match self.options.max_collection_size {
+                Some(max) if count > max => {
+                    self.error
+                        .replace(Some(error(format!("more than {max} {items}"))));
+                    Err(nom::Err::Failure(nom::error::Error::new(
+                        i,
+                        ErrorKind::TooLarge,
+                    )))
+                }
+                _ => Ok((rem, o)),
+            }
+        }
+    }
+
+    fn dict<'a>(&self, i: &'a str) -> PResult<'a, TotValue> {
+        let (i, _) = tag("{")(i)?;
+        self.nested(i, Error::DictError, |i| {
+            terminated(
+                |i| self.dict_contents(i),
+                preceded(|i| self.ignored(i), tag("}")),
+            )(i)
+        })
+    }
+
+    fn dict_contents<'a>(&self, i: &'a str) -> PResult<'a, TotValue> {
+        map(
+            many0(self.limited(Error::DictError, "entries", |i| self.key_value(i))),
+            |v| TotValue::Dict(HashMap::from_iter(v)),
+        )(i)
+    }
+
+    fn unit<'a>(&self, i: &'a str) -> PResult<'a, ()> {
+        keyword(&self.options.null_keywords, i)
+    }
+
+    fn boolean<'a>(&self, i: &'a str) -> PResult<'a, bool> {
+        alt((
+            value(true, |i| keyword(&self.options.true_keywords, i)),
+            value(false, |i| keyword(&self.options.false_keywords, i)),
+        ))(i)
+    }
+
+    fn string<'a>(&self, i: &'a str) -> PResult<'a, String> {
+        let (i, s) = string(i)?;
+        match self.options.trim_strings {
+            true => Ok((i, s.trim().to_string())),
+            false => Ok((i, s)),
+        }
+    }
+
+    /// The root of a document, which is a list or the contents of a dict.
+    fn root<'a>(&self, i: &'a str) -> PResult<'a, TotValue> {
+        let (i, _) = self.ignored(i)?;
+        if i.starts_with('[') {
+            self.list(i)
+        } else {
+            self.dict_contents(i)
+        }
+    }
+
+    // TODO missing s-expressions
+    fn scalar<'a>(&self, i: &'a str) -> PResult<'a, TotValue> {
+        alt((
+            map(|i| self.unit(i), |_| TotValue::Unit),
+            map(|i| self.boolean(i), TotValue::Boolean),
+            // Integers are tried first so they're kept exact
+            map(integer, TotValue::Integer),
+            map(number, TotValue::Float),
+            map(|i| self.string(i), TotValue::String),
+            map(bytes, TotValue::Bytes),
+            |i| self.list(i),
+            |i| self.dict(i),
+        ))(i)
+    }
+
+    fn key_value<'a>(&self, i: &'a str) -> PResult<'a, (String, TotValue)> {
+        delimited(
+            |i| self.ignored(i),
+            separated_pair(key, |i| self.ignored(i), |i| self.scalar(i)),
+            |i| self.separator(i),
+        )(i)
+    }
+
+    /// Whitespace and comments, and commas unless they're strict.
+    fn ignored<'a>(&self, i: &'a str) -> PResult<'a, ()> {
+        if self.options.strict_commas {
+            ignored_without_commas(i)
+        } else {
+            all_ignored(i)
+        }
+    }
+
+    /// What's ignored after a list element or dict entry, which can include one comma when
+    /// they're strict.
+    fn separator<'a>(&self, i: &'a str) -> PResult<'a, ()> {
+        if self.options.strict_commas {
+            value(
+                (),
+                tuple((ignored_without_commas, opt(comma), ignored_without_commas)),
+            )(i)
+        } else {
+            all_ignored(i)
+        }
+    }
+
+    // The spanned parsers mirror the ones above, with `src` being the whole document so offsets
+    // can be taken from how much input remains.
+
+    fn spanned_list<'a>(&self, src: &'a str, i: &'a str) -> PResult<'a, (TotValue, SpanChildren)> {
+        let (i, _) = tag("[")(i)?;
+        self.nested(i, Error::ListError, |i| {
+            terminated(
+                |i| self.spanned_list_contents(src, i),
+                preceded(|i| self.ignored(i), tag("]")),
+            )(i)
+        })
+    }
+
+    fn spanned_list_contents<'a>(
+        &self,
+        src: &'a str,
+        i: &'a str,
+    ) -> PResult<'a, (TotValue, SpanChildren)> {
+        map(
+            many0(self.limited(
+                Error::ListError,
+                "elements",
+                delimited(
+                    |i| self.ignored(i),
+                    |i| self.spanned_scalar(src, i),
+                    |i| self.element_separator(i),
+                ),
+            )),
+            |v| {
+                let (values, spans) = v.into_iter().unzip();
+                (TotValue::List(values), SpanChildren::List(spans))
+            },
+        )(i)
+    }
+
+    fn spanned_dict<'a>(&self, src: &'a str, i: &'a str) -> PResult<'a, (TotValue, SpanChildren)> {
+        let (i, _) = tag("{")(i)?;
+        self.nested(i, Error::DictError, |i| {
+            terminated(
+                |i| self.spanned_dict_contents(src, i),
+                preceded(|i| self.ignored(i), tag("}")),
+            )(i)
+        })
+    }
+
+    fn spanned_dict_contents<'a>(
+        &self,
+        src: &'a str,
+        i: &'a str,
+    ) -> PResult<'a, (TotValue, SpanChildren)> {
+        let entry = |i| self.spanned_key_value(src, i);
+        map(
+            many0(self.limited(Error::DictError, "entries", entry)),
+            |v| {
+                let (values, spans): (HashMap<_, _>, _) =
+                    v.into_iter().map(|(k, v, spans)| ((k, v), spans)).unzip();
+                (TotValue::Dict(values), SpanChildren::Dict(spans))
+            },
+        )(i)
+    }
+
+    /// The root of a document, like [`root`](Self::root).
+    fn spanned_root<'a>(&self, src: &'a str, i: &'a str) -> PResult<'a, (TotValue, Spans)> {
+        let (i, _) = self.ignored(i)?;
+        if i.starts_with('[') {
+            return self.spanned_scalar(src, i);
+        }
+
+        let (i, (v, children)) = self.spanned_dict_contents(src, i)?;
+        Ok((
+            i,
+            (
+                v,
+                Spans {
+                    span: 0..src.len(),
+                    children,
+                },
+            ),
+        ))
+    }
+
+    fn spanned_scalar<'a>(&self, src: &'a str, i: &'a str) -> PResult<'a, (TotValue, Spans)> {
+        let start = src.offset(i);
+        let (i, (v, children)) = alt((
+            |i| self.spanned_list(src, i),
+            |i| self.spanned_dict(src, i),
+            map(|i| self.scalar(i), |v| (v, SpanChildren::None)),
+        ))(i)?;
+
+        Ok((
+            i,
+            (
+                v,
+                Spans {
+                    span: start..src.offset(i),
+                    children,
+                },
+            ),
+        ))
+    }
+
+    fn spanned_key_value<'a>(
+        &self,
+        src: &'a str,
+        i: &'a str,
+    ) -> PResult<'a, (String, TotValue, EntrySpans)> {
+        let (i, _) = self.ignored(i)?;
+        let start = src.offset(i);
+        let (i, k) = key(i)?;
+        let key_span = start..src.offset(i);
+        let (i, _) = self.ignored(i)?;
+        let (i, (v, value)) = self.spanned_scalar(src, i)?;
+        let (i, _) = self.separator(i)?;
+
+        Ok((
+            i,
+            (
+                k.clone(),
+                v,
+                EntrySpans {
+                    key: k,
+                    key_span,
+                    value,
+                },
+            ),
+        ))
+    }
+}
+
+pub fn parse(i: &str) -> Result<TotValue, Error> {
+    Parser::default().parse(i)
+}
+
+/// Parse a single value rather than a document. See [`Parser::parse_value`].
+///
+/// ```
+/// use tot::TotValue;
+///
+/// assert_eq!(tot::parser::parse_value("true").unwrap(), TotValue::Boolean(true));
+/// assert!(tot::parser::parse_value("true false").is_err());
+/// ```
+pub fn parse_value(i: &str) -> Result<TotValue, Error> {
+    Parser::default().parse_value(i)
+}
+
+/// Parse a document as set in `options`, along with the version it declares with a
+/// [`VERSION_PRAGMA`], if any.
+///
+/// Which versions are known is up to the caller, like a tool that warns about unknown ones.
+///
+/// ```
+/// use tot::parser::{parse_with_options, ParseOptions};
+///
+/// let (value, version) = parse_with_options("// tot: v1\na 1\n", ParseOptions::new()).unwrap();
+///
+/// assert_eq!(value, tot::parser::parse("a 1").unwrap());
+/// assert_eq!(version.as_deref(), Some("v1"));
+/// ```
+pub fn parse_with_options(
+    i: &str,
+    options: ParseOptions,
+) -> Result<(TotValue, Option<String>), Error> {
+    let value = Parser::with_options(options).parse(i)?;
+
+    Ok((value, version(i).map(String::from)))
+}
+
+/// Parse a file, resolving includes. See [`Parser::parse_file`].
+pub fn parse_file<P: AsRef<Path>>(path: P) -> Result<TotValue, Error> {
+    Parser::default().parse_file(path)
+}
+
+/// Parse a document, collecting an error for every root dict entry that can't be parsed. See
+/// [`Parser::parse_recover`].
+pub fn parse_recover(i: &str) -> (Option<TotValue>, Vec<Error>) {
+    Parser::default().parse_recover(i)
+}
+
+/// Parse several documents separated by `---` lines. See [`Parser::parse_many`].
+pub fn parse_many(i: &str) -> Result<Vec<TotValue>, Error> {
+    Parser::default().parse_many(i)
+}
+
+/// Parse a document along with a parallel tree of the byte spans of its keys and values.
+///
+/// ```
+/// let input = "name \"tot\"\nports [80 443]\n";
+/// let (_, spans) = tot::parser::parse_with_spans(input).unwrap();
+///
+/// let ports = spans.get("ports").unwrap();
+/// assert_eq!(&input[ports.key_span.clone()], "ports");
+/// assert_eq!(&input[ports.value.span.clone()], "[80 443]");
+/// assert_eq!(&input[ports.value.index(1).unwrap().span.clone()], "443");
+/// ```
+pub fn parse_with_spans(i: &str) -> Result<(TotValue, Spans), Error> {
+    Parser::default().parse_with_spans(i)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn list(i: &str) -> PResult<'_, TotValue> {
+        Parser::default().list(i)
+    }
+
+    fn dict(i: &str) -> PResult<'_, TotValue> {
+        Parser::default().dict(i)
+    }
+
+    fn scalar(i: &str) -> PResult<'_, TotValue> {
+        Parser::default().scalar(i)
+    }
+
+    fn key_value(i: &str) -> PResult<'_, (String, TotValue)> {
+        Parser::default().key_value(i)
+    }
+
+    #[test]
+    fn test_parse() {
+        if let TotValue::Dict(v) = parse("test 1").unwrap() {
+            assert_eq!(
+                v.get_key_value("test").unwrap(),
+                (&"test".to_string(), &TotValue::Integer(1))
+            );
+        } else {
+            unreachable!();
+        }
+
+        if let TotValue::Dict(v) = parse("test 1 blah true").unwrap() {
+            assert_eq!(v.get("test").unwrap(), &TotValue::Integer(1));
+            assert_eq!(v.get("blah").unwrap(), &TotValue::Boolean(true));
+        } else {
+            unreachable!();
+        }
+
+        if let TotValue::Dict(v) = parse(
+            "\
+test 1
+blah true
+dict {
+    hello \"world\"
+}
+",
+        )
+        .unwrap()
+        {
+            assert_eq!(v.get("test").unwrap(), &TotValue::Integer(1));
+            assert_eq!(v.get("blah").unwrap(), &TotValue::Boolean(true));
+            assert_eq!(
+                v.get("dict").unwrap(),
+                &TotValue::Dict({
+                    let mut m = HashMap::new();
+                    m.insert("hello".to_string(), TotValue::String("world".to_string()));
+
+                    m
+                })
+            );
+        } else {
+            unreachable!();
+        }
+
+        assert_eq!(parse(" \n").unwrap(), TotValue::Dict(HashMap::new()));
+        assert_eq!(parse("").unwrap(), TotValue::Dict(HashMap::new()));
+        assert_eq!(parse("[]").unwrap(), TotValue::List(vec![]));
+        assert_eq!(
+            parse("// root\n[\n    1\n    {a 2}\n]\n").unwrap(),
+            TotValue::List(vec![
+                TotValue::Integer(1),
+                TotValue::Dict(HashMap::from([("a".to_string(), TotValue::Integer(2))]))
+            ])
+        );
+        assert!(parse("[] []").is_err());
+        assert!(parse("[] a 1").is_err());
+
+        // A single value
+        assert_eq!(parse("\"hi\"").unwrap(), TotValue::String("hi".to_string()));
+        assert_eq!(parse(" 1.5 // one\n").unwrap(), TotValue::Float(1.5));
+        assert_eq!(parse("-2").unwrap(), TotValue::Integer(-2));
+        assert_eq!(parse("true").unwrap(), TotValue::Boolean(true));
+        assert_eq!(parse("null").unwrap(), TotValue::Unit);
+        assert_eq!(
+            parse("{a 1}").unwrap(),
+            TotValue::Dict(HashMap::from([("a".to_string(), TotValue::Integer(1))]))
+        );
+        assert!(parse("\"hi\" \"there\" 1").is_err());
+        assert!(parse("hi").is_err());
+        assert_eq!(
+            parse("a {\n}\n\"[\" null").unwrap(),
+            parse("\"[\" null a {}").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_version() {
+        let input = "// tot: v1\nname \"tot\"\n";
+        let (value, version) = parse_with_options(input, ParseOptions::new()).unwrap();
+        assert_eq!(value, parse("name \"tot\"").unwrap());
+        assert_eq!(version.as_deref(), Some("v1"));
+
+        let (value, version) = parse_with_options("name \"tot\"\n", ParseOptions::new()).unwrap();
+        assert_eq!(value, parse("name \"tot\"").unwrap());
+        assert_eq!(version, None);
+
+        assert_eq!(super::version("\u{FEFF}  // tot:   v2 \r\n"), Some("v2"));
+        assert_eq!(super::version("// tot:\n"), None);
+        assert_eq!(super::version("// tot v1\n"), None);
+        assert_eq!(super::version("\n// tot: v1\n"), None);
+        assert_eq!(super::version(""), None);
+
+        assert!(parse_with_options("// tot: v1\n[", ParseOptions::new()).is_err());
+    }
+
+    #[test]
+    fn test_parse_bom() {
+        let input = "\u{FEFF}a 1\n";
+        assert_eq!(parse(input).unwrap(), parse("a 1").unwrap());
+
+        let (_, spans) = parse_with_spans(input).unwrap();
+        assert_eq!(&input[spans.get("a").unwrap().key_span.clone()], "a");
+    }
+
+    #[test]
+    fn test_parse_value() {
+        assert_eq!(parse_value("null").unwrap(), TotValue::Unit);
+        assert_eq!(parse_value("false").unwrap(), TotValue::Boolean(false));
+        assert_eq!(parse_value(" -12 ").unwrap(), TotValue::Integer(-12));
+        assert_eq!(parse_value("1.5e3").unwrap(), TotValue::Float(1500.0));
+        assert_eq!(
+            parse_value("\"a b\" // comment\n").unwrap(),
+            TotValue::String("a b".to_string())
+        );
+        assert_eq!(
+            parse_value("[1 2 3]").unwrap(),
+            TotValue::List(vec![
+                TotValue::Integer(1),
+                TotValue::Integer(2),
+                TotValue::Integer(3)
+            ])
+        );
+        assert_eq!(
+            parse_value("{a 1}").unwrap(),
+            TotValue::Dict([("a".to_string(), TotValue::Integer(1))].into())
+        );
+
+        assert!(matches!(parse_value(""), Err(Error::ParseError)));
+        assert!(matches!(parse_value("1 2"), Err(Error::ParseError)));
+        assert!(matches!(parse_value("a 1"), Err(Error::ParseError)));
+        assert!(matches!(parse_value("[1"), Err(Error::ParseError)));
+    }
+
+    #[test]
+    fn test_parse_max_depth() {
+        let input = format!("a {}{}", "[".repeat(5000), "]".repeat(5000));
+        assert!(matches!(parse(&input), Err(Error::ListError(_))));
+
+        let input = format!("a {}{}", "{b ".repeat(5000), "}".repeat(5000));
+        assert!(matches!(parse(&input), Err(Error::DictError(_))));
+
+        let parser = Parser::with_max_depth(2);
+        assert!(parser.parse("a [[1]]").is_ok());
+        assert!(matches!(
+            parser.parse("a [[[1]]]"),
+            Err(Error::ListError(_))
+        ));
+        assert!(matches!(
+            parser.parse("a {b [1]} c [{d {}}]"),
+            Err(Error::DictError(_))
+        ));
+
+        // The depth is reset between documents
+        assert!(parser.parse("a [[1]]").is_ok());
+
+        // The depth limit combines with other options
+        let parser = Parser::with_options(ParseOptions::new().max_depth(1).trim_strings(true));
+        assert_eq!(
+            parser.parse("a [\" x \"]").unwrap(),
+            parse("a [\"x\"]").unwrap()
+        );
+        assert!(matches!(
+            parser.parse("a [[\"x\"]]"),
+            Err(Error::ListError(_))
+        ));
+    }
+
+    #[test]
+    fn test_parse_with_spans() {
+        let input = "\
+a 1
+// comment
+\"b c\" {
+    d [true \"x\"]
+}
+a 2
+";
+        let (value, spans) = parse_with_spans(input).unwrap();
+        assert_eq!(value, parse(input).unwrap());
+        assert_eq!(spans.span, 0..input.len());
+
+        // The last duplicate key wins, like in the parsed value
+        let a = spans.get("a").unwrap();
+        assert_eq!(a.key_span, 42..43);
+        assert_eq!(a.value.span, 44..45);
+        assert_eq!(a.value.children, SpanChildren::None);
+
+        let b = spans.get("b c").unwrap();
+        assert_eq!(&input[b.key_span.clone()], "\"b c\"");
+        assert_eq!(&input[b.value.span.clone()], "{\n    d [true \"x\"]\n}");
+
+        let d = b.value.get("d").unwrap();
+        assert_eq!(&input[d.key_span.clone()], "d");
+        assert_eq!(&input[d.value.span.clone()], "[true \"x\"]");
+        assert_eq!(&input[d.value.index(0).unwrap().span.clone()], "true");
+        assert_eq!(&input[d.value.index(1).unwrap().span.clone()], "\"x\"");
+        assert!(d.value.index(2).is_none());
+        assert!(d.value.get("x").is_none());
+
+        assert!(parse_with_spans("a [1").is_err());
+    }
+
+    #[test]
+    fn test_parse_with_spans_roots() {
+        for input in ["", "// only a comment\n", "  a 1 // trailing\n"] {
+            let (value, spans) = parse_with_spans(input).unwrap();
+            assert_eq!(value, parse(input).unwrap());
+            assert_eq!(spans.span, 0..input.len());
+            assert!(matches!(spans.children, SpanChildren::Dict(_)));
+        }
+
+        let input = "// list\n[1 [2]]\n";
+        let (value, spans) = parse_with_spans(input).unwrap();
+        assert_eq!(value, parse(input).unwrap());
+        assert_eq!(&input[spans.span.clone()], "[1 [2]]");
+        assert_eq!(&input[spans.index(1).unwrap().span.clone()], "[2]");
+
+        for (input, source) in [("1\n", "1"), (" \"hi\" // s", "\"hi\""), ("{a 1}", "{a 1}")] {
+            let (value, spans) = parse_with_spans(input).unwrap();
+            assert_eq!(value, parse(input).unwrap());
+            assert_eq!(&input[spans.span.clone()], source);
+        }
+        let (_, spans) = parse_with_spans("1").unwrap();
+        assert_eq!(spans.children, SpanChildren::None);
+
+        assert!(parse_with_spans("[1] 2").is_err());
+        assert!(parse_with_spans("a").is_err());
+    }
+
+    /// A fresh directory for test files.
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("tot-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+
+        dir
+    }
+
+    #[test]
+    fn test_parse_file_include() {
+        let dir = test_dir("include");
+        std::fs::create_dir(dir.join("common")).unwrap();
+        std::fs::write(
+            dir.join("common/base.tot"),
+            "host \"localhost\"\nport 80\ntls { enabled false }",
+        )
+        .unwrap();
+        std::fs::write(dir.join("common/tls.tot"), "tls { enabled true }").unwrap();
+        std::fs::write(
+            dir.join("server.tot"),
+            "include [\"common/base.tot\" \"common/tls.tot\"]\nport 8080\nextra { include \"common/tls.tot\" }",
+        )
+        .unwrap();
+
+        let expected = parse(
+            "host \"localhost\" port 8080 tls { enabled true } extra { tls { enabled true } }",
+        )
+        .unwrap();
+        assert_eq!(parse_file(dir.join("server.tot")).unwrap(), expected);
+        assert_eq!(
+            Parser::default()
+                .parse_with_includes("include \"common/base.tot\"", &dir)
+                .unwrap(),
+            parse("host \"localhost\" port 80 tls { enabled false }").unwrap()
+        );
+
+        assert!(matches!(
+            parse_file(dir.join("missing.tot")),
+            Err(Error::IncludeError(_))
+        ));
+        assert!(matches!(
+            Parser::default().parse_with_includes("include 1", &dir),
+            Err(Error::IncludeError(_))
+        ));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_parse_file_include_max_length() {
+        let dir = test_dir("include-max-length");
+        std::fs::write(dir.join("a.tot"), "include \"b.tot\" a 1").unwrap();
+        std::fs::write(dir.join("b.tot"), "b \"1234567890\"").unwrap();
+
+        let parser = |max| Parser::with_options(ParseOptions::new().max_length(max));
+        // 19 bytes in a.tot and 14 in b.tot
+        assert!(parser(33).parse_file(dir.join("a.tot")).is_ok());
+        for err in [
+            parser(32).parse_file(dir.join("a.tot")).unwrap_err(),
+            parser(32)
+                .parse_with_includes("include \"b.tot\" a 1", &dir)
+                .unwrap_err(),
+        ] {
+            assert_eq!(
+                err.to_string(),
+                "size error: input and included files are longer than the maximum of 32 bytes"
+            );
+        }
 
-fn block_comment(i: &str) -> PResult<()> {
-    value((), tuple((tag("/*"), take_until("*/"), tag("*/"))))(i)
-}
+        std::fs::remove_dir_all(dir).unwrap();
+    }
 
-pub(crate) fn all_ignored(i: &str) -> PResult<()> {
-    map(
-        many0(alt((line_comment, block_comment, comma, whitespace))),
-        |_| (),
-    )(i)
-}
+    #[test]
+    fn test_parse_file_include_cycle() {
+        let dir = test_dir("include-cycle");
+        std::fs::write(dir.join("a.tot"), "include \"b.tot\" a 1").unwrap();
+        std::fs::write(dir.join("b.tot"), "include \"./a.tot\" b 2").unwrap();
+        std::fs::write(dir.join("self.tot"), "nested { include \"self.tot\" }").unwrap();
+
+        for file in ["a.tot", "self.tot"] {
+            let err = parse_file(dir.join(file)).unwrap_err();
+            assert!(matches!(err, Error::IncludeError(_)), "{err}");
+            assert!(err.to_string().contains("cyclic include of"), "{err}");
+        }
 
-fn list(i: &str) -> PResult<TotValue> {
-    delimited(tag("["), list_contents, tag("]"))(i)
-}
+        std::fs::remove_dir_all(dir).unwrap();
+    }
 
-fn list_contents(i: &str) -> PResult<TotValue> {
-    map(many0(delimited(all_ignored, scalar, all_ignored)), |v| {
-        TotValue::List(v)
-    })(i)
-}
+    #[test]
+    fn test_arbitrary_round_trip() {
+        use arbitrary::{Arbitrary, Unstructured};
+
+        fn has_nan(value: &TotValue) -> bool {
+            match value {
+                TotValue::Float(f) => f.is_nan(),
+                TotValue::List(list) => list.iter().any(has_nan),
+                TotValue::Dict(dict) => dict.values().any(has_nan),
+                _ => false,
+            }
+        }
 
-fn dict(i: &str) -> PResult<TotValue> {
-    delimited(tag("{"), dict_contents, tag("}"))(i)
-}
+        // A fixed xorshift seed, so every run checks the same values
+        let mut state = 0x2545_f491_4f6c_dd1d_u64;
+        let mut bytes = [0; 512];
+        for _ in 0..500 {
+            for chunk in bytes.chunks_mut(8) {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                chunk.copy_from_slice(&state.to_le_bytes());
+            }
+
+            // Each value on its own and in a root dict
+            let mut u = Unstructured::new(&bytes);
+            let value = TotValue::arbitrary(&mut u).unwrap();
+            let dict = TotValue::Dict(HashMap::from([(u.arbitrary().unwrap(), value.clone())]));
+            for value in [value, dict] {
+                let output = crate::to_string(&value).unwrap();
+                let parsed = parse(&output).unwrap_or_else(|e| panic!("{e}: {value:?}\n{output}"));
+                if !has_nan(&value) {
+                    assert_eq!(parsed, value, "{output}");
+                }
+                assert_eq!(crate::to_string(&parsed).unwrap(), output);
+            }
+        }
+    }
 
-fn dict_contents(i: &str) -> PResult<TotValue> {
-    map(many0(key_value), |v| TotValue::Dict(HashMap::from_iter(v)))(i)
+    #[test]
+    fn test_parse_many() {
+        let documents = parse_many(
+            "\
+// First
+a 1
+---
+// Second
+b [1 2]
+  ---\r
+c {
+    d true
 }
+// Trailing comment
+",
+        )
+        .unwrap();
 
-pub(crate) fn key(i: &str) -> PResult<String> {
-    alt((map(string, String::from), map(token, String::from)))(i)
-}
+        assert_eq!(
+            documents,
+            vec![
+                parse("a 1").unwrap(),
+                parse("b [1 2]").unwrap(),
+                parse("c { d true }").unwrap(),
+            ]
+        );
 
-pub(crate) fn expression(i: &str) -> PResult<TotValue> {
-    todo!()
-}
+        assert_eq!(parse_many("a 1").unwrap(), vec![parse("a 1").unwrap()]);
+        assert_eq!(
+            parse_many("---\na 1").unwrap(),
+            vec![parse("").unwrap(), parse("a 1").unwrap()]
+        );
+        assert!(parse_many("a 1\n---\nb [").is_err());
+        // Not on its own line
+        assert!(parse_many("a 1 ---").is_err());
+    }
 
-// TODO missing s-expressions
-fn scalar(i: &str) -> PResult<TotValue> {
-    alt((
-        map(unit, |_| TotValue::Unit),
-        map(boolean, |v| TotValue::Boolean(v)),
-        map(number, |v| TotValue::Number(v)),
-        map(string, |v| TotValue::String(v)),
-        list,
-        dict,
-    ))(i)
-}
+    #[test]
+    fn test_type_name() {
+        let names: Vec<_> = ["null", "true", "1", "\"a\"", "b\"\"", "[]", "{}"]
+            .iter()
+            .map(|i| scalar(i).unwrap().1.type_name())
+            .collect();
 
-fn key_value(i: &str) -> PResult<(String, TotValue)> {
-    delimited(
-        all_ignored,
-        separated_pair(key, all_ignored, scalar),
-        all_ignored,
-    )(i)
-}
+        assert_eq!(
+            names,
+            ["null", "bool", "number", "string", "bytes", "list", "dict"]
+        );
+    }
 
-pub fn parse(i: &str) -> Result<TotValue, Error> {
-    if let Ok((rem, v)) = dict_contents(i) {
-        if rem.is_empty() {
-            return Ok(v);
+    #[test]
+    fn test_into() {
+        let mut value = parse("list [1 \"two\" 3.5] flag true").unwrap();
+
+        let list = value.pointer_mut("/list").unwrap().take();
+        assert_eq!(value.pointer("/list"), Some(&TotValue::Unit));
+        let mut list = list.into_list().unwrap();
+        assert_eq!(list.len(), 3);
+        assert_eq!(list.pop().unwrap().into_f64(), Some(3.5));
+        assert_eq!(list.pop().unwrap().into_string(), Some("two".to_string()));
+        assert_eq!(list[0].clone().into_i64(), Some(1));
+        assert_eq!(list.pop().unwrap().into_f64(), Some(1.0));
+
+        let mut dict = value.into_dict().unwrap();
+        assert_eq!(dict.remove("flag").unwrap().into_bool(), Some(true));
+        assert_eq!(dict.remove("list").unwrap().into_list(), None);
+        assert_eq!(TotValue::Float(1.0).into_i64(), None);
+        assert_eq!(TotValue::Unit.into_dict(), None);
+    }
+
+    #[test]
+    fn test_is() {
+        let value = parse("u null b true i 1 f 1.0 s \"\" l [] d {}").unwrap();
+        let get = |key: &str| value.pointer(&format!("/{key}")).unwrap();
+
+        assert!(get("u").is_unit());
+        assert!(get("b").is_bool());
+        assert!(get("i").is_integer() && get("i").is_number() && !get("i").is_float());
+        assert!(get("f").is_float() && get("f").is_number() && !get("f").is_integer());
+        assert!(get("s").is_string());
+        assert!(get("l").is_list());
+        assert!(get("d").is_dict() && value.is_dict());
+
+        for key in ["u", "b", "i", "f", "s", "l", "d"] {
+            let v = get(key);
+            let matched = [
+                v.is_unit(),
+                v.is_bool(),
+                v.is_number(),
+                v.is_string(),
+                v.is_list(),
+                v.is_dict(),
+            ];
+            assert_eq!(matched.iter().filter(|&&m| m).count(), 1, "{key}");
         }
     }
 
-    Err(Error::ParseError)
-}
+    #[test]
+    fn test_pointer() {
+        let mut value =
+            parse("0 null \"a/b\" { \"~\" true } server { host \"localhost\" ports [80 443] }")
+                .unwrap();
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::collections::HashMap;
+        assert_eq!(value.pointer(""), Some(&value));
+        assert_eq!(
+            value.pointer("/server/host"),
+            Some(&TotValue::String("localhost".to_string()))
+        );
+        assert_eq!(
+            value.pointer("/server/ports/0"),
+            Some(&TotValue::Integer(80))
+        );
+        assert_eq!(value.pointer("/a~1b/~0"), Some(&TotValue::Boolean(true)));
+        assert_eq!(value.pointer("/0"), Some(&TotValue::Unit));
+
+        assert_eq!(value.pointer("/server/user"), None);
+        assert_eq!(value.pointer("/server/ports/2"), None);
+        assert_eq!(value.pointer("/server/ports/01"), None);
+        assert_eq!(value.pointer("/server/ports/-1"), None);
+        assert_eq!(value.pointer("/server/host/0"), None);
+        assert_eq!(value.pointer("server"), None);
+        assert_eq!(value.pointer("/"), None);
+
+        *value.pointer_mut("/server/ports/1").unwrap() = TotValue::Integer(8443);
+        assert_eq!(
+            value.pointer("/server/ports"),
+            Some(&TotValue::List(vec![
+                TotValue::Integer(80),
+                TotValue::Integer(8443)
+            ]))
+        );
+        assert_eq!(value.pointer_mut("/missing"), None);
+    }
 
     #[test]
-    fn test_parse() {
-        if let TotValue::Dict(v) = parse("test 1").unwrap() {
-            assert_eq!(
-                v.get_key_value("test").unwrap(),
-                (&"test".to_string(), &TotValue::Number(1.0))
-            );
-        } else {
-            assert!(false);
-        }
+    fn test_diff() {
+        let old = parse(
+            "\
+name \"tot\"
+server {
+    host \"localhost\"
+    ports [80 443]
+}
+",
+        )
+        .unwrap();
+        let new = parse(
+            "\
+name \"tot\"
+server {
+    host \"example.com\"
+    ports [80]
+    \"a/b\" true
+}
+",
+        )
+        .unwrap();
 
-        if let TotValue::Dict(v) = parse("test 1 blah true").unwrap() {
-            assert_eq!(v.get("test").unwrap(), &TotValue::Number(1.0));
-            assert_eq!(v.get("blah").unwrap(), &TotValue::Boolean(true));
-        } else {
-            assert!(false);
+        assert_eq!(old.diff(&old), []);
+        assert_eq!(
+            old.diff(&new),
+            [
+                Change {
+                    path: "/server/a~1b".to_string(),
+                    old: None,
+                    new: Some(TotValue::Boolean(true)),
+                },
+                Change {
+                    path: "/server/host".to_string(),
+                    old: Some(TotValue::String("localhost".to_string())),
+                    new: Some(TotValue::String("example.com".to_string())),
+                },
+                Change {
+                    path: "/server/ports/1".to_string(),
+                    old: Some(TotValue::Integer(443)),
+                    new: None,
+                },
+            ]
+        );
+        for change in old.diff(&new) {
+            assert_eq!(new.pointer(&change.path), change.new.as_ref());
+            assert_eq!(old.pointer(&change.path), change.old.as_ref());
         }
 
-        if let TotValue::Dict(v) = parse(
+        assert_eq!(
+            TotValue::Integer(1).diff(&TotValue::List(vec![])),
+            [Change {
+                path: String::new(),
+                old: Some(TotValue::Integer(1)),
+                new: Some(TotValue::List(vec![])),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut base = parse(
             "\
-test 1
-blah true
-dict {
-    hello \"world\"
+name \"base\"
+ports [80]
+server {
+    host \"localhost\"
+    tls {
+        enabled false
+        cert \"base.pem\"
+    }
 }
 ",
         )
-        .unwrap()
-        {
-            assert_eq!(v.get("test").unwrap(), &TotValue::Number(1.0));
-            assert_eq!(v.get("blah").unwrap(), &TotValue::Boolean(true));
-            assert_eq!(
-                v.get("dict").unwrap(),
-                &TotValue::Dict({
-                    let mut m = HashMap::new();
-                    m.insert("hello".to_string(), TotValue::String("world".to_string()));
+        .unwrap();
+        let overrides = parse(
+            "\
+ports [443]
+server {
+    tls {
+        enabled true
+    }
+    timeout 30
+}
+",
+        )
+        .unwrap();
 
-                    m
-                })
+        let mut appended = base.clone();
+        appended.merge_with(overrides.clone(), ListMerge::Append);
+        base.merge(overrides);
+
+        assert_eq!(
+            base,
+            parse(
+                "\
+name \"base\"
+ports [443]
+server {
+    host \"localhost\"
+    tls {
+        enabled true
+        cert \"base.pem\"
+    }
+    timeout 30
+}
+",
+            )
+            .unwrap()
+        );
+
+        if let TotValue::Dict(v) = appended {
+            assert_eq!(
+                v.get("ports").unwrap(),
+                &TotValue::List(vec![TotValue::Integer(80), TotValue::Integer(443)])
             );
         } else {
-            assert!(false);
+            unreachable!();
         }
     }
 
+    #[test]
+    fn test_merge_replaces_mismatched_types() {
+        let mut base = parse("value { inner 1 }").unwrap();
+        base.merge(parse("value [1 2]").unwrap());
+
+        assert_eq!(base, parse("value [1 2]").unwrap());
+    }
+
     #[test]
     fn test_unit() {
         let (rem, _) = unit("null// hello").unwrap();
         assert_eq!(rem, "// hello");
+
+        let (rem, _) = unit("null]").unwrap();
+        assert_eq!(rem, "]");
+
+        assert!(unit("nullable").is_err());
+    }
+
+    #[test]
+    fn test_keyword_options() {
+        let parser = Parser::with_options(
+            ParseOptions::new()
+                .true_keywords(["true", "yes"])
+                .false_keywords(["false", "no"])
+                .null_keywords(["null", "none", "~"]),
+        );
+
+        let (rem, par) = parser.boolean("yes, no").unwrap();
+        assert_eq!(rem, ", no");
+        assert!(par);
+        let (_, par) = parser.boolean("no").unwrap();
+        assert!(!par);
+        let (_, par) = parser.boolean("true").unwrap();
+        assert!(par);
+        assert!(parser.boolean("yesterday").is_err());
+        assert!(parser.boolean("nothing").is_err());
+        assert!(parser.unit("~").is_ok());
+        assert!(parser.unit("none").is_ok());
+
+        assert_eq!(
+            parser.parse("a yes b [no none]").unwrap(),
+            TotValue::Dict(HashMap::from([
+                ("a".to_string(), TotValue::Boolean(true)),
+                (
+                    "b".to_string(),
+                    TotValue::List(vec![TotValue::Boolean(false), TotValue::Unit])
+                ),
+            ]))
+        );
+
+        let parser = Parser::default();
+        assert!(parser.boolean("yes").is_err());
+        assert!(parser.boolean("no").is_err());
+        assert!(parser.unit("none").is_err());
+        assert!(parse("a yes").is_err());
+        assert!(parse("a no").is_err());
+    }
+
+    #[test]
+    fn test_trim_strings() {
+        let input = "\"  key  \" \"  hi  \" list [\"\\t a \\n\"]";
+        let expected = |value: &str, element: &str| {
+            TotValue::Dict(HashMap::from([
+                ("  key  ".to_string(), TotValue::String(value.to_string())),
+                (
+                    "list".to_string(),
+                    TotValue::List(vec![TotValue::String(element.to_string())]),
+                ),
+            ]))
+        };
+
+        assert_eq!(parse(input).unwrap(), expected("  hi  ", "\t a \n"));
+
+        let parser = Parser::with_options(ParseOptions::new().trim_strings(true));
+        assert_eq!(parser.parse(input).unwrap(), expected("hi", "a"));
+    }
+
+    #[test]
+    fn test_parse_recover() {
+        let input = "\
+a 1
+b [1
+c \"two\"
+d
+e { f 3 }
+";
+        let (value, errors) = parse_recover(input);
+        assert_eq!(value.unwrap(), parse("a 1 c \"two\" e { f 3 }").unwrap());
+        assert!(matches!(
+            errors[..],
+            [
+                Error::LineError { line: 2, .. },
+                Error::LineError { line: 4, .. }
+            ]
+        ));
+
+        let (value, errors) = parse_recover("a 1 b [2]\n");
+        assert_eq!(value.unwrap(), parse("a 1 b [2]").unwrap());
+        assert!(errors.is_empty());
+
+        let (value, errors) = parse_recover("\n\na [1");
+        assert_eq!(value, None);
+        assert_eq!(errors.len(), 1);
+
+        let (_, errors) = Parser::with_max_depth(1).parse_recover("a 1\nb [[1]]");
+        assert_eq!(
+            errors[0].to_string(),
+            "line 2: list error: maximum depth of 1 exceeded"
+        );
+    }
+
+    #[test]
+    fn test_size_limits() {
+        let parser = Parser::with_options(ParseOptions::new().max_length(16));
+        assert!(parser.parse("a \"short\"").is_ok());
+        assert_eq!(
+            parser
+                .parse("a \"rather long string\"")
+                .unwrap_err()
+                .to_string(),
+            "size error: input is longer than the maximum of 16 bytes"
+        );
+        assert_eq!(
+            parser
+                .parse_many("a 1\n---\nb 2\n---\nc 3\n")
+                .unwrap_err()
+                .to_string(),
+            "size error: input is longer than the maximum of 16 bytes"
+        );
+
+        let parser = Parser::with_options(ParseOptions::new().max_collection_size(3));
+        assert!(parser.parse("a [1 2 3] b { c 1 d 2 e 3 } f 1").is_ok());
+        assert_eq!(
+            parser.parse("a [1 2 3 4]").unwrap_err().to_string(),
+            "list error: more than 3 elements"
+        );
+        assert_eq!(
+            parser
+                .parse("a { b 1 c 2 d 3 e 4 }")
+                .unwrap_err()
+                .to_string(),
+            "dict error: more than 3 entries"
+        );
+        assert!(matches!(
+            parser.parse("a 1 b 2 c 3 d 4"),
+            Err(Error::DictError(_))
+        ));
+        assert!(matches!(
+            parser.parse_with_spans("a [[1 2 3 4]]"),
+            Err(Error::ListError(_))
+        ));
+        assert!(matches!(
+            parser.parse_recover("a 1\nb 2\nc 3\nd 4\n").1[..],
+            [Error::DictError(_)]
+        ));
+    }
+
+    #[test]
+    fn test_strict_commas() {
+        let parser = Parser::with_options(ParseOptions::new().strict_commas(true));
+
+        let input = "a 1, b [1, 2, 3,] c { d 4, }, // comment\ne 5";
+        assert_eq!(parser.parse(input).unwrap(), parse(input).unwrap());
+        assert!(parser.parse_with_spans(input).is_ok());
+
+        for input in [
+            ",a 1",
+            "a 1,,",
+            "a [,1]",
+            "a [1,,2]",
+            "a { , b 1 }",
+            "a , 1",
+            ",,,,",
+        ] {
+            assert!(parse(input).is_ok(), "{input}");
+            assert!(parser.parse(input).is_err(), "{input}");
+        }
     }
 
     #[test]
@@ -199,11 +2420,21 @@ dict {
     #[test]
     fn test_boolean() {
         let (_, par) = boolean("true").unwrap();
-        assert_eq!(par, true);
+        assert!(par);
 
         let (_, par) = boolean("false").unwrap();
-        assert_eq!(par, false);
+        assert!(!par);
+
+        let (rem, par) = boolean("false, true").unwrap();
+        assert_eq!(rem, ", true");
+        assert!(!par);
 
+        let (rem, _) = boolean("true]").unwrap();
+        assert_eq!(rem, "]");
+
+        assert!(boolean("truex").is_err());
+        assert!(boolean("trueish").is_err());
+        assert!(boolean("false_").is_err());
         assert!(boolean("True").is_err());
         assert!(boolean("False").is_err());
         assert!(boolean("").is_err());
@@ -224,18 +2455,167 @@ dict {
         assert_eq!(par, f64::from(0));
 
         let (_, par) = number("0.1").unwrap();
-        assert_eq!(par, f64::from(0.1));
+        assert_eq!(par, 0.1);
 
         let (_, par) = number(".1").unwrap();
-        assert_eq!(par, f64::from(0.1));
+        assert_eq!(par, 0.1);
 
-        let (_, par) = number("10]").unwrap();
+        let (rem, par) = number("10]").unwrap();
+        assert_eq!(rem, "]");
         assert_eq!(par, f64::from(10));
 
+        assert_eq!(number("1.5]").unwrap(), ("]", 1.5));
+        assert_eq!(number("1.]").unwrap(), ("]", 1.0));
+        assert_eq!(number("1e3]").unwrap(), ("]", 1000.0));
+
+        let (_, par) = number("-0").unwrap();
+        assert!(par == 0.0 && par.is_sign_negative());
+
+        let (_, par) = number("-0.0").unwrap();
+        assert!(par == 0.0 && par.is_sign_negative());
+
+        let (_, par) = number("5e-324").unwrap();
+        assert_eq!(par, 5e-324);
+
         assert!(number("one").is_err());
         assert!(number("").is_err());
     }
 
+    #[test]
+    fn test_number_special() {
+        for (input, expected) in [
+            ("inf", f64::INFINITY),
+            ("-inf", f64::NEG_INFINITY),
+            ("+Infinity", f64::INFINITY),
+            ("INF]", f64::INFINITY),
+        ] {
+            assert_eq!(number(input).unwrap().1, expected, "{input}");
+        }
+        assert_eq!(number("-infinity ").unwrap(), (" ", f64::NEG_INFINITY));
+        assert!(number("nan").unwrap().1.is_nan());
+        assert!(number("NaN,").unwrap().1.is_nan());
+
+        assert!(number("info").is_err());
+        assert!(number("nanny").is_err());
+
+        assert_eq!(
+            parse("a inf").unwrap(),
+            TotValue::Dict(HashMap::from([(
+                "a".to_string(),
+                TotValue::Float(f64::INFINITY)
+            )]))
+        );
+        assert_eq!(
+            scalar("-inf").unwrap().1,
+            TotValue::Float(f64::NEG_INFINITY)
+        );
+    }
+
+    #[test]
+    fn test_number_exponents() {
+        for (input, expected) in [
+            ("1e300", 1e300),
+            ("1e-300", 1e-300),
+            ("1.23e45", 1.23e45),
+            ("-1.23e45", -1.23e45),
+            ("1.7976931348623157e308", f64::MAX),
+            ("2.2250738585072014e-308", f64::MIN_POSITIVE),
+            ("1.5E-3", 1.5e-3),
+            ("1e+10", 1e10),
+        ] {
+            assert_eq!(number(input).unwrap(), ("", expected), "{input}");
+            assert_eq!(scalar(input).unwrap().1, TotValue::Float(expected));
+        }
+
+        assert_eq!(number("1e300]").unwrap(), ("]", 1e300));
+    }
+
+    #[test]
+    fn test_integer() {
+        assert_eq!(integer("10").unwrap(), ("", 10));
+        assert_eq!(integer("10]").unwrap(), ("]", 10));
+        assert_eq!(integer("-5 ").unwrap(), (" ", -5));
+        assert_eq!(integer("+1").unwrap(), ("", 1));
+        assert_eq!(integer("1_000").unwrap(), ("", 1000));
+        assert_eq!(
+            integer("9223372036854775807").unwrap(),
+            ("", 9223372036854775807)
+        );
+
+        assert!(integer("1.0").is_err());
+        assert!(integer("10.").is_err());
+        assert!(integer("1e5").is_err());
+        assert!(integer("1_").is_err());
+        assert!(integer("9223372036854775808").is_err());
+        assert!(integer(".5").is_err());
+
+        assert_eq!(scalar("1").unwrap().1, TotValue::Integer(1));
+        assert_eq!(scalar("1.0").unwrap().1, TotValue::Float(1.0));
+        assert_eq!(
+            scalar("9223372036854775808").unwrap().1,
+            TotValue::Float(9223372036854775808.0)
+        );
+
+        assert_eq!(
+            parse("a 1 b 1.0 c 1e0 d -0 e -0.0").unwrap(),
+            TotValue::Dict(HashMap::from([
+                ("a".to_string(), TotValue::Integer(1)),
+                ("b".to_string(), TotValue::Float(1.0)),
+                ("c".to_string(), TotValue::Float(1.0)),
+                ("d".to_string(), TotValue::Integer(0)),
+                ("e".to_string(), TotValue::Float(-0.0)),
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_number_sign() {
+        let (rem, par) = number("+1").unwrap();
+        assert_eq!(rem, "");
+        assert_eq!(par, 1.0);
+
+        let (rem, par) = number("+1.0").unwrap();
+        assert_eq!(rem, "");
+        assert_eq!(par, 1.0);
+
+        let (_, par) = number("+0").unwrap();
+        assert!(par == 0.0 && par.is_sign_positive());
+
+        let (_, par) = number("-0").unwrap();
+        assert!(par == 0.0 && par.is_sign_negative());
+
+        assert_eq!(number("+.5").unwrap().1, 0.5);
+        assert_eq!(number("+1e+2").unwrap().1, 100.0);
+
+        assert!(number("+").is_err());
+        assert!(number("++1").is_err());
+        assert!(number("+-1").is_err());
+        assert!(number("-+1").is_err());
+    }
+
+    #[test]
+    fn test_number_underscores() {
+        let (rem, par) = number("1_000").unwrap();
+        assert_eq!(rem, "");
+        assert_eq!(par, 1000.0);
+
+        let (_, par) = number("-1_000_000").unwrap();
+        assert_eq!(par, -1000000.0);
+
+        let (_, par) = number("1_000.000_5").unwrap();
+        assert_eq!(par, 1000.0005);
+
+        let (_, par) = number("1_0e1_0").unwrap();
+        assert_eq!(par, 10e10);
+
+        assert!(number("_1").is_err());
+        assert!(number("1__0").is_err());
+        assert!(number("1_").is_err());
+        assert!(number("1_.0").is_err());
+        assert!(number("1._0").is_err());
+        assert!(number("1.0__1").is_err());
+    }
+
     #[test]
     fn test_string() {
         let (rem, par) = string("\"hello world\"foo").unwrap();
@@ -246,6 +2626,50 @@ dict {
         assert!(string("1").is_err());
     }
 
+    #[test]
+    fn test_string_escapes() {
+        let (rem, par) = string(r#""a \"quoted\" \\ word" rest"#).unwrap();
+        assert_eq!(rem, " rest");
+        assert_eq!(par, "a \"quoted\" \\ word");
+
+        let (_, par) = string(r#""\n\r\t\b\f\/""#).unwrap();
+        assert_eq!(par, "\n\r\t\u{08}\u{0C}/");
+
+        let (_, par) = string(r#""\u{e9}\u{1F600}""#).unwrap();
+        assert_eq!(par, "\u{e9}\u{1F600}");
+
+        let (_, par) = string("\"\"").unwrap();
+        assert_eq!(par, "");
+
+        // Unknown escape
+        assert!(string(r#""\q""#).is_err());
+        // Not a valid char
+        assert!(string(r#""\u{D800}""#).is_err());
+        // Unterminated string
+        assert!(string(r#""\""#).is_err());
+    }
+
+    #[test]
+    fn test_string_slashes() {
+        let (rem, par) = string(r#""a/b // c /* d */ \/" rest"#).unwrap();
+        assert_eq!(rem, " rest");
+        assert_eq!(par, "a/b // c /* d */ /");
+
+        let (_, par) = string(r#""/*""#).unwrap();
+        assert_eq!(par, "/*");
+
+        assert_eq!(
+            parse("url \"http://x/*y\" // comment\nend \"*/\"").unwrap(),
+            TotValue::Dict(HashMap::from([
+                (
+                    "url".to_string(),
+                    TotValue::String("http://x/*y".to_string())
+                ),
+                ("end".to_string(), TotValue::String("*/".to_string())),
+            ]))
+        );
+    }
+
     #[test]
     fn test_whitespace() {
         let (rem, _) = whitespace(" hello").unwrap();
@@ -264,6 +2688,18 @@ dict {
 
         let (rem, _) = line_comment("// this is a comment\ntext").unwrap();
         assert_eq!(rem, "\ntext");
+
+        let (rem, _) = line_comment("//\ntext").unwrap();
+        assert_eq!(rem, "\ntext");
+    }
+
+    #[test]
+    fn test_comments() {
+        assert_eq!(
+            comments(" // one\n, /* two */\n\t//"),
+            vec![(1, "// one"), (10, "/* two */"), (21, "//")]
+        );
+        assert!(comments(" , \n").is_empty());
     }
 
     #[test]
@@ -298,7 +2734,23 @@ dict {
 
         let (rem, par) = list("[1]").unwrap();
         assert_eq!(rem, "");
-        assert_eq!(par, TotValue::List(vec![TotValue::Number(1.0)]));
+        assert_eq!(par, TotValue::List(vec![TotValue::Integer(1)]));
+
+        // Elements can end right at the bracket
+        for (input, element) in [
+            ("[1.5]", TotValue::Float(1.5)),
+            ("[true]", TotValue::Boolean(true)),
+            ("[null]", TotValue::Unit),
+            ("[\"a\"]", TotValue::String("a".to_string())),
+            ("[[]]", TotValue::List(vec![])),
+        ] {
+            assert_eq!(list(input).unwrap(), ("", TotValue::List(vec![element])));
+        }
+        assert!(list("[nullable]").is_err());
+
+        let (rem, par) = list("[\n    // empty\n]").unwrap();
+        assert_eq!(rem, "");
+        assert_eq!(par, TotValue::List(vec![]));
 
         let (rem, par) = list("[] blah []").unwrap();
         assert_eq!(rem, " blah []");
@@ -306,17 +2758,17 @@ dict {
 
         let (rem, par) = list("[1] blah []").unwrap();
         assert_eq!(rem, " blah []");
-        assert_eq!(par, TotValue::List(vec![TotValue::Number(1.0)]));
+        assert_eq!(par, TotValue::List(vec![TotValue::Integer(1)]));
 
         let (rem, par) = list("[1, 2\n , /* inner comment */ 3.1 4] blah []").unwrap();
         assert_eq!(rem, " blah []");
         assert_eq!(
             par,
             TotValue::List(vec![
-                TotValue::Number(1.0),
-                TotValue::Number(2.0),
-                TotValue::Number(3.1),
-                TotValue::Number(4.0)
+                TotValue::Integer(1),
+                TotValue::Integer(2),
+                TotValue::Float(3.1),
+                TotValue::Integer(4)
             ])
         );
 
@@ -330,12 +2782,41 @@ dict {
         assert!(list("[ 1 ").is_err());
     }
 
+    #[test]
+    fn test_list_separators() {
+        let (rem, par) = list("[-1 -2]").unwrap();
+        assert_eq!(rem, "");
+        assert_eq!(
+            par,
+            TotValue::List(vec![TotValue::Integer(-1), TotValue::Integer(-2)])
+        );
+        assert!(list("[-1,-2]").is_ok());
+        assert!(list("[-1/* */-2]").is_ok());
+        assert!(list("[[1] [2]]").is_ok());
+
+        match parse("a [-1-2]") {
+            Err(Error::ListError(e)) => assert_eq!(
+                e,
+                "expected whitespace or a comma between list elements, found `-2]`"
+            ),
+            r => panic!("unexpected result {r:?}"),
+        }
+        assert!(parse("a [1.5-2]").is_err());
+        assert!(parse("a [\"x\"\"y\"]").is_err());
+        assert!(parse("a [[1][2]]").is_err());
+        assert!(parse_with_spans("a [-1-2]").is_err());
+    }
+
     #[test]
     fn test_dict() {
         let (rem, par) = dict("{}").unwrap();
         assert_eq!(rem, "");
         assert_eq!(par, TotValue::Dict(HashMap::default()));
 
+        let (rem, par) = dict("{\n}").unwrap();
+        assert_eq!(rem, "");
+        assert_eq!(par, TotValue::Dict(HashMap::default()));
+
         let (_, par) = dict("{hello \"world\"}").unwrap();
         assert_eq!(
             par,
@@ -355,7 +2836,7 @@ dict {
                 map.insert("hello".to_string(), TotValue::String("world".to_string()));
                 map.insert(
                     "inner-list".to_string(),
-                    TotValue::List(vec![TotValue::Boolean(true), TotValue::Number(10.0)]),
+                    TotValue::List(vec![TotValue::Boolean(true), TotValue::Integer(10)]),
                 );
 
                 map
@@ -363,6 +2844,25 @@ dict {
         );
     }
 
+    #[test]
+    fn test_is_bare_key() {
+        assert!(is_bare_key("my-key"));
+        assert!(is_bare_key("my.key"));
+        assert!(is_bare_key("a/b"));
+
+        assert!(!is_bare_key(""));
+        assert!(!is_bare_key("my key"));
+        assert!(!is_bare_key("\"quoted"));
+        assert!(!is_bare_key("[list"));
+        assert!(!is_bare_key("//comment"));
+        assert!(!is_bare_key("/*comment"));
+        assert!(!is_bare_key("\u{feff}bom"));
+        assert_eq!(
+            parse(&crate::to_string(&HashMap::from([("\u{feff}", 1)])).unwrap()).unwrap(),
+            parse("\"\u{feff}\" 1").unwrap()
+        );
+    }
+
     #[test]
     fn test_key() {
         let (rem, par) = key("my-key").unwrap();
@@ -377,6 +2877,22 @@ dict {
         assert_eq!(rem, " 2");
         assert_eq!(par, "my-key");
 
+        // Keys that aren't written bare are still read
+        for input in ["'a 1", "[a 1", "{a 1", "]a 1", "a} 1"] {
+            let (rem, par) = key(input).unwrap();
+            assert_eq!(rem, " 1");
+            assert_eq!(par, input[..input.len() - 2]);
+        }
+        assert_eq!(
+            parse("'quoted' 1").unwrap(),
+            TotValue::Dict(HashMap::from([(
+                "'quoted'".to_string(),
+                TotValue::Integer(1)
+            )]))
+        );
+        assert!(key("} 1").is_err());
+        assert!(key(", 1").is_err());
+
         let (rem, par) = key("\"my key\" 2").unwrap();
         assert_eq!(rem, " 2");
         assert_eq!(par, "my key");
@@ -388,7 +2904,7 @@ dict {
         assert_eq!(par, TotValue::Boolean(true));
 
         let (_, par) = scalar("1").unwrap();
-        assert_eq!(par, TotValue::Number(1.0));
+        assert_eq!(par, TotValue::Integer(1));
 
         let (_, par) = scalar("\"hello\"").unwrap();
         assert_eq!(par, TotValue::String("hello".to_string()));
@@ -397,6 +2913,27 @@ dict {
         assert_eq!(par, TotValue::List(vec![TotValue::Boolean(false)]));
     }
 
+    #[test]
+    fn test_bytes() {
+        let (rem, par) = scalar("b\"AP8/+w==\"]").unwrap();
+        assert_eq!(rem, "]");
+        assert_eq!(par, TotValue::Bytes(vec![0, 255, 63, 251]));
+
+        assert_eq!(
+            parse("data b\"aGk\"").unwrap(),
+            TotValue::Dict(HashMap::from([(
+                "data".to_string(),
+                TotValue::Bytes(b"hi".to_vec())
+            )]))
+        );
+        assert_eq!(parse_value("b\"\"").unwrap(), TotValue::Bytes(Vec::new()));
+        assert_eq!(parse_value("b\"\"").unwrap().into_bytes(), Some(Vec::new()));
+
+        assert!(scalar("b\"Zm9vY\"").is_err());
+        assert!(scalar("b\"a b\"").is_err());
+        assert!(scalar("b \"aGk\"").is_err());
+    }
+
     #[test]
     fn test_key_value() {
         let (_, par) = key_value("hello true").unwrap();
@@ -409,7 +2946,7 @@ dict {
 
         let (_, par) = key_value("hello 10").unwrap();
         assert_eq!(par.0, "hello");
-        assert_eq!(par.1, TotValue::Number(10.0));
+        assert_eq!(par.1, TotValue::Integer(10));
 
         let (_, par) = key_value("hello \"world\"").unwrap();
         assert_eq!(par.0, "hello");
@@ -420,7 +2957,7 @@ dict {
         assert_eq!(
             par.1,
             TotValue::List(vec![
-                TotValue::Number(0.0),
+                TotValue::Integer(0),
                 TotValue::Boolean(true),
                 TotValue::List(vec![TotValue::String("hello".to_string())])
             ])
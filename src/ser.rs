@@ -1,21 +1,58 @@
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+
 use serde::{
     ser::{self, Impossible},
     Serialize,
 };
 
 use crate::error::{Error, Result};
+use crate::parser::{self, TotValue};
 
 /// Indents are 4 spaces.
-const INDENT: &str = "    ";
+pub(crate) const INDENT: &str = "    ";
+
+/// Spaces to copy indents from, enough for most line starts.
+const SPACES: [u8; 128] = [b' '; 128];
 
-trait Formatter {
+/// How a [`Serializer`] writes each piece of Tot.
+///
+/// Only the bookkeeping of the indent and the root type has to be implemented; every other method
+/// has a default that writes the same output as [`DefaultFormatter`] with no options set.
+pub trait Formatter {
+    /// Go one list or dict deeper.
     fn indent(&mut self);
+    /// Go one list or dict back out.
     fn unindent(&mut self);
+    /// The number of lists and dicts the current line is in.
     fn get_indent(&self) -> usize;
 
+    /// Whether the first list or dict, which decides the type of the root, has begun.
     fn is_root_type_set(&self) -> bool;
     fn set_root_type(&mut self, root_type: RootType);
 
+    /// Whether to write map entries sorted by key instead of in iteration order.
+    fn sort_keys(&self) -> bool {
+        false
+    }
+
+    /// Whether to put a blank line before each entry of a root dict that's a list or dict.
+    fn blank_line_between_sections(&self) -> bool {
+        false
+    }
+
+    /// Whether to leave out dict entries and struct fields that are `None` instead of writing
+    /// them as null.
+    fn omit_none(&self) -> bool {
+        false
+    }
+
+    /// The widest a list of scalars can be, brackets included, to be written on one line. Lists
+    /// are always written one element per line if this is `None`.
+    fn compact_list_width(&self) -> Option<usize> {
+        None
+    }
+
     fn write_space<W: ?Sized + std::io::Write>(&mut self, writer: &mut W) -> Result<()> {
         writer.write_all(b" ").map_err(Error::Io)
     }
@@ -29,7 +66,7 @@ trait Formatter {
         writer: &mut W,
         precalculated_amount: Option<usize>,
     ) -> Result<()> {
-        for _ in 1..precalculated_amount.unwrap_or(self.get_indent()) {
+        for _ in 0..precalculated_amount.unwrap_or(self.get_indent()) {
             writer.write_all(INDENT.as_bytes()).map_err(Error::Io)?;
         }
 
@@ -58,11 +95,27 @@ trait Formatter {
         writer: &mut W,
         value: f64,
     ) -> Result<()> {
+        if !value.is_finite() {
+            return write!(writer, "{value}").map_err(Error::Io);
+        }
+
         let mut buffer = ryu::Buffer::new();
         let s = buffer.format_finite(value);
         writer.write_all(s.as_bytes()).map_err(Error::Io)
     }
 
+    /// Write an integer exactly, without going through a float.
+    #[inline]
+    fn write_u64<W: ?Sized + std::io::Write>(&mut self, writer: &mut W, value: u64) -> Result<()> {
+        write!(writer, "{value}").map_err(Error::Io)
+    }
+
+    /// Write a signed integer exactly, without going through a float.
+    #[inline]
+    fn write_i64<W: ?Sized + std::io::Write>(&mut self, writer: &mut W, value: i64) -> Result<()> {
+        write!(writer, "{value}").map_err(Error::Io)
+    }
+
     #[inline]
     fn begin_string<W: ?Sized + std::io::Write>(&mut self, writer: &mut W) -> Result<()> {
         writer.write_all(b"\"").map_err(Error::Io)
@@ -79,26 +132,65 @@ trait Formatter {
         writer: &mut W,
         value: &str,
     ) -> Result<()> {
-        writer.write_all(value.as_bytes()).map_err(Error::Io)
+        write_escaped(writer, value, false)
+    }
+
+    /// Write bytes in base64 between `b"` and `"`.
+    #[inline]
+    fn write_bytes<W: ?Sized + std::io::Write>(
+        &mut self,
+        writer: &mut W,
+        value: &[u8],
+    ) -> Result<()> {
+        write!(writer, "b\"{}\"", crate::base64::encode(value)).map_err(Error::Io)
+    }
+
+    /// Write `comment` as `//` line comments, one per line of the comment.
+    fn write_comment<W: ?Sized + std::io::Write>(
+        &mut self,
+        writer: &mut W,
+        comment: &str,
+    ) -> Result<()> {
+        write_line_comments(self, writer, comment)
     }
 
     #[inline]
     fn write_key<W: ?Sized + std::io::Write>(&mut self, writer: &mut W, value: &str) -> Result<()> {
         self.write_indent(writer, None)?;
-        writer.write_all(value.as_bytes()).map_err(Error::Io)?;
+        if parser::is_bare_key(value) {
+            writer.write_all(value.as_bytes()).map_err(Error::Io)?;
+        } else {
+            self.begin_string(writer)?;
+            self.write_string_fragment(writer, value)?;
+            self.end_string(writer)?;
+        }
+        self.write_space(writer)
+    }
+
+    /// Write a key without checking or quoting it, like [`write_key`](Formatter::write_key) does
+    /// for a bare key. The caller guarantees that `key` is a valid bare key, which the serializer
+    /// does for struct fields whose names are plain identifiers.
+    #[inline]
+    fn write_raw_key<W: ?Sized + std::io::Write>(
+        &mut self,
+        writer: &mut W,
+        key: &str,
+    ) -> Result<()> {
+        self.write_indent(writer, None)?;
+        writer.write_all(key.as_bytes()).map_err(Error::Io)?;
         self.write_space(writer)
     }
 
+    // The indent is the number of lists and dicts the current line is in, not counting a root
+    // dict. A root dict has no braces, and a root list always has brackets.
+
     #[inline]
     fn begin_list<W: ?Sized + std::io::Write>(&mut self, writer: &mut W) -> Result<()> {
         if !self.is_root_type_set() {
             self.set_root_type(RootType::List);
-            self.indent();
         }
 
-        if self.get_indent() > 0 {
-            writer.write_all(b"[\n").map_err(Error::Io)?;
-        }
+        writer.write_all(b"[\n").map_err(Error::Io)?;
         self.indent();
 
         Ok(())
@@ -108,24 +200,38 @@ trait Formatter {
     fn end_list<W: ?Sized + std::io::Write>(&mut self, writer: &mut W) -> Result<()> {
         self.unindent();
 
-        let indent = self.get_indent();
-        if indent > 0 {
-            self.write_indent(writer, Some(indent))?;
-            writer.write_all(b"]").map_err(Error::Io)
-        } else {
-            Ok(())
+        self.write_indent(writer, None)?;
+        writer.write_all(b"]").map_err(Error::Io)
+    }
+
+    /// Write a whole list on one line, given its already formatted elements.
+    fn write_compact_list<W: ?Sized + std::io::Write>(
+        &mut self,
+        writer: &mut W,
+        elements: &[Vec<u8>],
+    ) -> Result<()> {
+        if !self.is_root_type_set() {
+            self.set_root_type(RootType::List);
+        }
+
+        writer.write_all(b"[").map_err(Error::Io)?;
+        for (i, element) in elements.iter().enumerate() {
+            if i > 0 {
+                self.write_space(writer)?;
+            }
+            writer.write_all(element).map_err(Error::Io)?;
         }
+        writer.write_all(b"]").map_err(Error::Io)
     }
 
     #[inline]
     fn begin_dict<W: ?Sized + std::io::Write>(&mut self, writer: &mut W) -> Result<()> {
         if !self.is_root_type_set() {
             self.set_root_type(RootType::Dict);
+            return Ok(());
         }
 
-        if self.get_indent() > 0 {
-            writer.write_all(b"{\n").map_err(Error::Io)?;
-        }
+        writer.write_all(b"{\n").map_err(Error::Io)?;
         self.indent();
 
         Ok(())
@@ -133,36 +239,302 @@ trait Formatter {
 
     #[inline]
     fn end_dict<W: ?Sized + std::io::Write>(&mut self, writer: &mut W) -> Result<()> {
-        self.unindent();
+        // Only the root dict can end at no indent
+        if self.get_indent() == 0 {
+            return Ok(());
+        }
 
-        let indent = self.get_indent();
-        if indent > 0 {
-            self.write_indent(writer, Some(indent))?;
+        self.unindent();
 
-            writer.write_all(b"}").map_err(Error::Io)?;
+        self.write_indent(writer, None)?;
+        writer.write_all(b"}").map_err(Error::Io)
+    }
+}
 
-            Ok(())
-        } else {
-            Ok(())
+/// Write `value` with quotes, backslashes and control characters escaped, and non-ASCII
+/// characters escaped as `\u{...}` if `ascii_only` is set. Control characters with a short
+/// escape like `\n` use it, and the rest are written as `\u{...}`. Slashes are never escaped,
+/// since `//` and `/*` only start comments outside of strings; `\/` is only accepted when reading.
+fn write_escaped<W: ?Sized + std::io::Write>(
+    writer: &mut W,
+    value: &str,
+    ascii_only: bool,
+) -> Result<()> {
+    let bytes = value.as_bytes();
+    let mut start = 0;
+    for (i, c) in value.char_indices() {
+        let escaped = match c {
+            '"' => Some("\\\""),
+            '\\' => Some("\\\\"),
+            '\n' => Some("\\n"),
+            '\r' => Some("\\r"),
+            '\t' => Some("\\t"),
+            '\u{08}' => Some("\\b"),
+            '\u{0C}' => Some("\\f"),
+            c if c < ' ' || (ascii_only && !c.is_ascii()) => None,
+            _ => continue,
+        };
+
+        writer.write_all(&bytes[start..i]).map_err(Error::Io)?;
+        match escaped {
+            Some(escaped) => writer.write_all(escaped.as_bytes()),
+            None => write!(writer, "{}", c.escape_unicode()),
         }
+        .map_err(Error::Io)?;
+        start = i + c.len_utf8();
     }
+
+    writer.write_all(&bytes[start..]).map_err(Error::Io)
 }
 
-#[derive(Debug, Default, PartialEq, Eq)]
-enum RootType {
+/// The type of the root value being written, see [`Formatter::set_root_type`].
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub enum RootType {
     #[default]
     None,
     Dict,
     List,
 }
 
+/// Write `comment` as a `//` comment for each of its lines.
+fn write_line_comments<F, W>(formatter: &mut F, writer: &mut W, comment: &str) -> Result<()>
+where
+    F: ?Sized + Formatter,
+    W: ?Sized + std::io::Write,
+{
+    for line in comment.lines() {
+        formatter.write_indent(writer, None)?;
+        writer.write_all(b"//").map_err(Error::Io)?;
+        if !line.is_empty() {
+            formatter.write_space(writer)?;
+            writer.write_all(line.as_bytes()).map_err(Error::Io)?;
+        }
+        formatter.write_newline(writer)?;
+    }
+
+    Ok(())
+}
+
+/// How [`DefaultFormatter`] writes floats.
+///
+/// Finite floats are always written with a decimal point or an exponent, so they read back as
+/// floats and not integers: `10.0` is a float while `10` is an integer. The rest are written as
+/// `NaN`, `inf` and `-inf` in every format.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub enum FloatFormat {
+    /// The shortest representation that reads back as the same value.
+    #[default]
+    Shortest,
+    /// A fixed number of digits after the decimal point, e.g. `0.33` for 2. With 0, a `.0` is
+    /// still added.
+    Decimals(usize),
+    /// A number of significant digits in scientific notation, e.g. `3.3e-1` for 2.
+    Precision(usize),
+}
+
+/// How [`DefaultFormatter`] writes comments.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub enum CommentStyle {
+    /// A `//` comment for every line.
+    #[default]
+    Line,
+    /// One `/* */` comment, with the lines after the first lined up under it.
+    Block,
+}
+
 #[derive(Debug, Default)]
 pub struct DefaultFormatter {
     indents: usize,
     root_type: RootType,
+    ascii_only: bool,
+    float_format: FloatFormat,
+    compact_list_width: Option<usize>,
+    blank_line_between_sections: bool,
+    sort_keys: bool,
+    omit_none: bool,
+    comment_style: CommentStyle,
+    /// What's written between a key and its value, if not a space.
+    key_separator: Option<String>,
+    /// Keywords written for `true`, `false` and null, if not those.
+    true_keyword: Option<String>,
+    false_keyword: Option<String>,
+    null_keyword: Option<String>,
+}
+
+impl DefaultFormatter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Escape every non-ASCII character in strings and keys as `\u{...}`. Keys with any are
+    /// quoted, since escapes only work in quotes.
+    pub fn ascii_only(mut self, ascii_only: bool) -> Self {
+        self.ascii_only = ascii_only;
+        self
+    }
+
+    /// Write floats using `float_format` instead of the shortest round-trip representation.
+    ///
+    /// Values written with fewer digits are rounded, so they may read back slightly different.
+    pub fn float_format(mut self, float_format: FloatFormat) -> Self {
+        self.float_format = float_format;
+        self
+    }
+
+    /// Write lists of only scalars on one line, like `[1 2 3]`, if that line is at most
+    /// `max_width` characters long (not counting indentation or a key). Other lists are still
+    /// written one element per line.
+    pub fn compact_lists(mut self, max_width: usize) -> Self {
+        self.compact_list_width = Some(max_width);
+        self
+    }
+
+    /// Put a blank line before each entry of a root dict whose value is a list or dict, except
+    /// the first entry. Other entries are kept together.
+    pub fn blank_line_between_sections(mut self, blank_line_between_sections: bool) -> Self {
+        self.blank_line_between_sections = blank_line_between_sections;
+        self
+    }
+
+    /// Write the entries of maps sorted by their keys as written, so a `HashMap` is always
+    /// written the same way. Non-string keys are sorted as strings too, so `10` comes before `2`.
+    /// Struct fields are still written in order.
+    pub fn sort_keys(mut self, sort_keys: bool) -> Self {
+        self.sort_keys = sort_keys;
+        self
+    }
+
+    /// Leave out map entries and struct fields whose value is `None`, like
+    /// `#[serde(skip_serializing_if = "Option::is_none")]` on every field, instead of writing
+    /// them as `key null`. A `None` anywhere else, like in a list, is still written as null.
+    pub fn omit_none(mut self, omit_none: bool) -> Self {
+        self.omit_none = omit_none;
+        self
+    }
+
+    /// Write `separator` between keys and their values instead of a single space, like `"\t"` or
+    /// `"   "`.
+    ///
+    /// Writing a key fails if `separator` is empty or has anything but spaces and tabs. Anything
+    /// else would either not read back or, like a comma, become part of a bare key.
+    pub fn key_separator<S: Into<String>>(mut self, separator: S) -> Self {
+        self.key_separator = Some(separator.into());
+        self
+    }
+
+    fn separator_bytes(&self) -> Result<&[u8]> {
+        match self.key_separator.as_deref() {
+            None => Ok(b" "),
+            Some(separator)
+                if !separator.is_empty()
+                    && separator.bytes().all(|b| matches!(b, b' ' | b'\t')) =>
+            {
+                Ok(separator.as_bytes())
+            }
+            Some(separator) => Err(Error::SerdeError(format!(
+                "invalid key separator {separator:?}"
+            ))),
+        }
+    }
+
+    /// Write comments in `comment_style` instead of as `//` line comments.
+    ///
+    /// A `*/` in a block comment would end it early, so it's written as `* /`.
+    pub fn comment_style(mut self, comment_style: CommentStyle) -> Self {
+        self.comment_style = comment_style;
+        self
+    }
+
+    /// Write booleans as `true_keyword` and `false_keyword` instead of `true` and `false`.
+    ///
+    /// The output can only be read back by a [`Parser`](crate::parser::Parser) with matching
+    /// [`ParseOptions`](crate::parser::ParseOptions).
+    pub fn bool_keywords<S: Into<String>>(mut self, true_keyword: S, false_keyword: S) -> Self {
+        self.true_keyword = Some(true_keyword.into());
+        self.false_keyword = Some(false_keyword.into());
+        self
+    }
+
+    /// Write null as `null_keyword` instead of `null`, with the same caveat as
+    /// [`bool_keywords`](Self::bool_keywords).
+    pub fn null_keyword<S: Into<String>>(mut self, null_keyword: S) -> Self {
+        self.null_keyword = Some(null_keyword.into());
+        self
+    }
 }
 
 impl Formatter for DefaultFormatter {
+    fn write_null<W: ?Sized + std::io::Write>(&mut self, writer: &mut W) -> Result<()> {
+        let keyword = self.null_keyword.as_deref().unwrap_or("null");
+        writer.write_all(keyword.as_bytes()).map_err(Error::Io)
+    }
+
+    fn write_bool<W: ?Sized + std::io::Write>(
+        &mut self,
+        writer: &mut W,
+        value: bool,
+    ) -> Result<()> {
+        let keyword = match value {
+            true => self.true_keyword.as_deref().unwrap_or("true"),
+            false => self.false_keyword.as_deref().unwrap_or("false"),
+        };
+        writer.write_all(keyword.as_bytes()).map_err(Error::Io)
+    }
+
+    fn write_string_fragment<W: ?Sized + std::io::Write>(
+        &mut self,
+        writer: &mut W,
+        value: &str,
+    ) -> Result<()> {
+        write_escaped(writer, value, self.ascii_only)
+    }
+
+    fn write_comment<W: ?Sized + std::io::Write>(
+        &mut self,
+        writer: &mut W,
+        comment: &str,
+    ) -> Result<()> {
+        if self.comment_style == CommentStyle::Line {
+            return write_line_comments(self, writer, comment);
+        }
+
+        let comment = comment.replace("*/", "* /");
+        self.write_indent(writer, None)?;
+        writer.write_all(b"/*").map_err(Error::Io)?;
+        for (i, line) in comment.lines().enumerate() {
+            if i > 0 {
+                self.write_newline(writer)?;
+                if !line.is_empty() {
+                    self.write_indent(writer, None)?;
+                    writer.write_all(b"  ").map_err(Error::Io)?;
+                }
+            }
+            if !line.is_empty() {
+                self.write_space(writer)?;
+                writer.write_all(line.as_bytes()).map_err(Error::Io)?;
+            }
+        }
+        writer.write_all(b" */").map_err(Error::Io)?;
+        self.write_newline(writer)
+    }
+
+    fn write_number<W: ?Sized + std::io::Write>(
+        &mut self,
+        writer: &mut W,
+        value: f64,
+    ) -> Result<()> {
+        let s = match self.float_format {
+            // `NaN`, `inf` and `-inf` read back as themselves
+            FloatFormat::Shortest if !value.is_finite() => value.to_string(),
+            FloatFormat::Shortest => ryu::Buffer::new().format_finite(value).to_string(),
+            FloatFormat::Decimals(0) if value.is_finite() => format!("{value:.0}.0"),
+            FloatFormat::Decimals(decimals) => format!("{value:.decimals$}"),
+            FloatFormat::Precision(0) => format!("{value:.0e}"),
+            FloatFormat::Precision(digits) => format!("{:.*e}", digits - 1, value),
+        };
+        writer.write_all(s.as_bytes()).map_err(Error::Io)
+    }
+
     fn indent(&mut self) {
         self.indents += 1;
     }
@@ -175,6 +547,59 @@ impl Formatter for DefaultFormatter {
         self.indents
     }
 
+    // Each of these writes its whole line start with one call, where the trait's defaults write
+    // every indent level, the key and the separator separately. That needs fewer system calls
+    // for an unbuffered writer like a `File`, but a `BufWriter` is still the faster choice.
+
+    fn write_indent<W: ?Sized + std::io::Write>(
+        &mut self,
+        writer: &mut W,
+        precalculated_amount: Option<usize>,
+    ) -> Result<()> {
+        let width = precalculated_amount.unwrap_or(self.indents) * INDENT.len();
+        match SPACES.get(..width) {
+            Some(spaces) => writer.write_all(spaces).map_err(Error::Io),
+            None => writer
+                .write_all(" ".repeat(width).as_bytes())
+                .map_err(Error::Io),
+        }
+    }
+
+    fn write_key<W: ?Sized + std::io::Write>(&mut self, writer: &mut W, value: &str) -> Result<()> {
+        // Only a quoted key can be escaped
+        if !parser::is_bare_key(value) || (self.ascii_only && !value.is_ascii()) {
+            self.separator_bytes()?;
+            self.write_indent(writer, None)?;
+            self.begin_string(writer)?;
+            self.write_string_fragment(writer, value)?;
+            self.end_string(writer)?;
+            return writer.write_all(self.separator_bytes()?).map_err(Error::Io);
+        }
+        self.write_raw_key(writer, value)
+    }
+
+    fn write_raw_key<W: ?Sized + std::io::Write>(
+        &mut self,
+        writer: &mut W,
+        key: &str,
+    ) -> Result<()> {
+        let separator = self.separator_bytes()?;
+        let indent = self.indents * INDENT.len();
+        let key_end = indent + key.len();
+        let len = key_end + separator.len();
+        if len > SPACES.len() {
+            self.write_indent(writer, None)?;
+            writer.write_all(key.as_bytes()).map_err(Error::Io)?;
+            return writer.write_all(self.separator_bytes()?).map_err(Error::Io);
+        }
+
+        // Keys are written over a line of spaces, which leaves the indent
+        let mut line = SPACES;
+        line[indent..key_end].copy_from_slice(key.as_bytes());
+        line[key_end..len].copy_from_slice(separator);
+        writer.write_all(&line[..len]).map_err(Error::Io)
+    }
+
     fn is_root_type_set(&self) -> bool {
         self.root_type != RootType::None
     }
@@ -182,24 +607,50 @@ impl Formatter for DefaultFormatter {
     fn set_root_type(&mut self, root_type: RootType) {
         self.root_type = root_type;
     }
+
+    fn compact_list_width(&self) -> Option<usize> {
+        self.compact_list_width
+    }
+
+    fn blank_line_between_sections(&self) -> bool {
+        self.blank_line_between_sections
+    }
+
+    fn sort_keys(&self) -> bool {
+        self.sort_keys
+    }
+
+    fn omit_none(&self) -> bool {
+        self.omit_none
+    }
 }
 
 pub struct CompactFormatter {
+    depth: usize,
     root_type: RootType,
 }
 
 // TODO reimplement to not insert newlines
 impl Formatter for CompactFormatter {
-    fn indent(&mut self) {
+    fn write_indent<W: ?Sized + std::io::Write>(
+        &mut self,
+        _writer: &mut W,
+        _precalculated_amount: Option<usize>,
+    ) -> Result<()> {
         // Intentionally blank
+        Ok(())
+    }
+
+    fn indent(&mut self) {
+        self.depth += 1;
     }
 
     fn unindent(&mut self) {
-        // Intentionally blank
+        self.depth -= 1;
     }
 
     fn get_indent(&self) -> usize {
-        0
+        self.depth
     }
 
     fn is_root_type_set(&self) -> bool {
@@ -242,56 +693,61 @@ impl<'a, W: std::io::Write, F: Formatter> ser::Serializer for KeySerializer<'a,
     type SerializeStructVariant = Impossible<(), Error>;
 
     fn serialize_bool(self, v: bool) -> Result<()> {
-        self.ser.serialize_bool(v)
+        self.serialize_str(if v { "true" } else { "false" })
     }
 
     fn serialize_i8(self, v: i8) -> Result<()> {
-        self.ser.serialize_i8(v)
+        self.serialize_i64(v.into())
     }
 
     fn serialize_i16(self, v: i16) -> Result<()> {
-        self.ser.serialize_i16(v)
+        self.serialize_i64(v.into())
     }
 
     fn serialize_i32(self, v: i32) -> Result<()> {
-        self.ser.serialize_i32(v)
+        self.serialize_i64(v.into())
     }
 
     fn serialize_i64(self, v: i64) -> Result<()> {
-        self.ser.serialize_i64(v)
+        self.serialize_str(&v.to_string())
     }
 
     fn serialize_u8(self, v: u8) -> Result<()> {
-        self.ser.serialize_u8(v)
+        self.serialize_u64(v.into())
     }
 
     fn serialize_u16(self, v: u16) -> Result<()> {
-        self.ser.serialize_u16(v)
+        self.serialize_u64(v.into())
     }
 
     fn serialize_u32(self, v: u32) -> Result<()> {
-        self.ser.serialize_u32(v)
+        self.serialize_u64(v.into())
     }
 
     fn serialize_u64(self, v: u64) -> Result<()> {
-        self.ser.serialize_u64(v)
+        self.serialize_str(&v.to_string())
     }
 
     fn serialize_f32(self, v: f32) -> Result<()> {
-        self.ser.serialize_f32(v)
+        self.serialize_f64(v.into())
     }
 
     fn serialize_f64(self, v: f64) -> Result<()> {
-        self.ser.serialize_f64(v)
+        if !v.is_finite() {
+            return self.serialize_str(&v.to_string());
+        }
+
+        self.serialize_str(ryu::Buffer::new().format_finite(v))
     }
 
     fn serialize_char(self, v: char) -> Result<()> {
-        self.ser.serialize_char(v)
+        self.serialize_str(&v.to_string())
     }
 
-    // TODO quote strings with spaces
     fn serialize_str(self, v: &str) -> Result<()> {
-        self.ser.formatter.write_key(&mut self.ser.writer, v)
+        self.ser.line_start = Some(LineStart::Key(Cow::Owned(v.to_string())));
+
+        Ok(())
     }
 
     fn serialize_bytes(self, v: &[u8]) -> Result<()> {
@@ -302,9 +758,9 @@ impl<'a, W: std::io::Write, F: Formatter> ser::Serializer for KeySerializer<'a,
         self.ser.serialize_none()
     }
 
-    fn serialize_some<T: ?Sized>(self, value: &T) -> Result<()>
+    fn serialize_some<T>(self, value: &T) -> Result<()>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
         self.ser.serialize_some(value)
     }
@@ -319,22 +775,21 @@ impl<'a, W: std::io::Write, F: Formatter> ser::Serializer for KeySerializer<'a,
 
     fn serialize_unit_variant(
         self,
-        name: &'static str,
-        variant_index: u32,
+        _name: &'static str,
+        _variant_index: u32,
         variant: &'static str,
     ) -> Result<()> {
-        self.ser
-            .serialize_unit_variant(name, variant_index, variant)
+        self.serialize_str(variant)
     }
 
-    fn serialize_newtype_struct<T: ?Sized>(self, name: &'static str, value: &T) -> Result<()>
+    fn serialize_newtype_struct<T>(self, _name: &'static str, value: &T) -> Result<()>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
-        self.ser.serialize_newtype_struct(name, value)
+        value.serialize(self)
     }
 
-    fn serialize_newtype_variant<T: ?Sized>(
+    fn serialize_newtype_variant<T>(
         self,
         name: &'static str,
         variant_index: u32,
@@ -342,7 +797,7 @@ impl<'a, W: std::io::Write, F: Formatter> ser::Serializer for KeySerializer<'a,
         value: &T,
     ) -> Result<()>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
         self.ser
             .serialize_newtype_variant(name, variant_index, variant, value)
@@ -406,123 +861,126 @@ impl<'a, W: std::io::Write, F: Formatter> ser::Serializer for KeySerializer<'a,
     }
 }
 
-#[derive(Debug)]
-pub struct Serializer<W, F = DefaultFormatter> {
-    /// The working string that things are serialized into.
-    writer: W,
-    formatter: F,
+/// Formats a scalar on its own, so a list of them can be written on one line. Anything else is
+/// an error, which the caller takes to mean the value has to be written normally.
+struct ScalarSerializer<'a, F: 'a> {
+    formatter: &'a mut F,
+    output: Vec<u8>,
 }
 
-impl Serializer<Vec<u8>, DefaultFormatter> {
-    fn new() -> Self {
+impl<'a, F: 'a> ScalarSerializer<'a, F> {
+    fn new(formatter: &'a mut F) -> Self {
         Self {
-            writer: Vec::default(),
-            formatter: DefaultFormatter::default(),
+            formatter,
+            output: Vec::new(),
         }
     }
+
+    fn not_scalar() -> Error {
+        Error::SerdeError("expected a scalar".to_string())
+    }
 }
 
-impl<'a, W: std::io::Write, F: Formatter> ser::Serializer for &'a mut Serializer<W, F> {
-    type Ok = ();
+impl<'a, F: Formatter> ser::Serializer for ScalarSerializer<'a, F> {
+    type Ok = Vec<u8>;
 
     type Error = Error;
 
-    type SerializeSeq = Self;
+    type SerializeSeq = Impossible<Vec<u8>, Error>;
 
-    type SerializeTuple = Self;
+    type SerializeTuple = Impossible<Vec<u8>, Error>;
 
-    type SerializeTupleStruct = Self;
+    type SerializeTupleStruct = Impossible<Vec<u8>, Error>;
 
-    type SerializeTupleVariant = Self;
+    type SerializeTupleVariant = Impossible<Vec<u8>, Error>;
 
-    type SerializeMap = Self;
+    type SerializeMap = Impossible<Vec<u8>, Error>;
 
-    type SerializeStruct = Self;
+    type SerializeStruct = Impossible<Vec<u8>, Error>;
 
-    type SerializeStructVariant = Self;
+    type SerializeStructVariant = Impossible<Vec<u8>, Error>;
 
-    fn serialize_bool(self, v: bool) -> Result<()> {
-        self.formatter.write_bool(&mut self.writer, v)
+    fn serialize_bool(mut self, v: bool) -> Result<Vec<u8>> {
+        self.formatter.write_bool(&mut self.output, v)?;
+        Ok(self.output)
     }
 
-    fn serialize_i8(self, v: i8) -> Result<()> {
+    fn serialize_i8(self, v: i8) -> Result<Vec<u8>> {
         self.serialize_i64(v.into())
     }
 
-    fn serialize_i16(self, v: i16) -> Result<()> {
+    fn serialize_i16(self, v: i16) -> Result<Vec<u8>> {
         self.serialize_i64(v.into())
     }
 
-    fn serialize_i32(self, v: i32) -> Result<()> {
+    fn serialize_i32(self, v: i32) -> Result<Vec<u8>> {
         self.serialize_i64(v.into())
     }
 
-    fn serialize_i64(self, v: i64) -> Result<()> {
-        self.formatter.write_number(&mut self.writer, v as f64)
+    fn serialize_i64(mut self, v: i64) -> Result<Vec<u8>> {
+        self.formatter.write_i64(&mut self.output, v)?;
+        Ok(self.output)
     }
 
-    fn serialize_u8(self, v: u8) -> Result<()> {
+    fn serialize_u8(self, v: u8) -> Result<Vec<u8>> {
         self.serialize_u64(v.into())
     }
 
-    fn serialize_u16(self, v: u16) -> Result<()> {
+    fn serialize_u16(self, v: u16) -> Result<Vec<u8>> {
         self.serialize_u64(v.into())
     }
 
-    fn serialize_u32(self, v: u32) -> Result<()> {
+    fn serialize_u32(self, v: u32) -> Result<Vec<u8>> {
         self.serialize_u64(v.into())
     }
 
-    fn serialize_u64(self, v: u64) -> Result<()> {
-        self.formatter.write_number(&mut self.writer, v as f64)
+    fn serialize_u64(mut self, v: u64) -> Result<Vec<u8>> {
+        self.formatter.write_u64(&mut self.output, v)?;
+        Ok(self.output)
     }
 
-    fn serialize_f32(self, v: f32) -> Result<()> {
+    fn serialize_f32(self, v: f32) -> Result<Vec<u8>> {
         self.serialize_f64(v.into())
     }
 
-    fn serialize_f64(self, v: f64) -> Result<()> {
-        self.formatter.write_number(&mut self.writer, v)
+    fn serialize_f64(mut self, v: f64) -> Result<Vec<u8>> {
+        self.formatter.write_number(&mut self.output, v)?;
+        Ok(self.output)
     }
 
-    fn serialize_char(self, v: char) -> Result<()> {
+    fn serialize_char(self, v: char) -> Result<Vec<u8>> {
         self.serialize_str(&v.to_string())
     }
 
-    // TODO handle strings with escapes
-    fn serialize_str(self, v: &str) -> Result<()> {
-        self.formatter.begin_string(&mut self.writer)?;
-        self.formatter.write_string_fragment(&mut self.writer, v)?;
-        self.formatter.end_string(&mut self.writer)
+    fn serialize_str(mut self, v: &str) -> Result<Vec<u8>> {
+        self.formatter.begin_string(&mut self.output)?;
+        self.formatter.write_string_fragment(&mut self.output, v)?;
+        self.formatter.end_string(&mut self.output)?;
+        Ok(self.output)
     }
 
-    fn serialize_bytes(self, v: &[u8]) -> Result<()> {
-        use serde::ser::SerializeSeq;
-
-        let mut seq = self.serialize_seq(Some(v.len()))?;
-        for byte in v {
-            seq.serialize_element(byte)?;
-        }
-
-        seq.end()
+    fn serialize_bytes(mut self, v: &[u8]) -> Result<Vec<u8>> {
+        self.formatter.write_bytes(&mut self.output, v)?;
+        Ok(self.output)
     }
 
-    fn serialize_none(self) -> Result<()> {
+    fn serialize_none(self) -> Result<Vec<u8>> {
         self.serialize_unit()
     }
 
-    fn serialize_some<T: ?Sized>(self, value: &T) -> Result<()>
+    fn serialize_some<T>(self, value: &T) -> Result<Vec<u8>>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
         value.serialize(self)
     }
 
-    fn serialize_unit(self) -> Result<()> {
-        self.formatter.write_null(&mut self.writer)
+    fn serialize_unit(mut self) -> Result<Vec<u8>> {
+        self.formatter.write_null(&mut self.output)?;
+        Ok(self.output)
     }
 
-    fn serialize_unit_struct(self, _: &'static str) -> Result<()> {
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Vec<u8>> {
         self.serialize_unit()
     }
 
@@ -531,334 +989,1648 @@ impl<'a, W: std::io::Write, F: Formatter> ser::Serializer for &'a mut Serializer
         _name: &'static str,
         _variant_index: u32,
         variant: &'static str,
-    ) -> Result<()> {
+    ) -> Result<Vec<u8>> {
         self.serialize_str(variant)
     }
 
-    fn serialize_newtype_struct<T: ?Sized>(self, _name: &'static str, value: &T) -> Result<()>
+    fn serialize_newtype_struct<T>(self, name: &'static str, value: &T) -> Result<Vec<u8>>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
+        if name == COMMENTED {
+            return Err(Self::not_scalar());
+        }
+
         value.serialize(self)
     }
 
-    fn serialize_newtype_variant<T: ?Sized>(
+    fn serialize_newtype_variant<T>(
         self,
         _name: &'static str,
         _variant_index: u32,
-        variant: &'static str,
-        value: &T,
-    ) -> Result<()>
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<Vec<u8>>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
-        self.formatter.begin_dict(&mut self.writer)?;
-        self.formatter.write_key(&mut self.writer, variant)?;
-        value.serialize(&mut *self)?;
-        self.formatter.write_newline(&mut self.writer)?;
-        self.formatter.end_dict(&mut self.writer)
+        Err(Self::not_scalar())
     }
 
     fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq> {
-        self.formatter.begin_list(&mut self.writer)?;
+        Err(Self::not_scalar())
+    }
 
-        Ok(self)
-    }
-
-    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple> {
-        self.serialize_seq(Some(len))
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple> {
+        Err(Self::not_scalar())
     }
 
     fn serialize_tuple_struct(
         self,
         _name: &'static str,
-        len: usize,
+        _len: usize,
     ) -> Result<Self::SerializeTupleStruct> {
-        self.serialize_seq(Some(len))
+        Err(Self::not_scalar())
     }
 
     fn serialize_tuple_variant(
         self,
         _name: &'static str,
         _variant_index: u32,
-        variant: &'static str,
+        _variant: &'static str,
         _len: usize,
     ) -> Result<Self::SerializeTupleVariant> {
-        self.formatter.begin_dict(&mut self.writer)?;
-        self.formatter.write_key(&mut self.writer, variant)?;
-        self.formatter.begin_list(&mut self.writer)?;
-
-        Ok(self)
+        Err(Self::not_scalar())
     }
 
     fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
-        self.formatter.begin_dict(&mut self.writer)?;
-
-        Ok(self)
+        Err(Self::not_scalar())
     }
 
     fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeStruct> {
-        self.formatter.begin_dict(&mut self.writer)?;
-
-        Ok(self)
+        Err(Self::not_scalar())
     }
 
     fn serialize_struct_variant(
         self,
         _name: &'static str,
         _variant_index: u32,
-        variant: &'static str,
+        _variant: &'static str,
         _len: usize,
     ) -> Result<Self::SerializeStructVariant> {
-        self.formatter.begin_dict(&mut self.writer)?;
-        self.formatter.write_key(&mut self.writer, variant)?;
+        Err(Self::not_scalar())
+    }
+}
 
-        self.formatter.begin_dict(&mut self.writer)?;
+/// Succeeds only for a value that would be written as null, without writing anything. Anything
+/// else fails on its first call, so a large payload isn't formatted just to be checked.
+struct UnitProbe;
 
-        Ok(self)
+impl UnitProbe {
+    fn not_unit() -> Error {
+        Error::SerdeError("expected unit".to_string())
     }
 }
 
-impl<'a, W: std::io::Write, F: Formatter> ser::SerializeSeq for &'a mut Serializer<W, F> {
+impl ser::Serializer for UnitProbe {
     type Ok = ();
 
     type Error = Error;
 
-    fn serialize_element<T: ?Sized>(&mut self, value: &T) -> Result<()>
-    where
-        T: Serialize,
-    {
-        self.formatter.write_indent(&mut self.writer, None)?;
-        value.serialize(&mut **self)?;
-        self.formatter.write_newline(&mut self.writer)
+    type SerializeSeq = Impossible<(), Error>;
+
+    type SerializeTuple = Impossible<(), Error>;
+
+    type SerializeTupleStruct = Impossible<(), Error>;
+
+    type SerializeTupleVariant = Impossible<(), Error>;
+
+    type SerializeMap = Impossible<(), Error>;
+
+    type SerializeStruct = Impossible<(), Error>;
+
+    type SerializeStructVariant = Impossible<(), Error>;
+
+    fn serialize_bool(self, _v: bool) -> Result<()> {
+        Err(Self::not_unit())
     }
 
-    fn end(self) -> Result<()> {
-        self.formatter.end_list(&mut self.writer)
+    fn serialize_i8(self, _v: i8) -> Result<()> {
+        Err(Self::not_unit())
     }
-}
 
-impl<'a, W: std::io::Write, F: Formatter> ser::SerializeTuple for &'a mut Serializer<W, F> {
-    type Ok = ();
+    fn serialize_i16(self, _v: i16) -> Result<()> {
+        Err(Self::not_unit())
+    }
 
-    type Error = Error;
+    fn serialize_i32(self, _v: i32) -> Result<()> {
+        Err(Self::not_unit())
+    }
 
-    fn serialize_element<T: ?Sized>(&mut self, value: &T) -> Result<()>
-    where
-        T: Serialize,
-    {
-        ser::SerializeSeq::serialize_element(self, value)
+    fn serialize_i64(self, _v: i64) -> Result<()> {
+        Err(Self::not_unit())
     }
 
-    fn end(self) -> Result<()> {
-        ser::SerializeSeq::end(self)
+    fn serialize_u8(self, _v: u8) -> Result<()> {
+        Err(Self::not_unit())
     }
-}
 
-impl<'a, W: std::io::Write, F: Formatter> ser::SerializeTupleStruct for &'a mut Serializer<W, F> {
-    type Ok = ();
+    fn serialize_u16(self, _v: u16) -> Result<()> {
+        Err(Self::not_unit())
+    }
 
-    type Error = Error;
+    fn serialize_u32(self, _v: u32) -> Result<()> {
+        Err(Self::not_unit())
+    }
 
-    fn serialize_field<T: ?Sized>(&mut self, value: &T) -> Result<()>
-    where
-        T: Serialize,
-    {
-        ser::SerializeSeq::serialize_element(self, value)
+    fn serialize_u64(self, _v: u64) -> Result<()> {
+        Err(Self::not_unit())
     }
 
-    fn end(self) -> Result<()> {
-        ser::SerializeSeq::end(self)
+    fn serialize_f32(self, _v: f32) -> Result<()> {
+        Err(Self::not_unit())
     }
-}
 
-impl<'a, W: std::io::Write, F: Formatter> ser::SerializeTupleVariant for &'a mut Serializer<W, F> {
-    type Ok = ();
+    fn serialize_f64(self, _v: f64) -> Result<()> {
+        Err(Self::not_unit())
+    }
 
-    type Error = Error;
+    fn serialize_char(self, _v: char) -> Result<()> {
+        Err(Self::not_unit())
+    }
 
-    fn serialize_field<T: ?Sized>(&mut self, value: &T) -> Result<()>
-    where
-        T: Serialize,
-    {
-        ser::SerializeSeq::serialize_element(self, value)
+    fn serialize_str(self, _v: &str) -> Result<()> {
+        Err(Self::not_unit())
     }
 
-    fn end(self) -> Result<()> {
-        self.formatter.end_list(&mut self.writer)?;
-        self.formatter.write_newline(&mut self.writer)?;
-        self.formatter.end_dict(&mut self.writer)
+    fn serialize_bytes(self, _v: &[u8]) -> Result<()> {
+        Err(Self::not_unit())
     }
-}
 
-impl<'a, W: std::io::Write, F: Formatter> ser::SerializeMap for &'a mut Serializer<W, F> {
-    type Ok = ();
-    type Error = Error;
+    fn serialize_none(self) -> Result<()> {
+        Ok(())
+    }
 
-    fn serialize_key<T: ?Sized>(&mut self, key: &T) -> Result<()>
+    fn serialize_some<T>(self, value: &T) -> Result<()>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
-        key.serialize(KeySerializer::new(*self))
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<()> {
+        Ok(())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<()> {
+        Ok(())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+    ) -> Result<()> {
+        Err(Self::not_unit())
     }
 
-    fn serialize_value<T: ?Sized>(&mut self, value: &T) -> Result<()>
+    fn serialize_newtype_struct<T>(self, name: &'static str, value: &T) -> Result<()>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
-        value.serialize(&mut **self)?;
-        self.formatter.write_newline(&mut self.writer)
+        if name == COMMENTED {
+            return Err(Self::not_unit());
+        }
+
+        value.serialize(self)
     }
 
-    fn serialize_entry<K: ?Sized, V: ?Sized>(&mut self, key: &K, value: &V) -> Result<()>
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<()>
     where
-        K: Serialize,
-        V: Serialize,
+        T: ?Sized + Serialize,
     {
-        self.serialize_key(key)?;
-        ser::SerializeMap::serialize_value(self, value)
+        Err(Self::not_unit())
     }
 
-    fn end(self) -> Result<()> {
-        self.formatter.end_dict(&mut self.writer)
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq> {
+        Err(Self::not_unit())
     }
-}
 
-impl<'a, W: std::io::Write, F: Formatter> ser::SerializeStruct for &'a mut Serializer<W, F> {
-    type Ok = ();
-    type Error = Error;
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple> {
+        Err(Self::not_unit())
+    }
 
-    fn serialize_field<T: ?Sized>(&mut self, key: &'static str, value: &T) -> Result<()>
-    where
-        T: Serialize,
-    {
-        self.formatter.write_key(&mut self.writer, key)?;
-        value.serialize(&mut **self)?;
-        self.formatter.write_newline(&mut self.writer)
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct> {
+        Err(Self::not_unit())
     }
 
-    fn end(self) -> Result<()> {
-        self.formatter.end_dict(&mut self.writer)
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant> {
+        Err(Self::not_unit())
     }
-}
 
-impl<'a, W: std::io::Write, F: Formatter> ser::SerializeStructVariant for &'a mut Serializer<W, F> {
-    type Ok = ();
-    type Error = Error;
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
+        Err(Self::not_unit())
+    }
 
-    fn serialize_field<T: ?Sized>(&mut self, key: &'static str, value: &T) -> Result<()>
-    where
-        T: Serialize,
-    {
-        self.formatter.write_key(&mut self.writer, key)?;
-        value.serialize(&mut **self)?;
-        self.formatter.write_newline(&mut self.writer)
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeStruct> {
+        Err(Self::not_unit())
     }
 
-    fn end(self) -> Result<()> {
-        self.formatter.end_dict(&mut self.writer)?;
-        self.formatter.write_newline(&mut self.writer)?;
-        self.formatter.end_dict(&mut self.writer)
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant> {
+        Err(Self::not_unit())
     }
 }
 
-pub fn to_string<T: ?Sized + Serialize>(value: &T) -> Result<String> {
-    let mut serializer = Serializer::new();
+/// Name of the newtype struct that [`Commented`] serializes as.
+const COMMENTED: &str = "$tot::private::Commented";
+
+/// A value preceded by a comment.
+///
+/// When serialized by this crate, the comment is written as `//` line comments on the lines
+/// before the value, at the same indentation as the value (including its key, if any). Other
+/// serializers see a tuple of the comment and the value.
+///
+/// ```
+/// use serde::Serialize;
+/// use tot::ser::Commented;
+///
+/// #[derive(Serialize)]
+/// struct Config {
+///     port: Commented<u16>,
+/// }
+///
+/// let config = Config {
+///     port: Commented::new("Port to listen on", 8080),
+/// };
+///
+/// assert_eq!(tot::to_string(&config).unwrap(), "// Port to listen on\nport 8080\n");
+/// ```
+///
+/// Wrapping every field like this, with a `Default` impl that fills in the comments, is an easy
+/// way to generate an annotated default config file. An empty comment writes nothing. For
+/// settings that aren't known until runtime, see [`to_string_documented`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Commented<T> {
+    pub comment: String,
+    pub value: T,
+}
 
-    value.serialize(&mut serializer)?;
+impl<T> Commented<T> {
+    pub fn new<S: Into<String>>(comment: S, value: T) -> Self {
+        Self {
+            comment: comment.into(),
+            value,
+        }
+    }
+}
 
-    // TODO Enum roots don't insert an ending newline so insert a newline manually for now
-    if !serializer.writer.ends_with(b"\n") {
-        serializer.writer.extend_from_slice(b"\n");
+impl<T: Serialize> Serialize for Commented<T> {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        serializer.serialize_newtype_struct(COMMENTED, &(&self.comment, &self.value))
     }
+}
 
-    String::from_utf8(serializer.writer).map_err(|e| Error::SerdeError(e.to_string()))
+/// Serializes the `(comment, value)` tuple wrapped by [`Commented`].
+struct CommentSerializer<'a, W: 'a, F: 'a> {
+    ser: &'a mut Serializer<W, F>,
+    /// Whether the comment element of the tuple has been written.
+    written: bool,
 }
 
-#[cfg(test)]
-mod tests {
-    use serde::Serialize;
-    use std::collections::BTreeMap;
+impl<'a, W: 'a, F: 'a> CommentSerializer<'a, W, F> {
+    fn new(ser: &'a mut Serializer<W, F>) -> Self {
+        Self {
+            ser,
+            written: false,
+        }
+    }
 
-    use super::to_string;
+    fn unsupported() -> Error {
+        Error::SerdeError("expected a comment and a value".to_string())
+    }
+}
 
-    mod primitive_tests {
-        use super::*;
+impl<'a, W: std::io::Write, F: Formatter> ser::Serializer for CommentSerializer<'a, W, F> {
+    type Ok = ();
 
-        #[test]
-        fn test_tuple() {
-            let data = ("hello", "world", true);
+    type Error = Error;
 
-            let output = to_string(&data).unwrap();
+    type SerializeSeq = Impossible<(), Error>;
 
-            assert_eq!(
-                output,
-                "\
-[
-    \"hello\"
-    \"world\"
-    true
-]
-"
-            )
-        }
+    type SerializeTuple = Self;
 
-        #[test]
-        fn test_tuple_nested() {
-            let data = (
-                "hello",
-                true,
-                (false, 123.0),
-                BTreeMap::<String, String>::new(),
-            );
+    type SerializeTupleStruct = Impossible<(), Error>;
 
-            let output = to_string(&data).unwrap();
+    type SerializeTupleVariant = Impossible<(), Error>;
 
-            assert_eq!(
-                output,
-                "\
-[
-    \"hello\"
-    true
-    [
-        false
-        123.0
-    ]
-    {
+    type SerializeMap = Impossible<(), Error>;
+
+    type SerializeStruct = Impossible<(), Error>;
+
+    type SerializeStructVariant = Impossible<(), Error>;
+
+    fn serialize_bool(self, _v: bool) -> Result<()> {
+        Err(Self::unsupported())
     }
-]
-"
-            );
-        }
 
-        #[test]
-        fn test_bytes() {
-            let data: &[u8] = &[0, 1, 2, 3];
+    fn serialize_i8(self, _v: i8) -> Result<()> {
+        Err(Self::unsupported())
+    }
 
-            let output = to_string(&data).unwrap();
+    fn serialize_i16(self, _v: i16) -> Result<()> {
+        Err(Self::unsupported())
+    }
 
-            assert_eq!(
-                output,
-                "\
-[
-    0.0
-    1.0
-    2.0
-    3.0
-]
-"
-            );
-        }
+    fn serialize_i32(self, _v: i32) -> Result<()> {
+        Err(Self::unsupported())
+    }
 
-        #[test]
-        fn test_vec() {
-            let data = vec![true, false, true];
+    fn serialize_i64(self, _v: i64) -> Result<()> {
+        Err(Self::unsupported())
+    }
 
-            let output = to_string(&data).unwrap();
+    fn serialize_u8(self, _v: u8) -> Result<()> {
+        Err(Self::unsupported())
+    }
 
-            assert_eq!(
-                output,
-                "\
+    fn serialize_u16(self, _v: u16) -> Result<()> {
+        Err(Self::unsupported())
+    }
+
+    fn serialize_u32(self, _v: u32) -> Result<()> {
+        Err(Self::unsupported())
+    }
+
+    fn serialize_u64(self, _v: u64) -> Result<()> {
+        Err(Self::unsupported())
+    }
+
+    fn serialize_f32(self, _v: f32) -> Result<()> {
+        Err(Self::unsupported())
+    }
+
+    fn serialize_f64(self, _v: f64) -> Result<()> {
+        Err(Self::unsupported())
+    }
+
+    fn serialize_char(self, _v: char) -> Result<()> {
+        Err(Self::unsupported())
+    }
+
+    fn serialize_str(self, v: &str) -> Result<()> {
+        self.ser.comment = Some(v.to_string());
+
+        Ok(())
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result<()> {
+        Err(Self::unsupported())
+    }
+
+    fn serialize_none(self) -> Result<()> {
+        Err(Self::unsupported())
+    }
+
+    fn serialize_some<T>(self, _value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        Err(Self::unsupported())
+    }
+
+    fn serialize_unit(self) -> Result<()> {
+        Err(Self::unsupported())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<()> {
+        Err(Self::unsupported())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+    ) -> Result<()> {
+        Err(Self::unsupported())
+    }
+
+    fn serialize_newtype_struct<T>(self, _name: &'static str, _value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        Err(Self::unsupported())
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        Err(Self::unsupported())
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq> {
+        Err(Self::unsupported())
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple> {
+        Ok(self)
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct> {
+        Err(Self::unsupported())
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant> {
+        Err(Self::unsupported())
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
+        Err(Self::unsupported())
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeStruct> {
+        Err(Self::unsupported())
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant> {
+        Err(Self::unsupported())
+    }
+}
+
+impl<'a, W: std::io::Write, F: Formatter> ser::SerializeTuple for CommentSerializer<'a, W, F> {
+    type Ok = ();
+
+    type Error = Error;
+
+    // The comment is written along with the start of the value's line.
+    fn serialize_element<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        if !self.written {
+            self.written = true;
+            value.serialize(CommentSerializer::new(&mut *self.ser))
+        } else {
+            value.serialize(&mut *self.ser)
+        }
+    }
+
+    fn end(self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// The start of a line, written right before the value on that line so anything that has to
+/// come first (like a comment) can still be written.
+#[derive(Debug)]
+enum LineStart {
+    /// Indentation followed by a key.
+    Key(Cow<'static, str>),
+    /// Indentation followed by the name of a struct field.
+    Field(&'static str),
+    /// Indentation for a list element.
+    Element,
+}
+
+/// Writes to the innermost buffer if there are any, or else the underlying writer.
+#[derive(Debug)]
+struct Output<W> {
+    writer: W,
+    buffers: Vec<Vec<u8>>,
+}
+
+impl<W: std::io::Write> std::io::Write for Output<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self.buffers.last_mut() {
+            Some(buffer) => buffer.write(buf),
+            None => self.writer.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// An entry of a map being sorted, written out once the map ends.
+#[derive(Debug)]
+struct SortedEntry {
+    key: String,
+    output: Vec<u8>,
+    /// Whether the value is a list or dict, for an entry of a root dict.
+    root_block: Option<bool>,
+}
+
+#[derive(Debug)]
+pub struct Serializer<W, F = DefaultFormatter> {
+    /// The working string that things are serialized into.
+    writer: Output<W>,
+    formatter: F,
+    line_start: Option<LineStart>,
+    /// Comment to write before the pending line start.
+    comment: Option<String>,
+    /// Whether an entry of a root dict has been started.
+    wrote_root_entry: bool,
+    /// The formatted elements of the innermost list while it can still be written on one line.
+    /// Any element that isn't a scalar ends this, so only one list can be pending at a time.
+    compact_list: Option<Vec<Vec<u8>>>,
+    /// The written entries of each map being sorted, innermost last.
+    sorted_entries: Vec<Vec<SortedEntry>>,
+    /// Whether the root entry being written for sorting is a list or dict. Its blank line is left
+    /// for when the entries are sorted.
+    sorted_root_entry: Option<bool>,
+    /// Whether the value of the current entry was `None` and left out.
+    omitted_entry: bool,
+}
+
+impl<W: std::io::Write> Serializer<W> {
+    /// Create a serializer that writes to `writer` with a [`DefaultFormatter`].
+    ///
+    /// Values are written in many small pieces, so wrap a `File` or socket in a
+    /// [`BufWriter`](std::io::BufWriter) first.
+    pub fn new(writer: W) -> Self {
+        Self::with_formatter(writer, DefaultFormatter::new())
+    }
+}
+
+impl<W: std::io::Write, F: Formatter> Serializer<W, F> {
+    /// Create a serializer that writes to `writer` with `formatter`.
+    ///
+    /// Unlike [`to_vec`], nothing is written after the value, so the output of a value that
+    /// doesn't end in a newline, like an enum root, doesn't either.
+    ///
+    /// ```
+    /// use serde::Serialize;
+    /// use tot::ser::{DefaultFormatter, Serializer};
+    ///
+    /// let formatter = DefaultFormatter::new().compact_lists(40);
+    /// let mut serializer = Serializer::with_formatter(Vec::new(), formatter);
+    /// vec![1u8, 2, 3].serialize(&mut serializer).unwrap();
+    ///
+    /// assert_eq!(serializer.into_inner(), b"[1 2 3]");
+    /// ```
+    pub fn with_formatter(writer: W, formatter: F) -> Self {
+        Self {
+            writer: Output {
+                writer,
+                buffers: Vec::new(),
+            },
+            formatter,
+            line_start: None,
+            comment: None,
+            wrote_root_entry: false,
+            compact_list: None,
+            sorted_entries: Vec::new(),
+            sorted_root_entry: None,
+            omitted_entry: false,
+        }
+    }
+
+    /// The writer being written to.
+    pub fn get_ref(&self) -> &W {
+        &self.writer.writer
+    }
+
+    /// The formatter in use.
+    pub fn formatter(&self) -> &F {
+        &self.formatter
+    }
+
+    /// Take the writer back out of the serializer.
+    pub fn into_inner(self) -> W {
+        self.writer.writer
+    }
+}
+
+/// Whether `key` is made of only ASCII letters, digits and underscores, which makes it a valid
+/// bare key. This is cheaper to check than [`parser::is_bare_key`].
+fn is_identifier(key: &str) -> bool {
+    !key.is_empty() && key.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'_')
+}
+
+/// Write the pending start of the line, if any. `block` is whether the value on the line is a
+/// list or dict.
+fn write_line_start<W: std::io::Write, F: Formatter>(
+    ser: &mut Serializer<W, F>,
+    block: bool,
+) -> Result<()> {
+    if matches!(
+        ser.line_start,
+        Some(LineStart::Key(_) | LineStart::Field(_))
+    ) && ser.formatter.get_indent() == 0
+    {
+        if !ser.writer.buffers.is_empty() {
+            ser.sorted_root_entry = Some(block);
+        } else if block && ser.wrote_root_entry && ser.formatter.blank_line_between_sections() {
+            ser.formatter.write_newline(&mut ser.writer)?;
+        }
+        ser.wrote_root_entry |= ser.writer.buffers.is_empty();
+    }
+
+    if let Some(comment) = ser.comment.take() {
+        ser.formatter.write_comment(&mut ser.writer, &comment)?;
+    }
+
+    match ser.line_start.take() {
+        Some(LineStart::Key(key)) => ser.formatter.write_key(&mut ser.writer, &key),
+        // Field names are almost always identifiers, but `#[serde(rename)]` can make them anything
+        Some(LineStart::Field(key)) if is_identifier(key) => {
+            ser.formatter.write_raw_key(&mut ser.writer, key)
+        }
+        Some(LineStart::Field(key)) => ser.formatter.write_key(&mut ser.writer, key),
+        Some(LineStart::Element) => ser.formatter.write_indent(&mut ser.writer, None),
+        None => Ok(()),
+    }
+}
+
+/// End the line of a dict entry, unless it was left out.
+fn end_entry<W: std::io::Write, F: Formatter>(ser: &mut Serializer<W, F>) -> Result<()> {
+    if std::mem::take(&mut ser.omitted_entry) {
+        return Ok(());
+    }
+
+    ser.formatter.write_newline(&mut ser.writer)
+}
+
+/// Start writing a list that was held back for one line on multiple lines instead.
+fn write_expanded_list<W: std::io::Write, F: Formatter>(
+    ser: &mut Serializer<W, F>,
+    elements: Vec<Vec<u8>>,
+) -> Result<()> {
+    ser.formatter.begin_list(&mut ser.writer)?;
+    for element in elements {
+        ser.formatter.write_indent(&mut ser.writer, None)?;
+        std::io::Write::write_all(&mut ser.writer, &element).map_err(Error::Io)?;
+        ser.formatter.write_newline(&mut ser.writer)?;
+    }
+
+    Ok(())
+}
+
+impl<W: std::io::Write, F: Formatter> ser::Serializer for &mut Serializer<W, F> {
+    type Ok = ();
+
+    type Error = Error;
+
+    type SerializeSeq = Self;
+
+    type SerializeTuple = Self;
+
+    type SerializeTupleStruct = Self;
+
+    type SerializeTupleVariant = Self;
+
+    type SerializeMap = Self;
+
+    type SerializeStruct = Self;
+
+    type SerializeStructVariant = Self;
+
+    fn serialize_bool(self, v: bool) -> Result<()> {
+        write_line_start(self, false)?;
+        self.formatter.write_bool(&mut self.writer, v)
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<()> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<()> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<()> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<()> {
+        write_line_start(self, false)?;
+        self.formatter.write_i64(&mut self.writer, v)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<()> {
+        self.serialize_u64(v.into())
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<()> {
+        self.serialize_u64(v.into())
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<()> {
+        self.serialize_u64(v.into())
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<()> {
+        write_line_start(self, false)?;
+        self.formatter.write_u64(&mut self.writer, v)
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<()> {
+        self.serialize_f64(v.into())
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<()> {
+        write_line_start(self, false)?;
+        self.formatter.write_number(&mut self.writer, v)
+    }
+
+    fn serialize_char(self, v: char) -> Result<()> {
+        self.serialize_str(&v.to_string())
+    }
+
+    fn serialize_str(self, v: &str) -> Result<()> {
+        write_line_start(self, false)?;
+        self.formatter.begin_string(&mut self.writer)?;
+        self.formatter.write_string_fragment(&mut self.writer, v)?;
+        self.formatter.end_string(&mut self.writer)
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<()> {
+        write_line_start(self, false)?;
+        self.formatter.write_bytes(&mut self.writer, v)
+    }
+
+    fn serialize_none(self) -> Result<()> {
+        let entry = matches!(
+            self.line_start,
+            Some(LineStart::Key(_) | LineStart::Field(_))
+        );
+        if entry && self.formatter.omit_none() {
+            self.line_start = None;
+            self.comment = None;
+            self.omitted_entry = true;
+            return Ok(());
+        }
+
+        self.serialize_unit()
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<()> {
+        write_line_start(self, false)?;
+        self.formatter.write_null(&mut self.writer)
+    }
+
+    fn serialize_unit_struct(self, _: &'static str) -> Result<()> {
+        self.serialize_unit()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<()> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T>(self, name: &'static str, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        if name == COMMENTED {
+            return value.serialize(CommentSerializer::new(self));
+        }
+
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        // A variant of nothing but unit is written like a unit variant
+        if value.serialize(UnitProbe).is_ok() {
+            return self.serialize_str(variant);
+        }
+
+        write_line_start(self, true)?;
+        self.formatter.begin_dict(&mut self.writer)?;
+        self.formatter.write_key(&mut self.writer, variant)?;
+        value.serialize(&mut *self)?;
+        self.formatter.write_newline(&mut self.writer)?;
+        self.formatter.end_dict(&mut self.writer)
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq> {
+        write_line_start(self, true)?;
+        if self.formatter.compact_list_width().is_some() {
+            self.compact_list = Some(Vec::new());
+        } else {
+            self.formatter.begin_list(&mut self.writer)?;
+        }
+
+        Ok(self)
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant> {
+        write_line_start(self, true)?;
+        self.formatter.begin_dict(&mut self.writer)?;
+        self.formatter.write_key(&mut self.writer, variant)?;
+        self.formatter.begin_list(&mut self.writer)?;
+
+        Ok(self)
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
+        write_line_start(self, true)?;
+        self.formatter.begin_dict(&mut self.writer)?;
+        if self.formatter.sort_keys() {
+            self.sorted_entries.push(Vec::new());
+        }
+
+        Ok(self)
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeStruct> {
+        write_line_start(self, true)?;
+        self.formatter.begin_dict(&mut self.writer)?;
+
+        Ok(self)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant> {
+        write_line_start(self, true)?;
+        self.formatter.begin_dict(&mut self.writer)?;
+        self.formatter.write_key(&mut self.writer, variant)?;
+
+        self.formatter.begin_dict(&mut self.writer)?;
+
+        Ok(self)
+    }
+}
+
+impl<W: std::io::Write, F: Formatter> ser::SerializeSeq for &mut Serializer<W, F> {
+    type Ok = ();
+
+    type Error = Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        if let Some(mut elements) = self.compact_list.take() {
+            let max_width = self.formatter.compact_list_width().unwrap_or(0);
+            if let Ok(element) = value.serialize(ScalarSerializer::new(&mut self.formatter)) {
+                let width = 2 + elements.iter().map(|e| e.len() + 1).sum::<usize>() + element.len();
+                if width <= max_width {
+                    elements.push(element);
+                    self.compact_list = Some(elements);
+                    return Ok(());
+                }
+            }
+
+            write_expanded_list(self, elements)?;
+        }
+
+        self.line_start = Some(LineStart::Element);
+        value.serialize(&mut **self)?;
+        self.formatter.write_newline(&mut self.writer)
+    }
+
+    fn end(self) -> Result<()> {
+        match self.compact_list.take() {
+            Some(elements) => self
+                .formatter
+                .write_compact_list(&mut self.writer, &elements),
+            None => self.formatter.end_list(&mut self.writer),
+        }
+    }
+}
+
+impl<W: std::io::Write, F: Formatter> ser::SerializeTuple for &mut Serializer<W, F> {
+    type Ok = ();
+
+    type Error = Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<()> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+impl<W: std::io::Write, F: Formatter> ser::SerializeTupleStruct for &mut Serializer<W, F> {
+    type Ok = ();
+
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<()> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+impl<W: std::io::Write, F: Formatter> ser::SerializeTupleVariant for &mut Serializer<W, F> {
+    type Ok = ();
+
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.formatter.end_list(&mut self.writer)?;
+        self.formatter.write_newline(&mut self.writer)?;
+        self.formatter.end_dict(&mut self.writer)
+    }
+}
+
+impl<W: std::io::Write, F: Formatter> ser::SerializeMap for &mut Serializer<W, F> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_key<T>(&mut self, key: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        key.serialize(KeySerializer::new(*self))?;
+
+        // Entries of a sorted map are written to a buffer of their own until the map ends
+        if self.formatter.sort_keys() {
+            self.writer.buffers.push(Vec::new());
+        }
+
+        Ok(())
+    }
+
+    fn serialize_value<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        let key = match &self.line_start {
+            Some(LineStart::Key(key)) if self.formatter.sort_keys() => key.to_string(),
+            _ => String::new(),
+        };
+
+        value.serialize(&mut **self)?;
+        end_entry(self)?;
+
+        if self.formatter.sort_keys() {
+            let entry = SortedEntry {
+                key,
+                output: self.writer.buffers.pop().unwrap_or_default(),
+                root_block: match self.formatter.get_indent() {
+                    0 => self.sorted_root_entry.take(),
+                    _ => None,
+                },
+            };
+            if let Some(entries) = self.sorted_entries.last_mut() {
+                entries.push(entry);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn serialize_entry<K, V>(&mut self, key: &K, value: &V) -> Result<()>
+    where
+        K: ?Sized + Serialize,
+        V: ?Sized + Serialize,
+    {
+        self.serialize_key(key)?;
+        ser::SerializeMap::serialize_value(self, value)
+    }
+
+    fn end(self) -> Result<()> {
+        if self.formatter.sort_keys() {
+            let mut entries = self.sorted_entries.pop().unwrap_or_default();
+            entries.sort_by(|a, b| a.key.cmp(&b.key));
+            for entry in entries {
+                if let Some(block) = entry.root_block {
+                    if block
+                        && self.wrote_root_entry
+                        && self.formatter.blank_line_between_sections()
+                    {
+                        self.formatter.write_newline(&mut self.writer)?;
+                    }
+                    self.wrote_root_entry = true;
+                }
+                std::io::Write::write_all(&mut self.writer, &entry.output).map_err(Error::Io)?;
+            }
+        }
+
+        self.formatter.end_dict(&mut self.writer)
+    }
+}
+
+impl<W: std::io::Write, F: Formatter> ser::SerializeStruct for &mut Serializer<W, F> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.line_start = Some(LineStart::Field(key));
+        value.serialize(&mut **self)?;
+        end_entry(self)
+    }
+
+    fn end(self) -> Result<()> {
+        self.formatter.end_dict(&mut self.writer)
+    }
+}
+
+impl<W: std::io::Write, F: Formatter> ser::SerializeStructVariant for &mut Serializer<W, F> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.line_start = Some(LineStart::Field(key));
+        value.serialize(&mut **self)?;
+        end_entry(self)
+    }
+
+    fn end(self) -> Result<()> {
+        self.formatter.end_dict(&mut self.writer)?;
+        self.formatter.write_newline(&mut self.writer)?;
+        self.formatter.end_dict(&mut self.writer)
+    }
+}
+
+impl std::fmt::Display for TotValue {
+    /// Write the value as a document, the same as [`to_string`].
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&to_string(self).map_err(|_| std::fmt::Error)?)
+    }
+}
+
+impl Serialize for TotValue {
+    /// Dict entries are written in key order, since a dict doesn't keep the order they were
+    /// parsed in.
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        match self {
+            TotValue::Unit => serializer.serialize_unit(),
+            TotValue::Boolean(v) => serializer.serialize_bool(*v),
+            TotValue::String(v) => serializer.serialize_str(v),
+            TotValue::Bytes(v) => serializer.serialize_bytes(v),
+            TotValue::Integer(v) => serializer.serialize_i64(*v),
+            TotValue::Float(v) => serializer.serialize_f64(*v),
+            TotValue::List(v) => serializer.collect_seq(v),
+            TotValue::Dict(v) => {
+                let mut entries = v.iter().collect::<Vec<_>>();
+                entries.sort_unstable_by_key(|(k, _)| *k);
+                serializer.collect_map(entries)
+            }
+        }
+    }
+}
+
+/// Serialize a `T` into a byte vector.
+///
+/// The output is always valid UTF-8, so this skips the validation done by [`to_string`].
+pub fn to_vec<T: ?Sized + Serialize>(value: &T) -> Result<Vec<u8>> {
+    to_vec_with_formatter(value, DefaultFormatter::default())
+}
+
+/// Serialize a `T` into a byte vector using the given formatter options.
+pub fn to_vec_with_formatter<T: ?Sized + Serialize>(
+    value: &T,
+    formatter: DefaultFormatter,
+) -> Result<Vec<u8>> {
+    to_vec_with_capacity(value, formatter, 0)
+}
+
+fn to_vec_with_capacity<T: ?Sized + Serialize>(
+    value: &T,
+    formatter: DefaultFormatter,
+    capacity: usize,
+) -> Result<Vec<u8>> {
+    let mut serializer = Serializer::with_formatter(Vec::with_capacity(capacity), formatter);
+
+    value.serialize(&mut serializer)?;
+
+    // TODO Enum roots don't insert an ending newline so insert a newline manually for now
+    let mut output = serializer.into_inner();
+    if !output.ends_with(b"\n") {
+        output.extend_from_slice(b"\n");
+    }
+
+    Ok(output)
+}
+
+/// Serialize a `T` into a `String`.
+///
+/// A dict at the root of a document is written without braces, so an empty root dict (or a
+/// struct with no fields) is written as a single newline. Nested empty dicts keep their braces
+/// and are written as `{` and `}` on separate lines.
+pub fn to_string<T: ?Sized + Serialize>(value: &T) -> Result<String> {
+    to_string_with_formatter(value, DefaultFormatter::default())
+}
+
+/// Serialize a `T` into a `String`, starting with room for `capacity` bytes.
+///
+/// The output is the same as [`to_string`]'s. When the size of the output is roughly known, like
+/// when writing documents of the same shape over and over, this saves growing the buffer as it's
+/// written. The buffer only doubles in size about 20 times for a megabyte though, so this
+/// matters more for keeping peak memory down than for speed.
+pub fn to_string_with_capacity<T: ?Sized + Serialize>(
+    value: &T,
+    capacity: usize,
+) -> Result<String> {
+    String::from_utf8(to_vec_with_capacity(
+        value,
+        DefaultFormatter::default(),
+        capacity,
+    )?)
+    .map_err(|e| Error::SerdeError(e.to_string()))
+}
+
+/// Serialize a dict of documented settings into a `String`, with a blank line between entries.
+///
+/// Each entry is written after its comment, like with [`to_string`], but the blank lines keep
+/// every setting and its documentation together, as in a generated default config file.
+///
+/// ```
+/// use std::collections::BTreeMap;
+///
+/// use tot::ser::{to_string_documented, Commented};
+/// use tot::TotValue;
+///
+/// let config = BTreeMap::from([
+///     (
+///         "port".to_string(),
+///         Commented::new("Port to listen on", TotValue::Integer(8080)),
+///     ),
+///     (
+///         "host".to_string(),
+///         Commented::new("", TotValue::String("localhost".to_string())),
+///     ),
+/// ]);
+///
+/// assert_eq!(
+///     to_string_documented(&config).unwrap(),
+///     "host \"localhost\"\n\n// Port to listen on\nport 8080\n"
+/// );
+/// ```
+pub fn to_string_documented(config: &BTreeMap<String, Commented<TotValue>>) -> Result<String> {
+    if config.is_empty() {
+        return to_string(config);
+    }
+
+    let mut output = String::new();
+    for (key, entry) in config {
+        if !output.is_empty() {
+            output.push('\n');
+        }
+        output.push_str(&to_string(&BTreeMap::from([(key, entry)]))?);
+    }
+
+    Ok(output)
+}
+
+/// Serialize a `T` into a `String`, with a blank line before each list or dict in a root dict.
+///
+/// See [`DefaultFormatter::blank_line_between_sections`].
+pub fn to_string_pretty<T: ?Sized + Serialize>(value: &T) -> Result<String> {
+    to_string_with_formatter(
+        value,
+        DefaultFormatter::default().blank_line_between_sections(true),
+    )
+}
+
+/// Serialize a `T` into a `String` using the given formatter options.
+pub fn to_string_with_formatter<T: ?Sized + Serialize>(
+    value: &T,
+    formatter: DefaultFormatter,
+) -> Result<String> {
+    String::from_utf8(to_vec_with_formatter(value, formatter)?)
+        .map_err(|e| Error::SerdeError(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Serialize;
+    use std::collections::{BTreeMap, HashMap};
+
+    use super::{
+        to_string, to_string_documented, to_string_pretty, to_string_with_capacity,
+        to_string_with_formatter, to_vec, CommentStyle, Commented, DefaultFormatter, FloatFormat,
+        Formatter, RootType, Serializer,
+    };
+    use crate::TotValue;
+
+    mod primitive_tests {
+        use super::*;
+
+        #[test]
+        fn test_tuple() {
+            let data = ("hello", "world", true);
+
+            let output = to_string(&data).unwrap();
+
+            assert_eq!(
+                output,
+                "\
+[
+    \"hello\"
+    \"world\"
+    true
+]
+"
+            )
+        }
+
+        #[test]
+        fn test_tuple_nested() {
+            let data = (
+                "hello",
+                true,
+                (false, 123.0),
+                BTreeMap::<String, String>::new(),
+            );
+
+            let output = to_string(&data).unwrap();
+
+            assert_eq!(
+                output,
+                "\
+[
+    \"hello\"
+    true
+    [
+        false
+        123.0
+    ]
+    {
+    }
+]
+"
+            );
+        }
+
+        #[test]
+        fn test_str_escapes() {
+            let output = to_string(&"say \"hi\" C:\\ é").unwrap();
+
+            assert_eq!(output, "\"say \\\"hi\\\" C:\\\\ é\"\n");
+            assert_eq!(
+                crate::from_str::<String>(output.trim_end()).unwrap(),
+                "say \"hi\" C:\\ é"
+            );
+        }
+
+        #[test]
+        fn test_str_control_chars() {
+            let data = "a\u{1}b\u{1F}c\nd\re\tf\u{8}\u{C}\u{0}";
+
+            let output = to_string(&data).unwrap();
+            assert_eq!(output, "\"a\\u{1}b\\u{1f}c\\nd\\re\\tf\\b\\f\\u{0}\"\n");
+            assert_eq!(crate::from_str::<String>(&output).unwrap(), data);
+
+            let output = to_string(&BTreeMap::from([(data, data)])).unwrap();
+            assert_eq!(output.lines().count(), 1);
+            assert_eq!(
+                crate::parser::parse(&output)
+                    .unwrap()
+                    .pointer(&format!("/{data}")),
+                Some(&TotValue::String(data.to_string()))
+            );
+        }
+
+        #[test]
+        fn test_str_slashes() {
+            for data in [
+                "a/b",
+                "http://example.com",
+                "/* not a comment */",
+                "//",
+                "\\/",
+            ] {
+                let output = to_string(&data).unwrap();
+                assert_eq!(output, format!("\"{}\"\n", data.replace('\\', "\\\\")));
+                assert_eq!(crate::from_str::<String>(&output).unwrap(), data);
+
+                let output = to_string(&BTreeMap::from([(data, data)])).unwrap();
+                assert_eq!(crate::format::format_source(&output).unwrap(), output);
+                assert_eq!(
+                    crate::from_str::<BTreeMap<String, String>>(&output).unwrap(),
+                    BTreeMap::from([(data.to_string(), data.to_string())])
+                );
+            }
+        }
+
+        #[test]
+        fn test_str_ascii_only() {
+            let data = "héllo 😀";
+
+            let output =
+                to_string_with_formatter(&data, DefaultFormatter::new().ascii_only(true)).unwrap();
+
+            assert_eq!(output, "\"h\\u{e9}llo \\u{1f600}\"\n");
+            assert!(output.is_ascii());
+            assert_eq!(crate::from_str::<String>(output.trim_end()).unwrap(), data);
+
+            let data = BTreeMap::from([("clé", "é"), ("key", "😀")]);
+
+            let output =
+                to_string_with_formatter(&data, DefaultFormatter::new().ascii_only(true)).unwrap();
+
+            assert_eq!(output, "\"cl\\u{e9}\" \"\\u{e9}\"\nkey \"\\u{1f600}\"\n");
+            assert_eq!(
+                crate::from_str::<BTreeMap<String, String>>(&output).unwrap(),
+                BTreeMap::from([
+                    ("clé".to_string(), "é".to_string()),
+                    ("key".to_string(), "😀".to_string())
+                ])
+            );
+            assert_eq!(to_string(&data).unwrap(), "clé \"é\"\nkey \"😀\"\n");
+        }
+
+        #[test]
+        fn test_float_format() {
+            let data = vec![1.0 / 3.0, 2.0, -1234.5678];
+
+            let output = to_string(&data).unwrap();
+            assert!(output.contains("0.3333333333333333\n"));
+
+            let output = to_string_with_formatter(
+                &data,
+                DefaultFormatter::new().float_format(FloatFormat::Decimals(2)),
+            )
+            .unwrap();
+            assert_eq!(
+                output,
+                "\
+[
+    0.33
+    2.00
+    -1234.57
+]
+"
+            );
+
+            let output = to_string_with_formatter(
+                &data,
+                DefaultFormatter::new().float_format(FloatFormat::Precision(3)),
+            )
+            .unwrap();
+            assert_eq!(
+                output,
+                "\
+[
+    3.33e-1
+    2.00e0
+    -1.23e3
+]
+"
+            );
+
+            for format in [FloatFormat::Decimals(2), FloatFormat::Precision(3)] {
+                let output =
+                    to_string_with_formatter(&data, DefaultFormatter::new().float_format(format))
+                        .unwrap();
+                let parsed = crate::from_str::<Vec<f64>>(&output).unwrap();
+                for (a, b) in data.iter().zip(parsed) {
+                    assert!((a - b).abs() <= a.abs() * 0.01, "{a} vs {b}");
+                }
+            }
+
+            let output = to_string_with_formatter(
+                &data,
+                DefaultFormatter::new().float_format(FloatFormat::Decimals(0)),
+            )
+            .unwrap();
+            assert_eq!(output, "[\n    0.0\n    2.0\n    -1235.0\n]\n");
+
+            let data = [f64::NAN, f64::INFINITY, f64::NEG_INFINITY];
+            for format in [
+                FloatFormat::Decimals(0),
+                FloatFormat::Decimals(2),
+                FloatFormat::Precision(0),
+                FloatFormat::Precision(3),
+            ] {
+                let output =
+                    to_string_with_formatter(&data, DefaultFormatter::new().float_format(format))
+                        .unwrap();
+                assert_eq!(output, "[\n    NaN\n    inf\n    -inf\n]\n", "{format:?}");
+                let parsed = crate::from_str::<Vec<f64>>(&output).unwrap();
+                assert!(parsed[0].is_nan());
+                assert_eq!(parsed[1..], data[1..]);
+            }
+        }
+
+        #[test]
+        fn test_float_exponents() {
+            for (value, expected) in [
+                (1e300, "1e300"),
+                (1e-300, "1e-300"),
+                (1.23e45, "1.23e45"),
+                (-1.23e45, "-1.23e45"),
+                (f64::MAX, "1.7976931348623157e308"),
+                (5e-324, "5e-324"),
+            ] {
+                let output = to_string(&value).unwrap();
+                assert_eq!(output, format!("{expected}\n"));
+                assert_eq!(crate::from_str::<f64>(&output).unwrap(), value);
+
+                let output = to_string(&vec![value]).unwrap();
+                assert_eq!(crate::from_str::<Vec<f64>>(&output).unwrap(), [value]);
+            }
+
+            let output = to_string_with_formatter(
+                &vec![1e300, 1e-300, 1.23e45],
+                DefaultFormatter::new().float_format(FloatFormat::Precision(3)),
+            )
+            .unwrap();
+            assert_eq!(output, "[\n    1.00e300\n    1.00e-300\n    1.23e45\n]\n");
+            assert_eq!(
+                crate::from_str::<Vec<f64>>(&output).unwrap(),
+                [1e300, 1e-300, 1.23e45]
+            );
+        }
+
+        #[test]
+        fn test_float_integer_distinct() {
+            #[derive(Serialize)]
+            struct Test {
+                float: f64,
+                single: f32,
+                integer: i64,
+                unsigned: u64,
+                large: f64,
+                small: f64,
+            }
+
+            let data = Test {
+                float: 10.0,
+                single: 10.0,
+                integer: 10,
+                unsigned: 10,
+                large: 1e20,
+                small: -1e-7,
+            };
+
+            let output = to_string(&data).unwrap();
+            assert_eq!(
+                output,
+                "\
+float 10.0
+single 10.0
+integer 10
+unsigned 10
+large 1e20
+small -1e-7
+"
+            );
+
+            let value = crate::parser::parse(&output).unwrap();
+            for key in ["float", "single", "large", "small"] {
+                assert!(
+                    value.pointer(&format!("/{key}")).unwrap().is_float(),
+                    "{key}"
+                );
+            }
+            for key in ["integer", "unsigned"] {
+                assert!(
+                    value.pointer(&format!("/{key}")).unwrap().is_integer(),
+                    "{key}"
+                );
+            }
+        }
+
+        #[test]
+        fn test_bytes() {
+            let data: &[u8] = &[0, 1, 2, 3];
+
+            let output = to_string(&data).unwrap();
+
+            assert_eq!(
+                output,
+                "\
+[
+    0
+    1
+    2
+    3
+]
+"
+            );
+        }
+
+        #[test]
+        fn test_bytes_literal() {
+            let data = TotValue::Dict(HashMap::from([(
+                "data".to_string(),
+                TotValue::Bytes(vec![0, 1, 2, 3, 255]),
+            )]));
+
+            let output = to_string(&data).unwrap();
+
+            assert_eq!(output, "data b\"AAECA/8=\"\n");
+            assert_eq!(crate::from_str::<TotValue>(&output).unwrap(), data);
+
+            let data = TotValue::List(vec![
+                TotValue::Bytes(b"hi".to_vec()),
+                TotValue::Bytes(Vec::new()),
+            ]);
+            let output =
+                to_string_with_formatter(&data, DefaultFormatter::new().compact_lists(40)).unwrap();
+
+            assert_eq!(output, "[b\"aGk=\" b\"\"]\n");
+            assert_eq!(crate::from_str::<TotValue>(&output).unwrap(), data);
+            assert_eq!(
+                crate::from_str::<TotValue>("b\"aGk=\"").unwrap(),
+                TotValue::Bytes(b"hi".to_vec())
+            );
+        }
+
+        #[test]
+        fn test_vec() {
+            let data = vec![true, false, true];
+
+            let output = to_string(&data).unwrap();
+
+            assert_eq!(
+                output,
+                "\
 [
     true
     false
@@ -868,60 +2640,922 @@ mod tests {
             )
         }
 
-        // NOTE BTreeMap is used for consistent ordering
         #[test]
-        fn test_map() {
-            let data = {
-                let mut map = BTreeMap::new();
-                map.insert("hello".to_string(), 123.0);
-                map.insert("world".to_string(), 0.0);
-                map
+        fn test_keywords() {
+            #[derive(Serialize)]
+            struct Test {
+                enabled: bool,
+                verbose: bool,
+                name: Option<&'static str>,
+            }
+
+            let data = Test {
+                enabled: true,
+                verbose: false,
+                name: None,
+            };
+            let formatter = DefaultFormatter::new()
+                .bool_keywords("yes", "no")
+                .null_keyword("none");
+
+            let output = to_string_with_formatter(&data, formatter).unwrap();
+            assert_eq!(output, "enabled yes\nverbose no\nname none\n");
+
+            let options = crate::parser::ParseOptions::new()
+                .true_keywords(["yes"])
+                .false_keywords(["no"])
+                .null_keywords(["none"]);
+            let value = crate::parser::Parser::with_options(options)
+                .parse(&output)
+                .unwrap();
+            assert_eq!(
+                value,
+                crate::parser::parse(&to_string(&data).unwrap()).unwrap()
+            );
+        }
+
+        #[test]
+        fn test_vec_compact() {
+            #[derive(Serialize)]
+            struct Point {
+                coords: Vec<i32>,
+                names: (&'static str, Option<bool>),
+                empty: Vec<i32>,
+            }
+
+            let formatter = || DefaultFormatter::new().compact_lists(30);
+
+            let output = to_string_with_formatter(&vec![1, 2, 3], formatter()).unwrap();
+            assert_eq!(output, "[1 2 3]\n");
+            assert_eq!(crate::from_str::<Vec<i32>>(&output).unwrap(), vec![1, 2, 3]);
+
+            let data = Point {
+                coords: vec![-1, 0, 1],
+                names: ("x", None),
+                empty: vec![],
+            };
+            let output = to_string_with_formatter(&data, formatter()).unwrap();
+            assert_eq!(
+                output,
+                "\
+coords [-1 0 1]
+names [\"x\" null]
+empty []
+"
+            );
+            crate::parser::parse(&output).unwrap();
+        }
+
+        #[test]
+        fn test_vec_compact_multiline() {
+            let formatter = || DefaultFormatter::new().compact_lists(30);
+
+            let data: Vec<i32> = (1..=20).collect();
+            let output = to_string_with_formatter(&data, formatter()).unwrap();
+            assert_eq!(output.lines().count(), 22);
+            assert!(output.starts_with("[\n    1\n    2\n"));
+            assert_eq!(crate::from_str::<Vec<i32>>(&output).unwrap(), data);
+
+            let data = vec![vec![1, 2], vec![3]];
+            let output = to_string_with_formatter(&data, formatter()).unwrap();
+            assert_eq!(
+                output,
+                "\
+[
+    [1 2]
+    [3]
+]
+"
+            );
+            assert_eq!(crate::from_str::<Vec<Vec<i32>>>(&output).unwrap(), data);
+
+            let data = (1, Commented::new("two", 2), 3);
+            let output = to_string_with_formatter(&data, formatter()).unwrap();
+            assert_eq!(
+                output,
+                "\
+[
+    1
+    // two
+    2
+    3
+]
+"
+            );
+        }
+
+        // NOTE BTreeMap is used for consistent ordering
+        #[test]
+        fn test_map() {
+            let data = {
+                let mut map = BTreeMap::new();
+                map.insert("hello".to_string(), 123.0);
+                map.insert("world".to_string(), 0.0);
+                map
+            };
+
+            let output = to_string(&data).unwrap();
+
+            assert_eq!(
+                output,
+                "\
+hello 123.0
+world 0.0
+"
+            )
+        }
+
+        #[test]
+        fn test_map_nested() {
+            let data = {
+                let mut map = BTreeMap::new();
+                map.insert("inner1", {
+                    let mut map = BTreeMap::new();
+                    map.insert("my-key", "my-value");
+
+                    map
+                });
+                map.insert("inner2", {
+                    let mut map = BTreeMap::new();
+                    map.insert("other", "val");
+
+                    map
+                });
+
+                map
+            };
+
+            let output = to_string(&data).unwrap();
+
+            assert_eq!(
+                output,
+                "\
+inner1 {
+    my-key \"my-value\"
+}
+inner2 {
+    other \"val\"
+}
+"
+            )
+        }
+
+        #[test]
+        fn test_map_empty() {
+            let output = to_string(&BTreeMap::<String, i32>::new()).unwrap();
+
+            assert_eq!(output, "\n");
+        }
+
+        #[test]
+        fn test_map_nested_empty() {
+            let data = {
+                let mut map = BTreeMap::new();
+                map.insert("inner", BTreeMap::<String, i32>::new());
+                map
+            };
+
+            let output = to_string(&data).unwrap();
+
+            assert_eq!(
+                output,
+                "\
+inner {
+}
+"
+            )
+        }
+
+        #[test]
+        fn test_map_quoted_keys() {
+            let data = {
+                let mut map = BTreeMap::new();
+                map.insert("bare", 1.0);
+                map.insert("with space", 2.0);
+                map.insert("", 3.0);
+                map
+            };
+
+            let output = to_string(&data).unwrap();
+
+            assert_eq!(
+                output,
+                "\
+\"\" 3.0
+bare 1.0
+\"with space\" 2.0
+"
+            )
+        }
+
+        #[test]
+        fn test_map_non_string_keys() {
+            let data = BTreeMap::from([(-1, 10), (2, 20)]);
+
+            let output = to_string(&data).unwrap();
+
+            assert_eq!(output, "-1 10\n2 20\n");
+            assert_eq!(
+                crate::from_str::<BTreeMap<i32, i32>>(&output).unwrap(),
+                data
+            );
+
+            let data = BTreeMap::from([("nested", BTreeMap::from([(false, 1.5), (true, 2.0)]))]);
+
+            let output = to_string(&data).unwrap();
+
+            assert_eq!(
+                output,
+                "\
+nested {
+    false 1.5
+    true 2.0
+}
+"
+            );
+            assert_eq!(
+                crate::from_str::<BTreeMap<String, BTreeMap<bool, f64>>>(&output).unwrap()
+                    ["nested"],
+                data["nested"]
+            );
+
+            let data = BTreeMap::from([('a', 0.5), ('b', 1.0)]);
+
+            let output = to_string(&data).unwrap();
+
+            assert_eq!(output, "a 0.5\nb 1.0\n");
+        }
+
+        #[test]
+        fn test_non_finite() {
+            /// A float map key, compared by its bits so NaN can be one.
+            #[derive(Serialize, serde::Deserialize, Debug, Clone, Copy)]
+            struct Key(f64);
+
+            impl PartialEq for Key {
+                fn eq(&self, other: &Self) -> bool {
+                    self.0.to_bits() == other.0.to_bits()
+                }
+            }
+
+            impl Eq for Key {}
+
+            impl PartialOrd for Key {
+                fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+                    Some(self.cmp(other))
+                }
+            }
+
+            impl Ord for Key {
+                fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+                    self.0.total_cmp(&other.0)
+                }
+            }
+
+            let values = [f64::NAN, f64::INFINITY, f64::NEG_INFINITY];
+
+            let output = to_string(&values).unwrap();
+            assert_eq!(output, "[\n    NaN\n    inf\n    -inf\n]\n");
+            let read = crate::from_str::<Vec<f64>>(&output).unwrap();
+            assert!(read[0].is_nan());
+            assert_eq!(read[1..], values[1..]);
+
+            let data = BTreeMap::from(values.map(|v| (Key(v), v)));
+            let output = to_string(&data).unwrap();
+            assert_eq!(output, "-inf -inf\ninf inf\nNaN NaN\n");
+            let read = crate::from_str::<BTreeMap<Key, f64>>(&output).unwrap();
+            assert_eq!(
+                read.keys().collect::<Vec<_>>(),
+                data.keys().collect::<Vec<_>>()
+            );
+        }
+
+        #[test]
+        fn test_tot_value() {
+            let input = "\
+name \"tot\"
+nothing null
+server {
+    ports [
+        80.0
+        443.0
+    ]
+    tls true
+}
+";
+            let value = crate::parser::parse(input).unwrap();
+
+            let output = to_string(&value).unwrap();
+            assert_eq!(
+                output,
+                "\
+name \"tot\"
+nothing null
+server {
+    ports [
+        80.0
+        443.0
+    ]
+    tls true
+}
+"
+            );
+            assert_eq!(crate::parser::parse(&output).unwrap(), value);
+
+            let value = TotValue::List(vec![TotValue::Unit, TotValue::Float(1.5)]);
+            assert_eq!(to_string(&value).unwrap(), "[\n    null\n    1.5\n]\n");
+        }
+
+        #[test]
+        fn test_tot_value_display() {
+            let value = crate::parser::parse("name \"tot\" server { ports [80 443.0] }").unwrap();
+
+            assert_eq!(
+                format!("{value}"),
+                "\
+name \"tot\"
+server {
+    ports [
+        80
+        443.0
+    ]
+}
+"
+            );
+            assert_eq!(value.to_string(), to_string(&value).unwrap());
+            assert_eq!(TotValue::Boolean(true).to_string(), "true\n");
+        }
+
+        #[test]
+        fn test_tot_value_numbers() {
+            let value = TotValue::List(vec![
+                TotValue::Float(10.0),
+                TotValue::Integer(10),
+                TotValue::Float(-0.0),
+                TotValue::Integer(0),
+            ]);
+
+            let output = to_string(&value).unwrap();
+            assert_eq!(output, "[\n    10.0\n    10\n    -0.0\n    0\n]\n");
+
+            let parsed = crate::parser::parse(&output).unwrap();
+            assert_eq!(parsed, value);
+            assert!(matches!(
+                parsed,
+                TotValue::List(ref v) if v[0].is_float() && v[1].is_integer()
+            ));
+            assert_eq!(crate::from_str::<TotValue>(&output).unwrap(), value);
+        }
+
+        #[test]
+        fn test_commented() {
+            #[derive(Serialize)]
+            struct Inner {
+                b: Commented<bool>,
+            }
+
+            #[derive(Serialize)]
+            struct Test {
+                a: Commented<f64>,
+                inner: Commented<Inner>,
+                list: Vec<Commented<&'static str>>,
+            }
+
+            let data = Test {
+                a: Commented::new("First line\n\nThird line", 1.0),
+                inner: Commented::new(
+                    "Nested",
+                    Inner {
+                        b: Commented::new("Indented", true),
+                    },
+                ),
+                list: vec![Commented::new("Element", "x")],
+            };
+
+            let output = to_string(&data).unwrap();
+
+            assert_eq!(
+                output,
+                "\
+// First line
+//
+// Third line
+a 1.0
+// Nested
+inner {
+    // Indented
+    b true
+}
+list [
+    // Element
+    \"x\"
+]
+"
+            )
+        }
+
+        #[test]
+        fn test_comment_style() {
+            #[derive(Serialize)]
+            struct Inner {
+                b: Commented<bool>,
+            }
+
+            #[derive(Serialize)]
+            struct Test {
+                a: Commented<f64>,
+                inner: Inner,
+            }
+
+            let data = || Test {
+                a: Commented::new("First line\n\nThird line", 1.0),
+                inner: Inner {
+                    b: Commented::new("Ends */ early?", true),
+                },
+            };
+
+            let output = to_string_with_formatter(
+                &data(),
+                DefaultFormatter::new().comment_style(CommentStyle::Line),
+            )
+            .unwrap();
+            assert_eq!(output, to_string(&data()).unwrap());
+
+            let output = to_string_with_formatter(
+                &data(),
+                DefaultFormatter::new().comment_style(CommentStyle::Block),
+            )
+            .unwrap();
+            assert_eq!(
+                output,
+                "\
+/* First line
+
+   Third line */
+a 1.0
+inner {
+    /* Ends * / early? */
+    b true
+}
+"
+            );
+            assert_eq!(
+                crate::parser::parse(&output).unwrap(),
+                crate::parser::parse("a 1.0\ninner { b true }").unwrap()
+            );
+        }
+
+        #[test]
+        fn test_u64_exact() {
+            #[derive(Serialize, serde::Deserialize, Debug, PartialEq)]
+            struct Test {
+                id: u64,
+                small: u8,
+            }
+
+            let data = Test {
+                id: 1234567890123456789,
+                small: 7,
             };
 
             let output = to_string(&data).unwrap();
+            assert_eq!(output, "id 1234567890123456789\nsmall 7\n");
+            assert_eq!(crate::from_str::<Test>(&output).unwrap(), data);
+
+            let output = to_string(&u64::MAX).unwrap();
+            assert_eq!(output, "18446744073709551615\n");
+            assert_eq!(crate::from_str::<u64>(&output).unwrap(), u64::MAX);
+        }
+
+        #[test]
+        fn test_i64_exact() {
+            // 2^53 + 1, the first integer a float can't hold
+            let output = to_string(&9007199254740993i64).unwrap();
+            assert_eq!(output, "9007199254740993\n");
+            assert_eq!(crate::from_str::<i64>(&output).unwrap(), 9007199254740993);
+
+            let data = BTreeMap::from([("min", i64::MIN), ("max", i64::MAX), ("neg", -7)]);
+            let output = to_string(&data).unwrap();
+            assert_eq!(
+                output,
+                "max 9223372036854775807\nmin -9223372036854775808\nneg -7\n"
+            );
+            assert_eq!(
+                crate::from_str::<BTreeMap<String, i64>>(&output).unwrap()["min"],
+                i64::MIN
+            );
+        }
+
+        #[test]
+        fn test_blank_line_between_sections() {
+            #[derive(Serialize)]
+            struct Server {
+                host: &'static str,
+            }
+
+            #[derive(Serialize)]
+            struct Config {
+                servers: Vec<Server>,
+                name: &'static str,
+                version: u32,
+                server: Commented<Server>,
+                ports: Vec<u32>,
+                debug: bool,
+            }
 
+            let data = Config {
+                servers: vec![Server { host: "a" }],
+                name: "tot",
+                version: 1,
+                server: Commented::new("main", Server { host: "b" }),
+                ports: vec![80],
+                debug: false,
+            };
+
+            let output = to_string_pretty(&data).unwrap();
             assert_eq!(
                 output,
                 "\
-hello 123.0
-world 0.0
+servers [
+    {
+        host \"a\"
+    }
+]
+name \"tot\"
+version 1
+
+// main
+server {
+    host \"b\"
+}
+
+ports [
+    80
+]
+debug false
 "
-            )
+            );
+            assert_eq!(
+                crate::parser::parse(&output).unwrap(),
+                crate::parser::parse(&to_string(&data).unwrap()).unwrap()
+            );
+
+            // Only a root dict is split into sections
+            let output = to_string_pretty(&vec![vec![1u8], vec![2]]).unwrap();
+            assert_eq!(output, to_string(&vec![vec![1u8], vec![2]]).unwrap());
         }
 
         #[test]
-        fn test_map_nested() {
-            let data = {
-                let mut map = BTreeMap::new();
-                map.insert("inner1", {
-                    let mut map = BTreeMap::new();
-                    map.insert("my-key", "my-value");
+        fn test_to_string_with_capacity() {
+            let data = BTreeMap::from([("list", vec![1, 2, 3]), ("empty", vec![])]);
+            let output = to_string(&data).unwrap();
 
-                    map
-                });
-                map.insert("inner2", {
-                    let mut map = BTreeMap::new();
-                    map.insert("other", "val");
+            for capacity in [0, 1, output.len(), 4096] {
+                assert_eq!(to_string_with_capacity(&data, capacity).unwrap(), output);
+            }
+            assert_eq!(to_string_with_capacity(&(), 0).unwrap(), "null\n");
+        }
 
-                    map
-                });
+        #[test]
+        fn test_to_string_documented() {
+            let config = BTreeMap::from([
+                (
+                    "server".to_string(),
+                    Commented::new(
+                        "Where to listen\nfor connections",
+                        TotValue::Dict(HashMap::from([(
+                            "port".to_string(),
+                            TotValue::Integer(8080),
+                        )])),
+                    ),
+                ),
+                (
+                    "debug".to_string(),
+                    Commented::new("Log every request", TotValue::Boolean(false)),
+                ),
+                (
+                    "name".to_string(),
+                    Commented::new("", TotValue::String("tot".to_string())),
+                ),
+            ]);
+
+            let output = to_string_documented(&config).unwrap();
+            assert_eq!(
+                output,
+                "\
+// Log every request
+debug false
+
+name \"tot\"
+
+// Where to listen
+// for connections
+server {
+    port 8080
+}
+"
+            );
+
+            let values = config
+                .into_iter()
+                .map(|(key, entry)| (key, entry.value))
+                .collect();
+            assert_eq!(
+                crate::parser::parse(&output).unwrap(),
+                TotValue::Dict(values)
+            );
+
+            assert_eq!(to_string_documented(&BTreeMap::new()).unwrap(), "\n");
+        }
+
+        #[test]
+        fn test_serializer_with_formatter() {
+            #[derive(Default)]
+            struct TwoSpaces {
+                depth: usize,
+                root_type: Option<RootType>,
+            }
+
+            impl Formatter for TwoSpaces {
+                fn indent(&mut self) {
+                    self.depth += 1;
+                }
+
+                fn unindent(&mut self) {
+                    self.depth -= 1;
+                }
+
+                fn get_indent(&self) -> usize {
+                    self.depth
+                }
+
+                fn is_root_type_set(&self) -> bool {
+                    self.root_type.is_some()
+                }
+
+                fn set_root_type(&mut self, root_type: RootType) {
+                    self.root_type = Some(root_type);
+                }
+
+                fn write_indent<W: ?Sized + std::io::Write>(
+                    &mut self,
+                    writer: &mut W,
+                    precalculated_amount: Option<usize>,
+                ) -> crate::Result<()> {
+                    let amount = precalculated_amount.unwrap_or(self.depth);
+                    writer
+                        .write_all("  ".repeat(amount).as_bytes())
+                        .map_err(crate::Error::Io)
+                }
+            }
+
+            #[derive(Serialize)]
+            struct Test {
+                list: Vec<u8>,
+                enabled: bool,
+            }
+
+            let mut serializer = Serializer::with_formatter(Vec::new(), TwoSpaces::default());
+            let data = Test {
+                list: vec![1, 2],
+                enabled: true,
+            };
+            data.serialize(&mut serializer).unwrap();
+
+            assert_eq!(serializer.formatter().root_type, Some(RootType::Dict));
+            assert_eq!(
+                String::from_utf8(serializer.into_inner()).unwrap(),
+                "\
+list [
+  1
+  2
+]
+enabled true
+"
+            );
+
+            let mut serializer = Serializer::new(Vec::new());
+            data.serialize(&mut serializer).unwrap();
+            assert_eq!(serializer.get_ref(), &to_vec(&data).unwrap());
+        }
+
+        #[test]
+        fn test_key_separator() {
+            let data = BTreeMap::from([
+                ("a", TotValue::Integer(1)),
+                ("quoted key", TotValue::Boolean(true)),
+                (
+                    "inner",
+                    TotValue::Dict(HashMap::from([("b".to_string(), TotValue::Integer(2))])),
+                ),
+            ]);
+
+            for (separator, output) in [
+                ("\t", "a\t1\ninner\t{\n    b\t2\n}\n\"quoted key\"\ttrue\n"),
+                (
+                    "   ",
+                    "a   1\ninner   {\n    b   2\n}\n\"quoted key\"   true\n",
+                ),
+            ] {
+                let formatter = DefaultFormatter::new().key_separator(separator);
+                let written = to_string_with_formatter(&data, formatter).unwrap();
+                assert_eq!(written, output);
+                assert_eq!(
+                    crate::parser::parse(&written).unwrap(),
+                    crate::parser::parse(&to_string(&data).unwrap()).unwrap()
+                );
+            }
+
+            // Too long to write in one go
+            let key = "k".repeat(200);
+            let formatter = DefaultFormatter::new().key_separator("\t");
+            assert_eq!(
+                to_string_with_formatter(&BTreeMap::from([(key.as_str(), 1)]), formatter).unwrap(),
+                format!("{key}\t1\n")
+            );
+        }
+
+        #[test]
+        fn test_key_separator_invalid() {
+            for (separator, key) in [(", ", "a"), ("", "a"), ("\n", "quoted key")] {
+                let formatter = DefaultFormatter::new().key_separator(separator);
+                let err = to_string_with_formatter(&BTreeMap::from([(key, 1)]), formatter);
+                assert_eq!(
+                    err.unwrap_err().to_string(),
+                    format!("serde error: invalid key separator {separator:?}")
+                );
+            }
+
+            // Nothing is checked until a key is written
+            let formatter = DefaultFormatter::new().key_separator(", ");
+            assert_eq!(
+                to_string_with_formatter(&[1], formatter).unwrap(),
+                "[\n    1\n]\n"
+            );
+        }
+
+        #[test]
+        fn test_omit_none() {
+            #[derive(Serialize)]
+            struct Test {
+                name: Option<&'static str>,
+                port: Option<u16>,
+                inner: Option<Commented<Option<u8>>>,
+                list: Vec<Option<u8>>,
+                map: BTreeMap<&'static str, Option<u8>>,
+            }
+
+            let data = Test {
+                name: None,
+                port: Some(80),
+                inner: Some(Commented::new("Left out too", None)),
+                list: vec![None, Some(1)],
+                map: BTreeMap::from([("a", None), ("b", Some(2)), ("c", None)]),
+            };
+
+            // None is null by default, as a value or at the root
+            assert_eq!(
+                to_string(&data).unwrap(),
+                "\
+name null
+port 80
+// Left out too
+inner null
+list [
+    null
+    1
+]
+map {
+    a null
+    b 2
+    c null
+}
+"
+            );
+            assert_eq!(to_string(&None::<u8>).unwrap(), "null\n");
+
+            for formatter in [
+                DefaultFormatter::new().omit_none(true),
+                DefaultFormatter::new().omit_none(true).sort_keys(true),
+            ] {
+                assert_eq!(
+                    to_string_with_formatter(&data, formatter).unwrap(),
+                    "\
+port 80
+list [
+    null
+    1
+]
+map {
+    b 2
+}
+"
+                );
+            }
+
+            let formatter = DefaultFormatter::new().omit_none(true);
+            assert_eq!(
+                to_string_with_formatter(&None::<u8>, formatter).unwrap(),
+                "null\n"
+            );
+        }
+
+        #[test]
+        fn test_sort_keys() {
+            let build = |keys: &[i32]| {
+                let mut map = HashMap::new();
+                for &key in keys {
+                    let inner = (0..4)
+                        .map(|i| (format!("k{i}"), key * i))
+                        .collect::<HashMap<_, _>>();
+                    map.insert(key, inner);
+                }
+                map
+            };
+            let sorted = || DefaultFormatter::new().sort_keys(true);
+
+            let first = to_string_with_formatter(&build(&[10, 2, -1]), sorted()).unwrap();
+            let second = to_string_with_formatter(&build(&[-1, 2, 10]), sorted()).unwrap();
+            assert_eq!(first, second);
+            assert_eq!(
+                first,
+                "\
+-1 {
+    k0 0
+    k1 -1
+    k2 -2
+    k3 -3
+}
+10 {
+    k0 0
+    k1 10
+    k2 20
+    k3 30
+}
+2 {
+    k0 0
+    k1 2
+    k2 4
+    k3 6
+}
+"
+            );
+        }
+
+        #[test]
+        fn test_sort_keys_blank_line_between_sections() {
+            /// A map written in the order given.
+            struct Entries(Vec<(&'static str, TotValue)>);
+
+            impl Serialize for Entries {
+                fn serialize<S: serde::Serializer>(
+                    &self,
+                    serializer: S,
+                ) -> std::result::Result<S::Ok, S::Error> {
+                    serializer.collect_map(self.0.iter().map(|(k, v)| (k, v)))
+                }
+            }
+
+            let list = || TotValue::List(vec![TotValue::Integer(1)]);
+            let formatter = || {
+                DefaultFormatter::new()
+                    .sort_keys(true)
+                    .blank_line_between_sections(true)
+            };
+
+            let data = Entries(vec![
+                ("z", list()),
+                ("y", TotValue::Integer(2)),
+                ("a", TotValue::Integer(3)),
+                (
+                    "b",
+                    TotValue::Dict(HashMap::from([("c".to_string(), list())])),
+                ),
+            ]);
+            assert_eq!(
+                to_string_with_formatter(&data, formatter()).unwrap(),
+                "a 3\n\nb {\n    c [\n        1\n    ]\n}\ny 2\n\nz [\n    1\n]\n"
+            );
+
+            let data = Entries(vec![("b", TotValue::Integer(2)), ("a", list())]);
+            assert_eq!(
+                to_string_with_formatter(&data, formatter()).unwrap(),
+                "a [\n    1\n]\nb 2\n"
+            );
+        }
 
+        #[test]
+        fn test_to_vec() {
+            let data = {
+                let mut map = BTreeMap::new();
+                map.insert("hello", vec![1.0, 2.0]);
+                map.insert("world", vec![]);
                 map
             };
 
-            let output = to_string(&data).unwrap();
+            let output = to_vec(&data).unwrap();
 
-            assert_eq!(
-                output,
-                "\
-inner1 {
-    my-key \"my-value\"
-}
-inner2 {
-    other \"val\"
-}
-"
-            )
+            assert_eq!(output, to_string(&data).unwrap().into_bytes());
         }
     }
 
@@ -949,6 +3583,68 @@ inner2 {
             assert_eq!(output, "\n");
         }
 
+        #[test]
+        fn test_struct_all_skipped() {
+            #[derive(Serialize)]
+            struct Skipped {
+                #[serde(skip_serializing_if = "Option::is_none")]
+                a: Option<f64>,
+                #[serde(skip_serializing_if = "std::ops::Not::not")]
+                b: bool,
+            }
+
+            #[derive(Serialize)]
+            struct TestStruct {
+                inner: Skipped,
+                list: Vec<Skipped>,
+            }
+
+            // Same as an empty struct, at the root and nested
+            let output = to_string(&Skipped { a: None, b: false }).unwrap();
+            assert_eq!(output, "\n");
+
+            let output = to_string(&TestStruct {
+                inner: Skipped { a: None, b: false },
+                list: vec![Skipped { a: None, b: false }],
+            })
+            .unwrap();
+            assert_eq!(
+                output,
+                "\
+inner {
+}
+list [
+    {
+    }
+]
+"
+            );
+        }
+
+        #[test]
+        fn test_struct_some_skipped() {
+            #[derive(Serialize)]
+            struct TestStruct {
+                #[serde(skip_serializing_if = "Option::is_none")]
+                a: Option<f64>,
+                b: f64,
+                #[serde(skip_serializing_if = "Option::is_none")]
+                c: Option<f64>,
+                #[serde(skip_serializing_if = "Option::is_none")]
+                d: Option<f64>,
+            }
+
+            let output = to_string(&TestStruct {
+                a: None,
+                b: 1.0,
+                c: None,
+                d: Some(2.0),
+            })
+            .unwrap();
+
+            assert_eq!(output, "b 1.0\nd 2.0\n");
+        }
+
         #[test]
         fn test_struct_newtype_string() {
             #[derive(Serialize)]
@@ -978,7 +3674,7 @@ inner2 {
 
             let output = to_string(&TestStruct(Inner(100))).unwrap();
 
-            assert_eq!(output, "100.0\n");
+            assert_eq!(output, "100\n");
         }
 
         #[test]
@@ -1045,7 +3741,7 @@ boolean true
                 output,
                 "\
 [
-    10.0
+    10
     false
 ]
 "
@@ -1091,7 +3787,7 @@ boolean true
                 "\
 boolean true
 number 10.0
-int_number 100.0
+int_number 100
 string \"hello, world!\"
 unit null
 "
@@ -1132,16 +3828,86 @@ unit null
                 "\
 boolean true
 number 10.0
-int_number 2.0
+int_number 2
 string \"hello world!\"
 inner {
     num 10.1
     vec [
-        1.0
-        2.0
-        3.0
+        1
+        2
+        3
+    ]
+}
+"
+            );
+        }
+
+        #[test]
+        fn test_struct_list_of_structs() {
+            #[derive(Serialize)]
+            struct Leaf {
+                value: f64,
+            }
+
+            #[derive(Serialize)]
+            struct Branch {
+                leaves: Vec<Leaf>,
+            }
+
+            #[derive(Serialize)]
+            struct Tree {
+                branch: Branch,
+                lists: Vec<Vec<Leaf>>,
+            }
+
+            let output = to_string(&Tree {
+                branch: Branch {
+                    leaves: vec![Leaf { value: 1.0 }, Leaf { value: 2.0 }],
+                },
+                lists: vec![vec![Leaf { value: 3.0 }]],
+            })
+            .unwrap();
+
+            assert_eq!(
+                output,
+                "\
+branch {
+    leaves [
+        {
+            value 1.0
+        }
+        {
+            value 2.0
+        }
     ]
 }
+lists [
+    [
+        {
+            value 3.0
+        }
+    ]
+]
+"
+            );
+
+            let output = to_string(&vec![Branch {
+                leaves: vec![Leaf { value: 1.0 }],
+            }])
+            .unwrap();
+
+            assert_eq!(
+                output,
+                "\
+[
+    {
+        leaves [
+            {
+                value 1.0
+            }
+        ]
+    }
+]
 "
             );
         }
@@ -1185,12 +3951,12 @@ map {
     hello \"world\"
 }
 array [
-    1.0
-    2.0
-    3.0
+    1
+    2
+    3
 ]
 inner {
-    my_int 100.0
+    my_int 100
     my_float 50.0
 }
 "
@@ -1223,7 +3989,7 @@ inner {
 
             let output = to_string(&TestEnum::Variant(10)).unwrap();
 
-            assert_eq!(output, "Variant 10.0\n");
+            assert_eq!(output, "Variant 10\n");
         }
 
         #[test]
@@ -1239,13 +4005,105 @@ inner {
                 output,
                 "\
 MultiVariant [
-    100.0
+    100
     false
 ]
 "
             )
         }
 
+        #[test]
+        fn test_enum_variants_in_list() {
+            #[derive(Serialize, serde::Deserialize, Debug, PartialEq)]
+            enum TestEnum {
+                Struct { a: f64, b: bool },
+                Tuple(f64, f64),
+                Unit,
+            }
+
+            let data = vec![
+                TestEnum::Struct { a: 1.0, b: true },
+                TestEnum::Tuple(2.0, 3.0),
+                TestEnum::Unit,
+                TestEnum::Struct { a: 4.0, b: false },
+            ];
+
+            let output = to_string(&data).unwrap();
+
+            assert_eq!(
+                output,
+                "\
+[
+    {
+        Struct {
+            a 1.0
+            b true
+        }
+    }
+    {
+        Tuple [
+            2.0
+            3.0
+        ]
+    }
+    \"Unit\"
+    {
+        Struct {
+            a 4.0
+            b false
+        }
+    }
+]
+"
+            );
+            assert_eq!(crate::from_str::<Vec<TestEnum>>(&output).unwrap(), data);
+        }
+
+        #[test]
+        fn test_enum_result() {
+            #[derive(Serialize, serde::Deserialize, Debug, PartialEq)]
+            struct Test {
+                ok: Result<i32, String>,
+                err: Result<i32, String>,
+            }
+
+            for (data, expected) in [
+                (Ok(-3), "Ok -3\n"),
+                (Err("bad".to_string()), "Err \"bad\"\n"),
+            ] {
+                let output = to_string(&data).unwrap();
+                assert_eq!(output, expected);
+                assert_eq!(
+                    crate::from_str::<Result<i32, String>>(&output).unwrap(),
+                    data
+                );
+            }
+
+            let data = vec![Test {
+                ok: Ok(1),
+                err: Err("bad".to_string()),
+            }];
+
+            let output = to_string(&data).unwrap();
+
+            assert_eq!(
+                output,
+                "\
+[
+    {
+        ok {
+            Ok 1
+        }
+        err {
+            Err \"bad\"
+        }
+    }
+]
+"
+            );
+            assert_eq!(crate::from_str::<Vec<Test>>(&output).unwrap(), data);
+        }
+
         #[test]
         fn test_enum_nested_enum_newtype() {
             #[derive(Serialize)]
@@ -1363,7 +4221,7 @@ Inner {
                 output,
                 "\
 MultiVariant [
-    100.0
+    100
     {
         Variant [
             \"Unit\"
@@ -1393,7 +4251,7 @@ MultiVariant [
                 output,
                 "\
 Tuple [
-    100.0
+    100
     false
 ]
 "
@@ -1419,20 +4277,300 @@ Tuple [
                 output,
                 "\
 val1 {
-    Num 10.0
+    Num 10
 }
 val2 {
-    Num 20.0
+    Num 20
 }
 val3 {
     Tuple [
-        10.0
-        20.0
+        10
+        20
     ]
 }
 "
             );
         }
+
+        #[test]
+        fn test_newtype_variant_of_unit() {
+            #[derive(Serialize, serde::Deserialize, Debug, PartialEq)]
+            struct Marker;
+
+            #[derive(Serialize, serde::Deserialize, Debug, PartialEq)]
+            struct Settings {
+                port: u32,
+            }
+
+            #[derive(Serialize, serde::Deserialize, Debug, PartialEq)]
+            enum Event {
+                Empty(()),
+                Marked(Marker),
+                Maybe(Option<u32>),
+                Configured(Settings),
+                Pair(u32, u32),
+            }
+
+            #[derive(Serialize, serde::Deserialize, Debug, PartialEq)]
+            struct Test {
+                empty: Event,
+                events: Vec<Event>,
+            }
+
+            let data = Test {
+                empty: Event::Empty(()),
+                events: vec![
+                    Event::Marked(Marker),
+                    Event::Maybe(None),
+                    Event::Maybe(Some(1)),
+                    Event::Configured(Settings { port: 80 }),
+                ],
+            };
+
+            let output = to_string(&data).unwrap();
+            assert_eq!(
+                output,
+                "\
+empty \"Empty\"
+events [
+    \"Marked\"
+    \"Maybe\"
+    {
+        Maybe 1
+    }
+    {
+        Configured {
+            port 80
+        }
+    }
+]
+"
+            );
+            assert_eq!(crate::from_str::<Test>(&output).unwrap(), data);
+            assert!(crate::from_str::<Event>("\"Pair\"").is_err());
+        }
+
+        #[test]
+        fn test_struct_field_keys() {
+            #[derive(Serialize)]
+            enum Variant {
+                Fields { inner_value: u32 },
+            }
+
+            #[derive(Serialize)]
+            struct Test {
+                plain: u32,
+                snake_case_2: bool,
+                #[serde(rename = "with space")]
+                with_space: u32,
+                #[serde(rename = "kebab-case")]
+                kebab_case: u32,
+                #[serde(rename = "")]
+                empty: u32,
+                variant: Variant,
+            }
+
+            let data = Test {
+                plain: 1,
+                snake_case_2: true,
+                with_space: 2,
+                kebab_case: 3,
+                empty: 4,
+                variant: Variant::Fields { inner_value: 5 },
+            };
+
+            assert_eq!(
+                to_string(&data).unwrap(),
+                "\
+plain 1
+snake_case_2 true
+\"with space\" 2
+kebab-case 3
+\"\" 4
+variant {
+    Fields {
+        inner_value 5
+    }
+}
+"
+            );
+        }
+
+        #[test]
+        fn test_key_line_writes() {
+            /// Keeps every write separately.
+            #[derive(Default)]
+            struct Writes(Vec<Vec<u8>>);
+
+            impl std::io::Write for Writes {
+                fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                    self.0.push(buf.to_vec());
+                    Ok(buf.len())
+                }
+
+                fn flush(&mut self) -> std::io::Result<()> {
+                    Ok(())
+                }
+            }
+
+            #[derive(Serialize)]
+            struct Inner {
+                value: u32,
+                #[serde(rename = "quoted key")]
+                quoted: u32,
+            }
+
+            #[derive(Serialize)]
+            struct Test {
+                inner: Inner,
+                list: Vec<Inner>,
+            }
+
+            let data = Test {
+                inner: Inner {
+                    value: 1,
+                    quoted: 2,
+                },
+                list: vec![Inner {
+                    value: 3,
+                    quoted: 4,
+                }],
+            };
+
+            /// Only the required methods, so every line start is written by the trait's
+            /// defaults, one piece at a time.
+            #[derive(Default)]
+            struct Unbatched {
+                depth: usize,
+                root_type: Option<RootType>,
+            }
+
+            impl Formatter for Unbatched {
+                fn indent(&mut self) {
+                    self.depth += 1;
+                }
+
+                fn unindent(&mut self) {
+                    self.depth -= 1;
+                }
+
+                fn get_indent(&self) -> usize {
+                    self.depth
+                }
+
+                fn is_root_type_set(&self) -> bool {
+                    self.root_type.is_some()
+                }
+
+                fn set_root_type(&mut self, root_type: RootType) {
+                    self.root_type = Some(root_type);
+                }
+            }
+
+            let mut writes = Writes::default();
+            data.serialize(&mut Serializer::new(&mut writes)).unwrap();
+            let mut unbatched = Writes::default();
+            data.serialize(&mut Serializer::with_formatter(
+                &mut unbatched,
+                Unbatched::default(),
+            ))
+            .unwrap();
+
+            assert_eq!(writes.0.concat(), to_string(&data).unwrap().as_bytes());
+            assert_eq!(writes.0.concat(), unbatched.0.concat());
+            assert!(writes.0.contains(&b"    value ".to_vec()));
+            assert!(writes.0.contains(&b"        value ".to_vec()));
+            assert!(unbatched.0.contains(&b"value".to_vec()));
+
+            // A bare key line start at depth `d` is one write instead of `d` indents, the key
+            // and the space, which saves 1 for `inner` and `list`, 2 for the first `value` and 3
+            // for the nested one. An indent alone at depth 2 or more saves a write per extra
+            // level, which here only the nested quoted key's is, saving 1.
+            assert_eq!(unbatched.0.len() - writes.0.len(), 1 + 1 + 2 + 3 + 1);
+
+            // Too long to batch, but still written the same
+            let key = "k".repeat(200);
+            let mut writes = Writes::default();
+            BTreeMap::from([(key.as_str(), 1)])
+                .serialize(&mut Serializer::new(&mut writes))
+                .unwrap();
+            assert_eq!(writes.0.concat(), format!("{key} 1\n").as_bytes());
+        }
+
+        #[test]
+        fn test_map_with_enum_keys() {
+            #[derive(Serialize, serde::Deserialize, Debug, PartialEq, Eq, PartialOrd, Ord)]
+            enum Color {
+                Red,
+                Green,
+            }
+
+            let map = BTreeMap::from([(Color::Red, 1), (Color::Green, 2)]);
+
+            let output = to_string(&map).unwrap();
+            assert_eq!(output, "Red 1\nGreen 2\n");
+            assert_eq!(
+                crate::from_str::<BTreeMap<Color, i32>>(&output).unwrap(),
+                map
+            );
+
+            let nested = BTreeMap::from([("colors", map)]);
+            assert_eq!(
+                to_string(&nested).unwrap(),
+                "colors {\n    Red 1\n    Green 2\n}\n"
+            );
+
+            assert_eq!(
+                crate::from_str::<BTreeMap<Color, i32>>("\"Green\" 3").unwrap(),
+                BTreeMap::from([(Color::Green, 3)])
+            );
+            assert!(crate::from_str::<BTreeMap<Color, i32>>("Blue 3").is_err());
+        }
+
+        #[test]
+        fn test_map_enum_matches_struct_field() {
+            #[derive(Serialize, serde::Deserialize, Debug, PartialEq)]
+            enum Value {
+                Unit,
+                Num(u32),
+                Pair(u32, u32),
+                Named { a: u32 },
+            }
+
+            #[derive(Serialize)]
+            struct Fields {
+                unit: Value,
+                num: Value,
+                pair: Value,
+                named: Value,
+            }
+
+            let fields = Fields {
+                unit: Value::Unit,
+                num: Value::Num(1),
+                pair: Value::Pair(2, 3),
+                named: Value::Named { a: 4 },
+            };
+            let map = BTreeMap::from([
+                ("unit", Value::Unit),
+                ("num", Value::Num(1)),
+                ("pair", Value::Pair(2, 3)),
+                ("named", Value::Named { a: 4 }),
+            ]);
+
+            // A variant with data needs its name as a key of its own so it can be read back, so a
+            // map value is wrapped in a dict just like a struct field
+            let output = to_string(&map).unwrap();
+            assert_eq!(
+                crate::parser::parse(&output).unwrap(),
+                crate::parser::parse(&to_string(&fields).unwrap()).unwrap()
+            );
+            assert!(output.contains("num {\n    Num 1\n}\n"));
+            assert_eq!(
+                crate::from_str::<BTreeMap<String, Value>>(&output).unwrap(),
+                map.into_iter().map(|(k, v)| (k.to_string(), v)).collect()
+            );
+        }
     }
 
     #[test]
@@ -1496,7 +4634,7 @@ inner {
 }
 enum_unit \"Unit\"
 enum_var_prim {
-    TupleVariantPrimitive 22.0
+    TupleVariantPrimitive 22
 }
 enum_var_stru {
     TupleVariantStruct {
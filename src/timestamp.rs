@@ -0,0 +1,268 @@
+/*!
+Serialize a [`SystemTime`] as an RFC 3339 timestamp string.
+
+Use this module with `#[serde(with = "tot::timestamp")]`. Timestamps are written in UTC, like
+`"2023-07-17T12:00:00Z"`, with as many fractional digits as needed. Any UTC offset is accepted
+when reading them back.
+
+```
+use std::time::{Duration, SystemTime};
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize)]
+struct Release {
+    #[serde(with = "tot::timestamp")]
+    date: SystemTime,
+}
+
+let release = Release {
+    date: SystemTime::UNIX_EPOCH + Duration::from_secs(1_689_595_200),
+};
+
+let output = tot::to_string(&release).unwrap();
+assert_eq!(output, "date \"2023-07-17T12:00:00Z\"\n");
+
+let release = tot::from_str::<Release>("date \"2023-07-17T14:00:00+02:00\"").unwrap();
+assert_eq!(
+    release.date,
+    SystemTime::UNIX_EPOCH + Duration::from_secs(1_689_595_200)
+);
+```
+*/
+
+use std::time::{Duration, SystemTime};
+
+use serde::{de, Deserialize, Deserializer, Serializer};
+
+const SECONDS_PER_DAY: i64 = 86_400;
+
+pub fn serialize<S>(value: &SystemTime, serializer: S) -> std::result::Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    let (secs, nanos) = match value.duration_since(SystemTime::UNIX_EPOCH) {
+        Ok(d) => (d.as_secs() as i64, d.subsec_nanos()),
+        Err(e) => {
+            let d = e.duration();
+            match d.subsec_nanos() {
+                0 => (-(d.as_secs() as i64), 0),
+                nanos => (-(d.as_secs() as i64) - 1, 1_000_000_000 - nanos),
+            }
+        }
+    };
+
+    serializer.serialize_str(&format(secs, nanos))
+}
+
+pub fn deserialize<'de, D>(deserializer: D) -> std::result::Result<SystemTime, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s = String::deserialize(deserializer)?;
+    let (secs, nanos) =
+        parse(&s).ok_or_else(|| de::Error::custom(format!("invalid RFC 3339 timestamp `{s}`")))?;
+
+    let since_epoch = Duration::new(secs.unsigned_abs(), 0);
+    let time = if secs >= 0 {
+        SystemTime::UNIX_EPOCH.checked_add(since_epoch)
+    } else {
+        SystemTime::UNIX_EPOCH.checked_sub(since_epoch)
+    };
+
+    time.and_then(|t| t.checked_add(Duration::from_nanos(nanos.into())))
+        .ok_or_else(|| de::Error::custom(format!("timestamp `{s}` is out of range")))
+}
+
+/// Format seconds and nanoseconds since the Unix epoch as a UTC timestamp.
+fn format(secs: i64, nanos: u32) -> String {
+    let (year, month, day) = civil_from_days(secs.div_euclid(SECONDS_PER_DAY));
+    let time = secs.rem_euclid(SECONDS_PER_DAY);
+
+    let mut s = format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}",
+        time / 3600,
+        time / 60 % 60,
+        time % 60
+    );
+    if nanos > 0 {
+        s.push_str(format!(".{nanos:09}").trim_end_matches('0'));
+    }
+    s.push('Z');
+
+    s
+}
+
+/// Parse a timestamp into seconds and nanoseconds since the Unix epoch.
+fn parse(s: &str) -> Option<(i64, u32)> {
+    fn number(s: &str, range: std::ops::Range<usize>) -> Option<i64> {
+        let digits = s.get(range)?;
+        if digits.bytes().all(|b| b.is_ascii_digit()) {
+            digits.parse().ok()
+        } else {
+            None
+        }
+    }
+    let separator = |i: usize, expected: &[char]| -> Option<()> {
+        s.get(i..)?.starts_with(expected).then_some(())
+    };
+
+    let year = number(s, 0..4)?;
+    separator(4, &['-'])?;
+    let month = number(s, 5..7)?;
+    separator(7, &['-'])?;
+    let day = number(s, 8..10)?;
+    separator(10, &['T', 't'])?;
+    let hour = number(s, 11..13)?;
+    separator(13, &[':'])?;
+    let minute = number(s, 14..16)?;
+    separator(16, &[':'])?;
+    let second = number(s, 17..19)?;
+
+    if !(1..=12).contains(&month)
+        || day < 1
+        || day > days_in_month(year, month)
+        || hour > 23
+        || minute > 59
+        || second > 59
+    {
+        return None;
+    }
+
+    let mut rest = &s[19..];
+    let mut nanos = 0;
+    if let Some(fraction) = rest.strip_prefix('.') {
+        let len = fraction.bytes().take_while(u8::is_ascii_digit).count();
+        if len == 0 {
+            return None;
+        }
+        // Digits past nanoseconds are truncated
+        let digits = &fraction[..len.min(9)];
+        nanos = digits.parse::<u32>().ok()? * 10u32.pow(9 - digits.len() as u32);
+        rest = &fraction[len..];
+    }
+
+    let offset = match rest {
+        "Z" | "z" => 0,
+        _ => {
+            let sign = match rest.get(..1)? {
+                "+" => 1,
+                "-" => -1,
+                _ => return None,
+            };
+            let offset_hour = number(rest, 1..3)?;
+            let offset_minute = number(rest, 4..6)?;
+            if rest.len() != 6 || &rest[3..4] != ":" || offset_hour > 23 || offset_minute > 59 {
+                return None;
+            }
+            sign * (offset_hour * 3600 + offset_minute * 60)
+        }
+    };
+
+    let secs =
+        days_from_civil(year, month, day) * SECONDS_PER_DAY + hour * 3600 + minute * 60 + second
+            - offset;
+
+    Some((secs, nanos))
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+// Conversions between days since the Unix epoch and dates, from
+// https://howardhinnant.github.io/date_algorithms.html
+
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    era * 146_097 + day_of_era - 719_468
+}
+
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400;
+
+    (if month <= 2 { year + 1 } else { year }, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime};
+
+    use serde::{Deserialize, Serialize};
+
+    use super::{format, parse};
+    use crate::{from_str, to_string};
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Test {
+        #[serde(with = "super")]
+        time: SystemTime,
+    }
+
+    #[test]
+    fn test_format() {
+        assert_eq!(format(0, 0), "1970-01-01T00:00:00Z");
+        assert_eq!(format(1_689_595_200, 0), "2023-07-17T12:00:00Z");
+        assert_eq!(format(951_786_123, 500_000_000), "2000-02-29T01:02:03.5Z");
+        assert_eq!(format(-1, 1), "1969-12-31T23:59:59.000000001Z");
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(parse("1970-01-01T00:00:00Z"), Some((0, 0)));
+        assert_eq!(parse("2023-07-17t12:00:00z"), Some((1_689_595_200, 0)));
+        assert_eq!(parse("2023-07-17T07:30:00-04:30"), Some((1_689_595_200, 0)));
+        assert_eq!(
+            parse("2000-02-29T01:02:03.123456789123Z"),
+            Some((951_786_123, 123_456_789))
+        );
+        assert_eq!(parse("1969-12-31T23:59:59.25Z"), Some((-1, 250_000_000)));
+
+        assert_eq!(parse("2023-02-29T00:00:00Z"), None);
+        assert_eq!(parse("2023-07-17T24:00:00Z"), None);
+        assert_eq!(parse("2023-07-17T12:00:00"), None);
+        assert_eq!(parse("2023-07-17T12:00:00.Z"), None);
+        assert_eq!(parse("2023-07-17T12:00:00+0200"), None);
+        assert_eq!(parse("2023-7-17T12:00:00Z"), None);
+        assert_eq!(parse(""), None);
+    }
+
+    #[test]
+    fn test_timestamp() {
+        for time in [
+            SystemTime::UNIX_EPOCH,
+            SystemTime::UNIX_EPOCH + Duration::new(1_689_595_200, 120_000_000),
+            SystemTime::UNIX_EPOCH - Duration::new(86_400 * 365, 1),
+        ] {
+            let data = Test { time };
+            let output = to_string(&data).unwrap();
+            assert_eq!(from_str::<Test>(&output).unwrap(), data);
+        }
+
+        assert_eq!(
+            from_str::<Test>("time \"yesterday\"")
+                .unwrap_err()
+                .to_string(),
+            "serde error: time: invalid RFC 3339 timestamp `yesterday`"
+        );
+    }
+}
@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use tot::{from_str, to_string};
 
 #[test]
@@ -21,7 +22,7 @@ fn test_simple_struct() {
         output,
         "\
 boolean true
-integer 22.0
+integer 22
 string \"hello world\"
 "
     );
@@ -75,3 +76,78 @@ fields {
     assert_eq!(output.fields.key2, data.fields.key2);
     assert_eq!(output.fields.key3, data.fields.key3);
 }
+
+#[test]
+fn test_renamed_keys() {
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    #[serde(rename_all = "kebab-case")]
+    struct Data {
+        my_field: bool,
+        #[serde(rename = "server.host")]
+        host: String,
+        #[serde(rename = "display name")]
+        name: String,
+    }
+
+    let data = Data {
+        my_field: true,
+        host: "localhost".to_string(),
+        name: "Tim".to_string(),
+    };
+
+    let output = to_string(&data).unwrap();
+    assert_eq!(
+        output,
+        "\
+my-field true
+server.host \"localhost\"
+\"display name\" \"Tim\"
+"
+    );
+
+    assert_eq!(from_str::<Data>(output.as_str()).unwrap(), data);
+}
+
+#[test]
+fn test_empty_maps() {
+    type Map = BTreeMap<String, BTreeMap<String, i32>>;
+
+    let output = to_string(&Map::new()).unwrap();
+    assert_eq!(output, "\n");
+    assert_eq!(from_str::<Map>(output.as_str()).unwrap(), Map::new());
+
+    let mut data = Map::new();
+    data.insert("inner".to_string(), BTreeMap::new());
+
+    let output = to_string(&data).unwrap();
+    assert_eq!(output, "inner {\n}\n");
+    assert_eq!(from_str::<Map>(output.as_str()).unwrap(), data);
+}
+
+#[test]
+fn test_float_edge_cases() {
+    #[derive(Serialize, Deserialize)]
+    struct Data {
+        zero: f64,
+        tiny: f64,
+    }
+
+    let data = Data {
+        zero: -0.0,
+        tiny: 5e-324,
+    };
+
+    let output = to_string(&data).unwrap();
+    assert_eq!(
+        output,
+        "\
+zero -0.0
+tiny 5e-324
+"
+    );
+
+    let output = from_str::<Data>(output.as_str()).unwrap();
+    assert!(output.zero == 0.0 && output.zero.is_sign_negative());
+    assert_eq!(output.tiny, 5e-324);
+    assert!(output.tiny.is_subnormal());
+}